@@ -0,0 +1,91 @@
+//! Stores a session token on a client and then transfers it to another server.
+//!
+//! A fake client talks to a `Connection` over an in-memory stream: it logs in,
+//! and once the connection is in the Configuration state the server stores a
+//! cookie and sends a Transfer. The next server reads the cookie back with a
+//! Cookie Request, which is how proxies keep a session across servers.
+//!
+//! Run with `cargo run --example cookie_transfer`.
+
+use bytes::{BufMut, BytesMut};
+use octavia::config::ServerConfig;
+use octavia::connection::{Connection, PROTOCOL_VERSION};
+use octavia::packet::reader::PacketReader;
+use octavia::world::SharedWorld;
+use octavia::Result;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+/// Length-prefixes packet content like a client would
+fn frame(content: &[u8]) -> BytesMut {
+    let mut packet = BytesMut::new();
+    PacketReader::write_varint(&mut packet, content.len() as i32);
+    packet.extend_from_slice(content);
+    packet
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let (mut client, server) = tokio::io::duplex(64 * 1024);
+    let config = ServerConfig {
+        compression_threshold: None,
+        ..Default::default()
+    };
+    let mut connection = Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+
+    // Handshake asking to log in
+    let mut handshake = BytesMut::new();
+    PacketReader::write_varint(&mut handshake, 0x00);
+    PacketReader::write_varint(&mut handshake, PROTOCOL_VERSION);
+    PacketReader::write_string(&mut handshake, "lobby.example.com");
+    handshake.put_u16(25565);
+    PacketReader::write_varint(&mut handshake, 2);
+
+    // Login Start
+    let mut login_start = BytesMut::new();
+    PacketReader::write_varint(&mut login_start, 0x00);
+    PacketReader::write_string(&mut login_start, "Steve");
+    login_start.put_u128(0);
+
+    // Login Acknowledged
+    let login_acknowledged = [0x03];
+
+    for content in [&handshake[..], &login_start[..], &login_acknowledged[..]] {
+        connection.handle_packet(&mut frame(content)).await?;
+    }
+
+    // the client is in Configuration now, hand it a token and send it on its way
+    connection
+        .store_cookie("octavia:session", b"token-1234")
+        .await?;
+    connection.transfer("game.example.com", 25566).await?;
+    connection.flush().await?;
+    drop(connection);
+
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).await?;
+
+    let mut buf = &received[..];
+    while !buf.is_empty() {
+        let length = PacketReader::read_varint(&mut buf)? as usize;
+        let (mut packet, rest) = buf.split_at(length);
+        buf = rest;
+
+        match PacketReader::read_varint(&mut packet)? {
+            0x0A => {
+                let key = PacketReader::read_string(&mut packet)?;
+                let length = PacketReader::read_varint(&mut packet)? as usize;
+                let payload = String::from_utf8_lossy(&packet[..length]);
+                println!("Store Cookie  {key} = {payload}");
+            }
+            0x0B => {
+                let host = PacketReader::read_string(&mut packet)?;
+                let port = PacketReader::read_varint(&mut packet)?;
+                println!("Transfer      {host}:{port}");
+            }
+            packet_id => println!("packet 0x{packet_id:02X} ({length} bytes)"),
+        }
+    }
+
+    Ok(())
+}
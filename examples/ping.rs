@@ -0,0 +1,46 @@
+//! Pings a server like the multiplayer screen does and prints its status.
+//!
+//! Run with `cargo run --example ping -- play.example.com:25565`, the port
+//! defaults to 25565. Exits with a non-zero code when the server can't be
+//! reached or doesn't answer the way a server should.
+
+use octavia::status;
+use std::process::ExitCode;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let Some(address) = std::env::args().nth(1) else {
+        eprintln!("usage: ping <host[:port]>");
+        return ExitCode::from(2);
+    };
+
+    match status::ping(&address).await {
+        Ok(status) => {
+            println!("MOTD     {}", status.motd());
+            println!(
+                "Version  {} (protocol {})",
+                status.version_name, status.protocol
+            );
+            println!("Players  {}/{}", status.online_players, status.max_players);
+            println!("Latency  {} ms", status.latency.as_millis());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to ping {address}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // needs the server running locally, `cargo run` in another terminal
+    #[tokio::test]
+    #[ignore]
+    async fn test_ping_local_server() {
+        let status = status::ping("127.0.0.1:25565").await.unwrap();
+        assert_eq!(status.protocol, octavia::connection::PROTOCOL_VERSION);
+    }
+}
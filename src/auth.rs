@@ -0,0 +1,239 @@
+//! Online-mode authentication against Mojang's session server
+
+use crate::error::{MinecraftError, Result};
+use crate::player::ProfileProperty;
+use md5::{Digest, Md5};
+use rsa::pkcs8::EncodePublicKey;
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+use serde::Deserialize;
+use sha1::Sha1;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+pub const MOJANG_SESSION_SERVER: &str = "https://sessionserver.mojang.com";
+
+/// The server's RSA key pair, the client encrypts the shared secret with it
+pub struct ServerKey {
+    private_key: RsaPrivateKey,
+    /// Public key as a DER encoded SubjectPublicKeyInfo, sent in Encryption Request
+    pub public_key_der: Vec<u8>,
+}
+
+impl ServerKey {
+    /// Vanilla generates a 1024 bit key once per server start
+    pub fn get() -> &'static ServerKey {
+        static KEY: OnceLock<ServerKey> = OnceLock::new();
+        KEY.get_or_init(|| {
+            let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)
+                .expect("failed to generate RSA key");
+            let public_key_der = private_key
+                .to_public_key()
+                .to_public_key_der()
+                .expect("failed to encode RSA public key")
+                .into_vec();
+
+            ServerKey {
+                private_key,
+                public_key_der,
+            }
+        })
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.private_key
+            .decrypt(Pkcs1v15Encrypt, data)
+            .map_err(|e| MinecraftError::Protocol(format!("Failed to decrypt: {e}")))
+    }
+}
+
+/// A player's profile as confirmed by the session server
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameProfile {
+    pub id: Uuid,
+    pub name: String,
+    pub properties: Vec<ProfileProperty>,
+}
+
+#[derive(Deserialize)]
+struct SessionProfile {
+    id: String,
+    name: String,
+    #[serde(default)]
+    properties: Vec<SessionProperty>,
+}
+
+#[derive(Deserialize)]
+struct SessionProperty {
+    name: String,
+    value: String,
+    signature: Option<String>,
+}
+
+/// UUID of an offline-mode player, derived from the username like vanilla does
+pub fn offline_uuid(username: &str) -> Uuid {
+    let hash = Md5::digest(format!("OfflinePlayer:{username}"));
+    uuid::Builder::from_md5_bytes(hash.into()).into_uuid()
+}
+
+/// SHA-1 of `parts` formatted the way Minecraft does: as a signed
+/// big-endian number in hex, with a leading '-' when negative
+pub fn mc_sha1_hex(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha1::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut hash: [u8; 20] = hasher.finalize().into();
+
+    let negative = hash[0] & 0x80 != 0;
+    if negative {
+        // two's complement, so the digits are of the absolute value
+        let mut carry = true;
+        for byte in hash.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                let (value, overflow) = byte.overflowing_add(1);
+                *byte = value;
+                carry = overflow;
+            }
+        }
+    }
+
+    let hex: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+    let hex = hex.trim_start_matches('0');
+    let hex = if hex.is_empty() { "0" } else { hex };
+    if negative {
+        format!("-{hex}")
+    } else {
+        hex.to_string()
+    }
+}
+
+/// Asks the session server whether `username` joined with our server hash,
+/// returns the player's profile when they did
+pub async fn has_joined(
+    session_server: &str,
+    username: &str,
+    shared_secret: &[u8],
+    public_key: &[u8],
+) -> Result<Option<GameProfile>> {
+    // the server id is always empty since 1.7
+    let hash = mc_sha1_hex(&[b"", shared_secret, public_key]);
+    let url = format!("{session_server}/session/minecraft/hasJoined");
+
+    let response = reqwest::Client::new()
+        .get(url)
+        .query(&[("username", username), ("serverId", &hash)])
+        .send()
+        .await
+        .map_err(|e| MinecraftError::Protocol(format!("Session server request failed: {e}")))?;
+
+    // 204 No Content means the player didn't authenticate
+    if response.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+
+    let profile: SessionProfile = response
+        .error_for_status()
+        .map_err(|e| MinecraftError::Protocol(format!("Session server error: {e}")))?
+        .json()
+        .await
+        .map_err(|e| MinecraftError::Protocol(format!("Invalid session server response: {e}")))?;
+
+    let id = Uuid::parse_str(&profile.id)
+        .map_err(|e| MinecraftError::Protocol(format!("Invalid profile id: {e}")))?;
+    Ok(Some(GameProfile {
+        id,
+        name: profile.name,
+        properties: profile
+            .properties
+            .into_iter()
+            .map(|property| ProfileProperty {
+                name: property.name,
+                value: property.value,
+                signature: property.signature,
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_mc_sha1_hex() {
+        assert_eq!(
+            mc_sha1_hex(&[b"Notch"]),
+            "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48"
+        );
+        assert_eq!(
+            mc_sha1_hex(&[b"jeb_"]),
+            "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1"
+        );
+        assert_eq!(
+            mc_sha1_hex(&[b"simon"]),
+            "88e16a1019277b15d58faf0541e11910eb756f6"
+        );
+        // da39a3ee... as a signed number
+        assert_eq!(
+            mc_sha1_hex(&[b""]),
+            "-25c65c11a194b4f2cdaa40106a9fe76f5027f8f7"
+        );
+        assert_eq!(mc_sha1_hex(&[b"No", b"tch"]), mc_sha1_hex(&[b"Notch"]));
+    }
+
+    #[test]
+    fn test_offline_uuid() {
+        assert_eq!(
+            offline_uuid("Notch"),
+            Uuid::parse_str("b50ad385-829d-3141-a216-7e7d7539ba7f").unwrap()
+        );
+        assert_ne!(offline_uuid("Notch"), offline_uuid("notch"));
+    }
+
+    #[tokio::test]
+    async fn test_has_joined_parses_profile() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let session_server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let n = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_string();
+
+            let body = r#"{
+                "id": "069a79f444e94726a5befca90e38aaf5",
+                "name": "Notch",
+                "properties": [{ "name": "textures", "value": "e30=", "signature": "c2ln" }]
+            }"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request
+        });
+
+        let profile = has_joined(&format!("http://{address}"), "Notch", &[1; 16], b"key")
+            .await
+            .unwrap();
+        let request = session_server.await.unwrap();
+
+        assert!(request.starts_with("GET /session/minecraft/hasJoined?username=Notch&serverId="));
+        assert_eq!(
+            profile,
+            Some(GameProfile {
+                id: Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap(),
+                name: "Notch".to_string(),
+                properties: vec![ProfileProperty {
+                    name: "textures".to_string(),
+                    value: "e30=".to_string(),
+                    signature: Some("c2ln".to_string()),
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Commands players run with '/', and the Declare Commands graph that lets
+//! clients suggest and validate them
+
+use crate::packet::reader::PacketReader;
+use crate::text::Component;
+use crate::world::SharedWorld;
+use bytes::{BufMut, BytesMut};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub const DECLARE_COMMANDS_PACKET_ID: i32 = 0x11;
+
+// Declare Commands node flags
+const ROOT_NODE: u8 = 0x00;
+const LITERAL_NODE: u8 = 0x01;
+const ARGUMENT_NODE: u8 = 0x02;
+const EXECUTABLE: u8 = 0x04;
+
+// brigadier:string, with the greedy phrase behavior taking the rest of the line
+const STRING_PARSER: i32 = 5;
+const GREEDY_PHRASE: i32 = 2;
+
+/// What a command handler gets to know about who ran it
+pub struct CommandContext<'a> {
+    /// Username of the player who ran the command
+    pub sender: &'a str,
+    pub world: &'a SharedWorld,
+    replies: Vec<Component>,
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn new(sender: &'a str, world: &'a SharedWorld) -> Self {
+        Self {
+            sender,
+            world,
+            replies: Vec::new(),
+        }
+    }
+
+    /// Shows `message` in the sender's chat once the handler returns
+    pub fn reply(&mut self, message: impl Into<Component>) {
+        self.replies.push(message.into());
+    }
+
+    pub fn into_replies(self) -> Vec<Component> {
+        self.replies
+    }
+}
+
+/// Runs a command, with the words after the command name as arguments
+pub type CommandHandler = Arc<dyn Fn(&mut CommandContext, &[&str]) + Send + Sync>;
+
+/// Commands by name, along with the Declare Commands packet describing them
+pub struct CommandRegistry {
+    commands: BTreeMap<String, CommandHandler>,
+    // rebuilt on every registration so joining players get it as is
+    declare_commands: BytesMut,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            commands: BTreeMap::new(),
+            declare_commands: BytesMut::new(),
+        };
+        registry.declare_commands = registry.write_declare_commands_packet();
+        registry
+    }
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a command, replacing any command with the same name. `name` is
+    /// given without the leading '/'.
+    pub fn register(&mut self, name: &str, handler: CommandHandler) {
+        self.commands.insert(name.to_string(), handler);
+        self.declare_commands = self.write_declare_commands_packet();
+    }
+
+    pub fn get(&self, name: &str) -> Option<CommandHandler> {
+        self.commands.get(name).cloned()
+    }
+
+    /// The Declare Commands packet for the commands registered so far
+    pub fn declare_commands_packet(&self) -> &BytesMut {
+        &self.declare_commands
+    }
+
+    // Packet ID             varint
+    // Count                 varint
+    // Nodes                 array of
+    //   Flags               byte
+    //   Children            varint array of node indices
+    //   Name                string (literal and argument nodes)
+    //   Parser ID           varint (argument nodes)
+    //   Properties          varies by parser
+    // Root Index            varint
+    //
+    // Every command is a literal under the root, executable on its own or
+    // followed by a greedy string argument
+    fn write_declare_commands_packet(&self) -> BytesMut {
+        let mut packet = BytesMut::new();
+        PacketReader::write_varint(&mut packet, DECLARE_COMMANDS_PACKET_ID);
+        PacketReader::write_varint(&mut packet, 1 + 2 * self.commands.len() as i32);
+
+        // the root comes first, each command's literal is followed by its argument
+        packet.put_u8(ROOT_NODE);
+        PacketReader::write_varint(&mut packet, self.commands.len() as i32);
+        for index in 0..self.commands.len() {
+            PacketReader::write_varint(&mut packet, 1 + 2 * index as i32);
+        }
+
+        for (index, name) in self.commands.keys().enumerate() {
+            let literal = 1 + 2 * index as i32;
+            packet.put_u8(LITERAL_NODE | EXECUTABLE);
+            PacketReader::write_varint(&mut packet, 1);
+            PacketReader::write_varint(&mut packet, literal + 1);
+            PacketReader::write_string(&mut packet, name);
+
+            packet.put_u8(ARGUMENT_NODE | EXECUTABLE);
+            PacketReader::write_varint(&mut packet, 0);
+            PacketReader::write_string(&mut packet, "args");
+            PacketReader::write_varint(&mut packet, STRING_PARSER);
+            PacketReader::write_varint(&mut packet, GREEDY_PHRASE);
+        }
+
+        PacketReader::write_varint(&mut packet, 0);
+        packet
+    }
+}
+
+/// Splits a command line, without its '/', into the command name and its arguments
+pub fn parse_command(input: &str) -> (&str, Vec<&str>) {
+    let mut words = input.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    (name, words.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("ping"), ("ping", vec![]));
+        assert_eq!(
+            parse_command("tell  Steve hello there"),
+            ("tell", vec!["Steve", "hello", "there"])
+        );
+        assert_eq!(parse_command(""), ("", vec![]));
+    }
+
+    #[test]
+    fn test_handler_replies() {
+        let mut registry = CommandRegistry::new();
+        registry.register("ping", Arc::new(|ctx, _args| ctx.reply("pong")));
+
+        let world = SharedWorld::new();
+        let mut ctx = CommandContext::new("Steve", &world);
+        let (name, args) = parse_command("ping");
+        registry.get(name).unwrap()(&mut ctx, &args);
+
+        assert_eq!(ctx.into_replies(), vec![Component::text("pong")]);
+        assert!(registry.get("pong").is_none());
+    }
+
+    #[test]
+    fn test_declare_commands_graph() {
+        let mut registry = CommandRegistry::new();
+        // only the root node, which is the root
+        assert_eq!(
+            &registry.declare_commands_packet()[..],
+            b"\x11\x01\x00\x00\x00"
+        );
+
+        registry.register("ping", Arc::new(|_, _| {}));
+        let mut buf = &registry.declare_commands_packet()[..];
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x11);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 3); // node count
+
+        // root, with the literal as its only child
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 0x00);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 1);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 1);
+
+        // executable literal "ping", with the argument as its only child
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 0x05);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 1);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 2);
+        assert_eq!(PacketReader::read_string(&mut buf).unwrap(), "ping");
+
+        // executable greedy string argument without children
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 0x06);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0);
+        assert_eq!(PacketReader::read_string(&mut buf).unwrap(), "args");
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 5);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 2);
+
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0); // root index
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::player::GameMode;
+use crate::text::Component;
+use crate::world::{Difficulty, Weather, WorldBorder};
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// zlib's own default, a balance between speed and size
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+/// Highest level zlib supports
+pub const MAX_COMPRESSION_LEVEL: u32 = 9;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub address: String,
+    /// Shown under the server name in the server list, see `Component::from_legacy`
+    /// for writing it with '&' color codes
+    pub motd: Component,
+    /// Connections open at once, counting those that never log in. New
+    /// connections are closed right away while the server is at the limit.
+    pub max_connections: usize,
+    /// Connections that don't finish the handshake in time are closed
+    pub handshake_timeout: Duration,
+    /// Expect a PROXY v2 header with the real client address before the handshake
+    pub proxy_protocol: bool,
+    /// Secret shared with a Velocity proxy, enables modern forwarding
+    pub velocity_secret: Option<String>,
+    /// Authenticate players with the session server and encrypt the connection
+    pub online_mode: bool,
+    /// Base url of the session server used in online mode
+    pub session_server: String,
+    /// Registry data sent during configuration, the built-in registries when `None`
+    pub registry_path: Option<PathBuf>,
+    /// Packets at least this big are compressed, `None` disables compression
+    pub compression_threshold: Option<usize>,
+    /// zlib level for compressed packets, from 0 (fastest, stored as is) to 9
+    /// (smallest). Lower levels trade bandwidth for CPU.
+    pub compression_level: u32,
+    /// Players get one life, the client shows hardcore hearts and no respawn button
+    pub hardcore: bool,
+    /// Hides coordinates and most other details from the F3 debug screen
+    pub reduced_debug_info: bool,
+    /// Show the death screen, without it players respawn right away
+    pub enable_respawn_screen: bool,
+    /// Game mode players join in
+    pub game_mode: GameMode,
+    pub difficulty: Difficulty,
+    /// Sent to players when they join
+    pub world_border: WorldBorder,
+    /// Weather the world starts with
+    pub weather: Weather,
+    /// Distance in chunks around a player that the client keeps loaded
+    pub view_distance: i32,
+    /// Distance in chunks around a player in which entities move and blocks tick
+    pub simulation_distance: i32,
+    /// World seed, clients only get its hash
+    pub seed: i64,
+    /// Only allow crafting recipes the player has unlocked
+    pub limited_crafting: bool,
+    /// Ticks before a player who just joined can use a portal
+    pub portal_cooldown: i32,
+    /// Stops players from changing the difficulty
+    pub difficulty_locked: bool,
+    /// Require chat messages to be signed by the player's Mojang key
+    pub enforce_secure_chat: bool,
+    /// Usernames of players allowed to change server settings
+    pub operators: Vec<String>,
+    /// Players listed when hovering over the player count in the server list
+    pub sample: Vec<(String, Uuid)>,
+    /// At most this many sample players are sent, vanilla uses 12
+    pub max_sample: usize,
+    /// Where to serve metrics for Prometheus to scrape at /metrics, not
+    /// served when `None`. Needs the `metrics-http` feature.
+    pub metrics_address: Option<String>,
+    /// Close connections that send a packet id the server doesn't know,
+    /// instead of skipping it. Helps catch missing handlers while developing.
+    pub strict_protocol: bool,
+    /// Packets other connections can queue for a player before the player is
+    /// dropped for not keeping up
+    pub outbound_queue_capacity: usize,
+    /// Bytes a connection may hold while waiting for the rest of a packet.
+    /// Clients that send more are disconnected. Has to be a little over
+    /// `MAX_PACKET_SIZE` so the largest packet and its length still fit.
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:25565".to_string(),
+            motd: Component::text("Hello world!"),
+            max_connections: 1024,
+            handshake_timeout: Duration::from_secs(5),
+            proxy_protocol: false,
+            velocity_secret: None,
+            online_mode: false,
+            session_server: crate::auth::MOJANG_SESSION_SERVER.to_string(),
+            registry_path: None,
+            compression_threshold: Some(256),
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            hardcore: false,
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+            game_mode: GameMode::Survival,
+            difficulty: Difficulty::Normal,
+            world_border: WorldBorder::default(),
+            weather: Weather::clear(),
+            view_distance: 10,
+            simulation_distance: 10,
+            seed: 0,
+            limited_crafting: false,
+            portal_cooldown: 0,
+            difficulty_locked: false,
+            operators: Vec::new(),
+            enforce_secure_chat: false,
+            sample: Vec::new(),
+            max_sample: 12,
+            metrics_address: None,
+            strict_protocol: false,
+            outbound_queue_capacity: 1024,
+            max_buffered_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
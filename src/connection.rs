@@ -1,32 +1,173 @@
+use crate::auth::{self, ServerKey};
+use crate::command::{parse_command, CommandContext};
+use crate::config::ServerConfig;
 use crate::error::MinecraftError;
-use crate::packet::reader::PacketReader;
+use crate::packet::advancements::SeenAdvancements;
+use crate::packet::buf::PacketBuf;
+use crate::packet::cipher::PacketCipher;
+use crate::packet::client_information::ClientInformation;
+use crate::packet::common::{
+    brand_data, write_disconnect_packet, write_plugin_message_packet, write_store_cookie_packet,
+    write_transfer_packet, BRAND_CHANNEL, CONFIGURATION_DISCONNECT_PACKET_ID,
+    CONFIGURATION_PLUGIN_MESSAGE_PACKET_ID, CONFIGURATION_STORE_COOKIE_PACKET_ID,
+    CONFIGURATION_TRANSFER_PACKET_ID, PLAY_DISCONNECT_PACKET_ID, PLAY_PLUGIN_MESSAGE_PACKET_ID,
+    PLAY_STORE_COOKIE_PACKET_ID, PLAY_TRANSFER_PACKET_ID,
+};
+use crate::packet::container::{ClickContainer, SetCreativeModeSlot};
+use crate::packet::frame::{decompress, frame_packet, MAX_PACKET_SIZE};
+use crate::packet::interact_entity::InteractEntity;
+use crate::packet::login::{
+    write_encryption_request_packet, write_login_disconnect_packet,
+    write_login_plugin_request_packet, write_set_compression_packet, EncryptionResponse,
+    LoginPluginResponse,
+};
+use crate::packet::metadata::{EntityMetadata, Pose, ENTITY_FLAGS_INDEX, POSE_INDEX};
+use crate::packet::play::{
+    position_delta, write_acknowledge_block_change_packet, write_block_update_packet,
+    write_change_difficulty_packet, write_entity_animation_packet, write_entity_metadata_packet,
+    write_entity_velocity_packet, write_game_event_packet, write_initialize_world_border_packet,
+    write_keep_alive_packet, write_login_packet, write_player_abilities_packet,
+    write_player_info_latency_packet, write_remove_entities_packet,
+    write_set_action_bar_text_packet, write_set_block_destroy_stage_packet,
+    write_set_cooldown_packet, write_set_experience_packet, write_set_health_packet,
+    write_set_passengers_packet, write_set_render_distance_packet,
+    write_set_simulation_distance_packet, write_set_subtitle_text_packet,
+    write_set_title_animation_times_packet, write_set_title_text_packet, write_spawn_entity_packet,
+    write_start_configuration_packet, write_synchronize_player_position_packet,
+    write_system_chat_packet, write_teleport_entity_packet,
+    write_update_entity_position_and_rotation_packet, write_weather_packets, PlayLogin,
+    PlayerTeleport, SpawnEntity, ABILITY_FLYING, CHANGE_GAME_MODE_EVENT, CLEAR_DESTROY_STAGE,
+    DEFAULT_FLYING_SPEED, DEFAULT_FOV_MODIFIER, SWING_MAIN_ARM_ANIMATION, SWING_OFFHAND_ANIMATION,
+};
+use crate::packet::player_command::{PlayerCommand, PlayerCommandAction};
+use crate::packet::reader::{PacketReader, MAX_COMMAND_LENGTH};
+use crate::packet::resource_pack::{ResourcePackResponse, ResourcePackResult};
+use crate::packet::sound::{write_sound_effect_packet, SoundEffect};
+use crate::packet::use_item::UseItem;
+use crate::packet::vehicle::MoveVehicle;
+use crate::player::{Experience, GameMode, PlayerState};
+use crate::proxy_protocol::parse_proxy_header;
 use crate::registry::manager::RegistryManager;
+use crate::scheduler::TICK_DURATION;
+use crate::text::Component;
+use crate::velocity::{verify_player_info, MODERN_DEFAULT_VERSION, PLAYER_INFO_CHANNEL};
+use crate::world::chunk_sender::ChunkSender;
+use crate::world::{
+    hashed_seed, Difficulty, EntityLocation, Outbound, OutboundMessage, Position, SharedWorld,
+    Weather, WorldBorder, AIR, STONE,
+};
 use crate::Result;
 use bytes::{Buf, BufMut, BytesMut};
+use flate2::Compression;
 use serde_json::json;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tracing::{debug, instrument, warn};
-use uuid::Uuid;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{debug, field, info, instrument, warn, Span};
 
 pub const PROTOCOL_VERSION: i32 = 767;
 
+/// Dimension and dimension type players spawn in
+const OVERWORLD: &str = "minecraft:overworld";
+
+/// Sent to clients on join, they show it in the debug screen
+pub const SERVER_BRAND: &str = "octavia";
+
+/// Told to Forge clients, they'd fail later in the login otherwise
+pub const NOT_MODDED_REASON: &str = "This server is not modded, join with a vanilla client";
+
+/// How often players in the Play state are sent a Keep Alive
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a client that is being dropped gets to receive its disconnect
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// ids tell apart the logs of concurrent connections
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Packets handled in a row before the read loop yields to the runtime
+pub const PACKETS_PER_YIELD: usize = 64;
+
 // Packet IDs
+//
+// Ids are only unique within a state and direction, 0x00 alone is Handshake,
+// Status Request, Login Start, Client Information or Confirm Teleportation
+// depending on the state. Always match on the state first.
+//
+// Handshake (serverbound)
 pub const HANDSHAKE_PACKET_ID: i32 = 0x00;
 
+// Handshake intents
+pub const NEXT_STATE_STATUS: i32 = 1;
+pub const NEXT_STATE_LOGIN: i32 = 2;
+pub const NEXT_STATE_TRANSFER: i32 = 3;
+
+// Status (serverbound)
 pub const STATUS_REQUEST_PACKET_ID: i32 = 0x00;
-pub const STATUS_RESPONSE_PACKET_ID: i32 = 0x00;
+// the Pong Response echoes the payload back under the same id
 pub const PING_REQUEST_PACKET_ID: i32 = 0x01;
 
+// Status (clientbound)
+pub const STATUS_RESPONSE_PACKET_ID: i32 = 0x00;
+
+// Login (serverbound)
 pub const LOGIN_START_PACKET_ID: i32 = 0x00;
-pub const LOGIN_SUCCESS_PACKET_ID: i32 = 0x02;
+pub const ENCRYPTION_RESPONSE_PACKET_ID: i32 = 0x01;
+pub const LOGIN_PLUGIN_RESPONSE_PACKET_ID: i32 = 0x02;
 pub const LOGIN_ACKNOWLEDGED_PACKET_ID: i32 = 0x03;
 
+// Login (clientbound), 0x02 is also Login Plugin Response going the other way
+pub const LOGIN_SUCCESS_PACKET_ID: i32 = 0x02;
+
+// Configuration (serverbound)
 pub const CLIENT_INFORMATION_PACKET_ID: i32 = 0x00;
 pub const PLUGIN_MESSAGE_PACKET_ID: i32 = 0x02;
 pub const FINISH_CONFIGURATION_PACKET_ID: i32 = 0x03;
 pub const KNOWN_PACKS_PACKET_ID: i32 = 0x07;
 
-#[derive(Debug, PartialEq)]
+// Play (serverbound)
+pub const CONFIRM_TELEPORTATION_PACKET_ID: i32 = 0x00;
+pub const CHANGE_DIFFICULTY_PACKET_ID: i32 = 0x02;
+pub const CHAT_COMMAND_PACKET_ID: i32 = 0x04;
+pub const CHUNK_BATCH_RECEIVED_PACKET_ID: i32 = 0x08;
+pub const PLAY_CLIENT_INFORMATION_PACKET_ID: i32 = 0x0A;
+pub const ACKNOWLEDGE_CONFIGURATION_PACKET_ID: i32 = 0x0C;
+pub const CLICK_CONTAINER_PACKET_ID: i32 = 0x0E;
+pub const CLOSE_CONTAINER_PACKET_ID: i32 = 0x0F;
+pub const INTERACT_ENTITY_PACKET_ID: i32 = 0x16;
+pub const KEEP_ALIVE_PACKET_ID: i32 = 0x18;
+pub const SET_PLAYER_POSITION_PACKET_ID: i32 = 0x1A;
+pub const SET_PLAYER_POSITION_AND_ROTATION_PACKET_ID: i32 = 0x1B;
+pub const SET_PLAYER_ROTATION_PACKET_ID: i32 = 0x1C;
+pub const SET_PLAYER_ON_GROUND_PACKET_ID: i32 = 0x1D;
+pub const MOVE_VEHICLE_PACKET_ID: i32 = 0x1E;
+pub const PLAYER_ABILITIES_PACKET_ID: i32 = 0x23;
+pub const PLAYER_ACTION_PACKET_ID: i32 = 0x24;
+pub const PLAYER_COMMAND_PACKET_ID: i32 = 0x25;
+pub const RESOURCE_PACK_RESPONSE_PACKET_ID: i32 = 0x2B;
+pub const SEEN_ADVANCEMENTS_PACKET_ID: i32 = 0x2C;
+pub const SET_CREATIVE_MODE_SLOT_PACKET_ID: i32 = 0x32;
+pub const SWING_ARM_PACKET_ID: i32 = 0x36;
+pub const USE_ITEM_ON_PACKET_ID: i32 = 0x38;
+pub const USE_ITEM_PACKET_ID: i32 = 0x39;
+
+// Player Action statuses
+pub const STARTED_DIGGING: i32 = 0;
+pub const CANCELLED_DIGGING: i32 = 1;
+pub const FINISHED_DIGGING: i32 = 2;
+
+// Hands
+pub const MAIN_HAND: i32 = 0;
+pub const OFF_HAND: i32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConnectionState {
     Handshake,
     Status,
@@ -35,49 +176,367 @@ pub enum ConnectionState {
     Play,
 }
 
-pub struct Connection {
-    socket: tokio::net::TcpStream,
+impl ConnectionState {
+    /// Whether the protocol allows moving from this state to `next`
+    pub fn can_transition_to(self, next: ConnectionState) -> bool {
+        use ConnectionState::*;
+
+        matches!(
+            (self, next),
+            (Handshake, Status)
+                | (Handshake, Login)
+                | (Login, Configuration)
+                | (Configuration, Play)
+                // the server can send a playing client back to configuration
+                | (Play, Configuration)
+        )
+    }
+}
+
+pub struct Connection<S = TcpStream> {
+    id: u64,
+    // outgoing packets are queued here and only hit the wire on flush()
+    socket: BufWriter<S>,
     state: ConnectionState,
+    config: Arc<ServerConfig>,
+    world: Arc<SharedWorld>,
+    // set once Set Compression has been sent, packets are framed differently after that
+    compression_threshold: Option<usize>,
+    // packets other connections want this player to receive
+    outbound_tx: Outbound,
+    outbound_rx: Receiver<OutboundMessage>,
+    entity_id: Option<i32>,
+    player: PlayerState,
+    client_information: Option<ClientInformation>,
+    // from the handshake, some packet layouts depend on it
+    protocol_version: i32,
+    // connected through a Transfer packet rather than directly
+    transferred: bool,
+    // flips to true when the server is shutting down
+    shutdown: Option<watch::Receiver<bool>>,
+    // the client's address, as reported by the proxy when there is one
+    peer_addr: Option<SocketAddr>,
+    awaiting_proxy_header: bool,
+    // channels of the login plugin requests still waiting for a response, by message id
+    plugin_requests: HashMap<i32, String>,
+    next_plugin_message_id: i32,
+    // set once the encryption handshake is done, every byte after that is encrypted
+    cipher: Option<PacketCipher>,
+    // sent in Encryption Request, the client has to echo it back
+    verify_token: [u8; 4],
+    // loaded once by the server, a connection without them loads the built-in ones
+    registries: Option<Arc<RegistryManager>>,
+    // teleport the client still has to confirm
+    pending_teleport: Option<i32>,
+    next_teleport_id: i32,
+    // the join message went out, so leaving is announced too
+    joined: bool,
+    // id and send time of the Keep Alive still waiting for a response
+    pending_keep_alive: Option<(i64, Instant)>,
+    // the socket broke or the client stopped reading, nothing more is written to it
+    abandoned: bool,
+    // Start Configuration was sent, the client still has to acknowledge it
+    awaiting_configuration_ack: bool,
+    // entity id of the vehicle the player is riding
+    vehicle: Option<i32>,
+    // highest sequence of a block interaction acknowledged so far
+    block_sequence: Option<i32>,
+    chunk_sender: ChunkSender,
+}
+
+/// Decodes a packet that carries item stacks. An item with a component the
+/// server can't decode only loses that packet, not the connection.
+fn decode_with_items<'a, T>(
+    packet_data: &mut PacketBuf<'a>,
+    decode: impl FnOnce(&mut PacketBuf<'a>) -> Result<T>,
+) -> Result<Option<T>> {
+    match decode(packet_data) {
+        Ok(packet) => Ok(Some(packet)),
+        Err(MinecraftError::UnsupportedItemComponent(type_id)) => {
+            warn!(
+                type_id,
+                "Skipping packet with an unsupported item component"
+            );
+            packet_data.skip_remaining();
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Splits the server address from a handshake into the host and the marker
+/// Forge clients append to it, like "FML3" in "example.com\0FML3\0"
+pub fn split_forge_marker(server_address: &str) -> (&str, Option<&str>) {
+    let mut parts = server_address.split('\0');
+    let host = parts.next().unwrap_or_default();
+    let marker = parts.find(|part| part.starts_with("FML"));
+    (host, marker)
 }
 
-impl Connection {
-    pub fn new(socket: tokio::net::TcpStream) -> Self {
+impl<S> Connection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(socket: S, config: Arc<ServerConfig>, world: Arc<SharedWorld>) -> Self {
+        let (outbound_tx, outbound_rx) = Outbound::channel(config.outbound_queue_capacity);
+        let awaiting_proxy_header = config.proxy_protocol;
+
         Self {
-            socket,
+            id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            socket: BufWriter::new(socket),
             state: ConnectionState::Handshake,
+            config,
+            world,
+            compression_threshold: None,
+            outbound_tx,
+            outbound_rx,
+            entity_id: None,
+            player: PlayerState::default(),
+            client_information: None,
+            protocol_version: PROTOCOL_VERSION,
+            transferred: false,
+            shutdown: None,
+            peer_addr: None,
+            awaiting_proxy_header,
+            plugin_requests: HashMap::new(),
+            next_plugin_message_id: 0,
+            cipher: None,
+            verify_token: rand::random(),
+            registries: None,
+            pending_teleport: None,
+            next_teleport_id: 0,
+            joined: false,
+            pending_keep_alive: None,
+            abandoned: false,
+            awaiting_configuration_ack: false,
+            vehicle: None,
+            block_sequence: None,
+            chunk_sender: ChunkSender::new(),
+        }
+    }
+
+    pub fn with_peer_addr(mut self, peer_addr: SocketAddr) -> Self {
+        self.peer_addr = Some(peer_addr);
+        self
+    }
+
+    /// Sends these registries during configuration instead of loading the
+    /// built-in ones for every connection
+    pub fn with_registries(mut self, registries: Arc<RegistryManager>) -> Self {
+        self.registries = Some(registries);
+        self
+    }
+
+    /// Stops reading packets once `shutdown` becomes true
+    pub fn with_shutdown(mut self, shutdown: watch::Receiver<bool>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    async fn shutdown_requested(shutdown: &mut Option<watch::Receiver<bool>>) {
+        match shutdown {
+            // a dropped sender means the server is gone, which is a shutdown too
+            Some(shutdown) => {
+                let _ = shutdown.wait_for(|&stop| stop).await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Moves the connection to a new state, rejecting jumps the protocol doesn't allow
+    pub fn set_state(&mut self, new: ConnectionState) -> Result<()> {
+        if !self.state.can_transition_to(new) {
+            warn!(from = ?self.state, to = ?new, "Illegal state transition");
+            return Err(MinecraftError::Protocol(format!(
+                "Illegal state transition from {:?} to {:?}",
+                self.state, new
+            )));
+        }
+
+        debug!(from = ?self.state, to = ?new, "State transition");
+        self.state = new;
+        Ok(())
+    }
+
+    /// Warns when a handler read less than the whole packet
+    fn check_consumed(&self, packet_id: i32, packet_length: usize, remaining: usize) {
+        if remaining != 0 {
+            warn!(
+                packet_id,
+                state = ?self.state,
+                remaining,
+                packet_length,
+                "Handler did not read the whole packet, skipping trailing bytes"
+            );
         }
     }
 
-    #[instrument(skip(self))]
+    /// Writes every queued packet to the socket
+    pub async fn flush(&mut self) -> Result<()> {
+        self.socket.flush().await?;
+        Ok(())
+    }
+
+    /// Unique for the lifetime of the process, every log line of this connection carries it
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[instrument(name = "connection", skip(self), fields(id = self.id, peer = field::Empty))]
     pub async fn handle_connection(&mut self) -> Result<()> {
+        if let Some(peer_addr) = self.peer_addr {
+            Span::current().record("peer", field::display(peer_addr));
+        }
+        self.world.metrics().connection_opened();
+        let result = self.read_packets().await;
+        if let Err(e) = &result {
+            self.handle_error(e).await;
+        }
+        self.close().await;
+        self.world.metrics().connection_closed();
+        result
+    }
+
+    /// Tells the client why the connection failed when the socket still works.
+    /// IO errors mean it doesn't, so the connection is closed without writing.
+    async fn handle_error(&mut self, error: &MinecraftError) {
+        match error {
+            MinecraftError::Io(_) => self.abandoned = true,
+            MinecraftError::Utf8(_)
+            | MinecraftError::VarInt(_)
+            | MinecraftError::BufferUnderrun(_)
+            | MinecraftError::Protocol(_)
+            | MinecraftError::Nbt(_)
+            | MinecraftError::Registry(_)
+            | MinecraftError::UnsupportedItemComponent(_)
+            | MinecraftError::Config(_)
+            | MinecraftError::Dns(_)
+            | MinecraftError::Json(_) => self.try_disconnect(&error.to_string()).await,
+        }
+    }
+
+    /// Takes the player out of the world, sends whatever is still buffered
+    /// and closes our side of the socket so the client sees a clean FIN
+    async fn close(&mut self) {
+        if self.remove_from_world() && self.joined {
+            self.world.broadcast_system_chat(
+                &Component::translate(
+                    "multiplayer.player.left",
+                    vec![Component::text(self.player.username.clone())],
+                )
+                .color("yellow"),
+            );
+        }
+
+        // flushing would fail or block forever, so the buffered bytes are dropped
+        let shutdown = if self.abandoned {
+            self.socket.get_mut().shutdown().await
+        } else {
+            // the socket may already be broken, there's nothing left to do about it then
+            if let Err(e) = self.flush().await {
+                debug!(error = %e, "Failed to flush on close");
+            }
+            self.socket.shutdown().await
+        };
+        if let Err(e) = shutdown {
+            debug!(error = %e, "Failed to shut down socket");
+        }
+    }
+
+    /// Takes the player's entity out of the world, returns false when it wasn't in it
+    fn remove_from_world(&mut self) -> bool {
+        let Some(entity_id) = self.entity_id.take() else {
+            return false;
+        };
+
+        self.world.remove_player(entity_id);
+        // the player's entity disappears for everyone still online
+        self.world
+            .broadcast(&write_remove_entities_packet(&[entity_id]));
+        true
+    }
+
+    async fn read_packets(&mut self) -> Result<()> {
         let mut buffer = BytesMut::with_capacity(1024);
+        // port scanners and half-open connections never get past the handshake
+        let handshake_deadline = tokio::time::sleep(self.config.handshake_timeout);
+        tokio::pin!(handshake_deadline);
+        let mut keep_alive =
+            tokio::time::interval_at(Instant::now() + KEEP_ALIVE_INTERVAL, KEEP_ALIVE_INTERVAL);
+        let mut chunk_tick = tokio::time::interval(TICK_DURATION);
+        let outbound = self.outbound_tx.clone();
 
         loop {
             let mut temp_buf = [0; 1024];
-            match self.socket.read(&mut temp_buf).await {
-                Ok(0) => {
-                    debug!("Connection closed by peer");
-                    break;
-                }
-                Ok(n) => {
-                    debug!(bytes = n, "Received data");
-                    buffer.extend_from_slice(&temp_buf[..n]);
+            tokio::select! {
+                read = self.socket.read(&mut temp_buf) => match read {
+                    Ok(0) => {
+                        debug!("Connection closed by peer");
+                        break;
+                    }
+                    Ok(n) => {
+                        debug!(bytes = n, "Received data");
+                        self.world.metrics().record_received(n);
+                        if let Some(cipher) = &mut self.cipher {
+                            cipher.decrypt(&mut temp_buf[..n]);
+                        }
+                        buffer.extend_from_slice(&temp_buf[..n]);
 
-                    while !buffer.is_empty() {
-                        match self.handle_packet(&mut buffer).await {
-                            Ok(should_continue) => {
-                                if !should_continue {
-                                    return Ok(());
-                                }
-                            }
-                            Err(e) => {
-                                return Err(e);
-                            }
+                        if !self.handle_buffered(&mut buffer).await? {
+                            self.flush().await?;
+                            return Ok(());
+                        }
+                        // whole packets were just handled, so only a packet that
+                        // never finishes arriving can pile up here
+                        if buffer.len() > self.config.max_buffered_bytes {
+                            return Err(MinecraftError::Protocol(format!(
+                                "More than {} bytes buffered without a complete packet",
+                                self.config.max_buffered_bytes
+                            )));
+                        }
+
+                        // everything we had to say about this batch goes out in one write
+                        self.flush().await?;
+                    }
+                    Err(e) => {
+                        return Err(e.into());
+                    }
+                },
+                Some(message) = self.outbound_rx.recv() => {
+                    // a client that stopped reading blocks the write, give up on it
+                    // once the queue overflows rather than waiting forever
+                    let keep_open = tokio::select! {
+                        keep_open = self.drain_outbound(message) => keep_open?,
+                        _ = outbound.overflowed() => {
+                            self.disconnect_slow_client().await;
+                            false
                         }
+                    };
+                    if !keep_open {
+                        break;
                     }
                 }
-                Err(e) => {
-                    return Err(e.into());
+                _ = outbound.overflowed() => {
+                    self.disconnect_slow_client().await;
+                    break;
+                }
+                _ = keep_alive.tick(), if self.state == ConnectionState::Play => {
+                    self.send_keep_alive().await?;
+                    self.flush().await?;
+                }
+                _ = chunk_tick.tick(), if self.state == ConnectionState::Play
+                    && self.chunk_sender.pending() > 0 =>
+                {
+                    self.send_chunk_batch().await?;
+                    self.flush().await?;
+                }
+                _ = &mut handshake_deadline, if self.state == ConnectionState::Handshake => {
+                    debug!("No handshake received in time, closing connection");
+                    break;
+                }
+                _ = Self::shutdown_requested(&mut self.shutdown) => {
+                    debug!("Server is shutting down, closing connection");
+                    break;
                 }
             }
         }
@@ -85,16 +544,217 @@ impl Connection {
         Ok(())
     }
 
+    /// Handles `message` and everything else already queued, then flushes.
+    /// Returns false once the connection should close.
+    async fn drain_outbound(&mut self, message: OutboundMessage) -> Result<bool> {
+        let mut keep_open = self.handle_outbound(message).await?;
+        while keep_open {
+            match self.outbound_rx.try_recv() {
+                Ok(message) => keep_open = self.handle_outbound(message).await?,
+                Err(_) => break,
+            }
+        }
+        self.flush().await?;
+        Ok(keep_open)
+    }
+
+    /// Drops a client that fell behind, its queued packets are discarded
+    async fn disconnect_slow_client(&mut self) {
+        warn!("Outbound queue is full, disconnecting slow client");
+        self.try_disconnect("Client too slow").await;
+        self.abandoned = true;
+    }
+
+    /// Sends a disconnect and flushes, without waiting long on a client that
+    /// may not be reading. The connection is abandoned if that fails.
+    async fn try_disconnect(&mut self, reason: &str) {
+        let disconnect = async {
+            self.disconnect(reason).await?;
+            self.flush().await
+        };
+        if !matches!(
+            tokio::time::timeout(DISCONNECT_TIMEOUT, disconnect).await,
+            Ok(Ok(()))
+        ) {
+            debug!("Client did not take the disconnect");
+            self.abandoned = true;
+        }
+    }
+
+    /// Updates the player's movement state and shows it to everyone else
+    fn handle_player_command(&mut self, action: PlayerCommandAction) {
+        let mut metadata = EntityMetadata::new();
+        match action {
+            PlayerCommandAction::StartSneaking | PlayerCommandAction::StopSneaking => {
+                self.player.sneaking = action == PlayerCommandAction::StartSneaking;
+                let pose = if self.player.sneaking {
+                    Pose::Sneaking
+                } else {
+                    Pose::Standing
+                };
+                metadata = metadata
+                    .byte(ENTITY_FLAGS_INDEX, self.player.entity_flags())
+                    .pose(POSE_INDEX, pose);
+            }
+            PlayerCommandAction::StartSprinting | PlayerCommandAction::StopSprinting => {
+                self.player.sprinting = action == PlayerCommandAction::StartSprinting;
+                metadata = metadata.byte(ENTITY_FLAGS_INDEX, self.player.entity_flags());
+            }
+            // TODO beds, vehicles and elytra
+            _ => return,
+        }
+
+        if let Some(entity_id) = self.entity_id {
+            let packet = write_entity_metadata_packet(entity_id, &metadata);
+            self.world.broadcast_except(entity_id, &packet);
+        }
+    }
+
+    /// Moves the vehicle the player is driving and shows the move to everyone else
+    fn handle_move_vehicle(&mut self, movement: &MoveVehicle) {
+        let Some(vehicle_id) = self.vehicle else {
+            debug!("Moved a vehicle while not riding one");
+            return;
+        };
+
+        let location = EntityLocation {
+            x: movement.x,
+            y: movement.y,
+            z: movement.z,
+            yaw: movement.yaw,
+            pitch: movement.pitch,
+        };
+        let previous = self.world.move_entity(vehicle_id, location);
+        let delta = previous.and_then(|from| {
+            Some((
+                position_delta(from.x, location.x)?,
+                position_delta(from.y, location.y)?,
+                position_delta(from.z, location.z)?,
+            ))
+        });
+        // a move of 8 blocks or more doesn't fit in a delta
+        let packet = match delta {
+            Some(delta) => write_update_entity_position_and_rotation_packet(
+                vehicle_id,
+                delta,
+                location.yaw,
+                location.pitch,
+                false,
+            ),
+            None => write_teleport_entity_packet(vehicle_id, &location, false),
+        };
+
+        match self.entity_id {
+            Some(entity_id) => self.world.broadcast_except(entity_id, &packet),
+            None => self.world.broadcast(&packet),
+        }
+    }
+
+    /// Acts on a message from another connection, returns false once the connection should close
+    async fn handle_outbound(&mut self, message: OutboundMessage) -> Result<bool> {
+        match message {
+            OutboundMessage::Packet(packet) => {
+                self.write_packet(&packet).await?;
+                Ok(true)
+            }
+            OutboundMessage::Kick(reason) => {
+                self.disconnect(&reason).await?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Sends the disconnect packet of the current state, the caller closes the connection
+    pub async fn disconnect(&mut self, reason: &str) -> Result<()> {
+        let packet = match self.state {
+            ConnectionState::Login => write_login_disconnect_packet(reason),
+            ConnectionState::Configuration => {
+                write_disconnect_packet(CONFIGURATION_DISCONNECT_PACKET_ID, reason)
+            }
+            ConnectionState::Play => write_disconnect_packet(PLAY_DISCONNECT_PACKET_ID, reason),
+            // there's no way to tell the client why
+            ConnectionState::Handshake | ConnectionState::Status => return Ok(()),
+        };
+
+        debug!(reason, state = ?self.state, "Disconnecting");
+        self.write_packet(&packet).await
+    }
+
+    /// Handles every complete packet in `buffer`, leaving a trailing partial
+    /// packet for the next read. Returns false once the connection should close.
+    async fn handle_buffered(&mut self, buffer: &mut BytesMut) -> Result<bool> {
+        if self.awaiting_proxy_header {
+            match parse_proxy_header(buffer)? {
+                Some(header) => {
+                    if let Some(source) = header.source {
+                        debug!(%source, "Client address from PROXY header");
+                        self.peer_addr = Some(source);
+                        Span::current().record("peer", field::display(source));
+                    }
+                    buffer.advance(header.length);
+                    self.awaiting_proxy_header = false;
+                }
+                None => return Ok(true),
+            }
+        }
+
+        let mut handled = 0;
+        while !buffer.is_empty() {
+            let before = buffer.len();
+            let encrypted = self.cipher.is_some();
+            if !self.handle_packet(buffer).await? {
+                return Ok(false);
+            }
+
+            // the client started encrypting right after its Encryption
+            // Response, so whatever came in the same read is still ciphertext
+            if !encrypted {
+                if let Some(cipher) = &mut self.cipher {
+                    cipher.decrypt(buffer);
+                }
+            }
+
+            // nothing was consumed, the rest of the packet hasn't arrived yet
+            if buffer.len() == before {
+                break;
+            }
+
+            handled += 1;
+            if handled % PACKETS_PER_YIELD == 0 {
+                // a client pipelining lots of packets shouldn't starve the
+                // other connections on this thread
+                tokio::task::yield_now().await;
+            }
+        }
+
+        Ok(true)
+    }
+
     #[instrument(skip(self, buffer))]
     pub async fn handle_packet(&mut self, buffer: &mut BytesMut) -> Result<bool> {
         debug!("Received raw packet data: {:?}", &buffer[..]);
-        let packet_length = match PacketReader::read_varint(&mut &buffer[..]) {
-            Ok(len) => len as usize,
+        let (packet_length, length_size) = match PacketReader::read_varint_counted(&mut &buffer[..])
+        {
+            Ok((len, size)) => (len as usize, size),
             Err(MinecraftError::VarInt(_)) => return Ok(true), // not enough data yet
             Err(e) => return Err(e),
         };
 
-        let length_size = PacketReader::get_varint_size(packet_length as i32);
+        // checked before waiting for the rest, no client may send more
+        if packet_length > MAX_PACKET_SIZE {
+            return Err(MinecraftError::Protocol(format!(
+                "Packet length {packet_length} is over the limit of {MAX_PACKET_SIZE}"
+            )));
+        }
+
+        // every packet has at least an id
+        if packet_length == 0 {
+            warn!("Received a zero-length packet");
+            return Err(MinecraftError::Protocol(
+                "Packet length is 0, a packet needs at least an id".to_string(),
+            ));
+        }
+
         let total_size = length_size + packet_length;
 
         // check if we have the full packet
@@ -102,9 +762,22 @@ impl Connection {
             return Ok(true);
         }
 
+        let mut frame = &buffer[length_size..total_size];
+        let decompressed;
+        if self.compression_threshold.is_some() {
+            let data_length = PacketReader::read_varint(&mut frame)?;
+            // a data length of 0 means the packet wasn't worth compressing
+            if data_length != 0 {
+                decompressed = decompress(frame, data_length as usize)?;
+                frame = &decompressed;
+            }
+        }
+        let data_length = frame.len();
+        self.world.metrics().record_packet_received();
+
         // read packet ID from the actual packet data
-        let mut packet_data = &buffer[length_size..total_size];
-        let packet_id = PacketReader::read_varint(&mut packet_data)?;
+        let mut packet_data = PacketBuf::new(frame);
+        let packet_id = packet_data.read_varint()?;
         debug!(
             packet_id,
             length = packet_length,
@@ -115,10 +788,10 @@ impl Connection {
         match self.state {
             ConnectionState::Handshake => {
                 if packet_id == HANDSHAKE_PACKET_ID {
-                    let protocol_version = PacketReader::read_varint(&mut packet_data)?;
-                    let server_address = PacketReader::read_string(&mut packet_data)?;
-                    let server_port = PacketReader::read_unsigned_short(&mut packet_data)?;
-                    let next_state = PacketReader::read_varint(&mut packet_data)?;
+                    let protocol_version = packet_data.read_varint()?;
+                    let server_address = packet_data.read_string()?;
+                    let server_port = packet_data.read_unsigned_short()?;
+                    let next_state = packet_data.read_varint()?;
 
                     debug!(
                         %protocol_version,
@@ -127,17 +800,39 @@ impl Connection {
                         %next_state,
                         "Handshake packet"
                     );
+                    self.protocol_version = protocol_version;
+                    let (_host, forge_marker) = split_forge_marker(&server_address);
 
                     match next_state {
-                        1 => self.state = ConnectionState::Status,
-                        2 => self.state = ConnectionState::Login,
+                        NEXT_STATE_STATUS => self.set_state(ConnectionState::Status)?,
+                        NEXT_STATE_LOGIN => self.set_state(ConnectionState::Login)?,
+                        NEXT_STATE_TRANSFER => {
+                            // a client sent here by another server's Transfer packet logs in as usual
+                            self.transferred = true;
+                            self.set_state(ConnectionState::Login)?;
+                        }
                         _ => {
                             warn!(next_state, "Unexpected next state in handshake");
-                            return Err(MinecraftError::Protocol(format!(
+                            // the client expects login packets after asking for anything but status
+                            self.write_packet(&write_login_disconnect_packet(&format!(
                                 "Unexpected next state: {next_state}"
-                            )));
+                            )))
+                            .await?;
+                            return Ok(false);
+                        }
+                    }
+
+                    // they expect login plugin requests that a vanilla server never sends
+                    if let Some(marker) = forge_marker {
+                        if self.state == ConnectionState::Login {
+                            info!(marker, "Forge client tried to log in, disconnecting");
+                            self.disconnect(NOT_MODDED_REASON).await?;
+                            return Ok(false);
                         }
                     }
+                } else {
+                    warn!(packet_id, "Unknown packet ID in Handshake state");
+                    self.skip_unknown_packet(packet_id, &mut packet_data)?;
                 }
             }
             ConnectionState::Status => match packet_id {
@@ -146,74 +841,152 @@ impl Connection {
                     self.send_status_response().await?;
                 }
                 PING_REQUEST_PACKET_ID => {
-                    let payload = PacketReader::read_long(&mut packet_data)?;
+                    let payload = packet_data.read_long()?;
                     debug!(payload, "Received ping request");
                     self.send_pong_response(payload).await?;
                     return Ok(false);
                 }
                 _ => {
                     warn!(packet_id, "Unknown packet ID in Status state");
+                    self.skip_unknown_packet(packet_id, &mut packet_data)?;
                 }
             },
             ConnectionState::Login => match packet_id {
                 LOGIN_START_PACKET_ID => {
-                    let username = PacketReader::read_string(&mut packet_data)?;
-                    self.send_login_success("00000000-0000-0000-0000-000000000001", &username)
+                    self.player.username = packet_data.read_string()?;
+                    // the client's own idea of its uuid, the server decides the real one
+                    let client_uuid = packet_data.read_uuid()?;
+                    debug!(username = self.player.username, %client_uuid, "Received login start");
+                    self.player.uuid = auth::offline_uuid(&self.player.username);
+
+                    if self.config.velocity_secret.is_some() {
+                        // the proxy tells us who this really is before we continue
+                        debug!("Requesting forwarded player info from Velocity");
+                        self.send_login_plugin_request(
+                            PLAYER_INFO_CHANNEL,
+                            &[MODERN_DEFAULT_VERSION],
+                        )
                         .await?;
+                    } else if self.config.online_mode {
+                        debug!("Requesting encryption");
+                        let packet = write_encryption_request_packet(
+                            &ServerKey::get().public_key_der,
+                            &self.verify_token,
+                            true,
+                        );
+                        self.write_packet(&packet).await?;
+                    } else {
+                        self.finish_login().await?;
+                    }
+                }
+                ENCRYPTION_RESPONSE_PACKET_ID => {
+                    let response = EncryptionResponse::decode(&mut packet_data)?;
+                    if !self.handle_encryption_response(response).await? {
+                        return Ok(false);
+                    }
+                }
+                LOGIN_PLUGIN_RESPONSE_PACKET_ID => {
+                    let response = LoginPluginResponse::decode(&mut packet_data)?;
+                    let Some(channel) = self.plugin_requests.remove(&response.message_id) else {
+                        return Err(MinecraftError::Protocol(format!(
+                            "Login plugin response to unknown message {}",
+                            response.message_id
+                        )));
+                    };
+                    debug!(
+                        message_id = response.message_id,
+                        channel,
+                        understood = response.data.is_some(),
+                        "Received login plugin response"
+                    );
+
+                    match channel.as_str() {
+                        PLAYER_INFO_CHANNEL => {
+                            if !self.handle_velocity_response(response.data).await? {
+                                return Ok(false);
+                            }
+                        }
+                        _ => warn!(channel, "No handler for login plugin channel"),
+                    }
                 }
                 LOGIN_ACKNOWLEDGED_PACKET_ID => {
                     debug!("Login acknowledged, switching to Configuration state");
-                    self.state = ConnectionState::Configuration;
+                    self.set_state(ConnectionState::Configuration)?;
                     self.send_known_packs().await?;
                 }
-                _ => warn!(packet_id, "Unknown packet ID in Login state"),
+                _ => {
+                    warn!(packet_id, "Unknown packet ID in Login state");
+                    self.skip_unknown_packet(packet_id, &mut packet_data)?;
+                }
             },
             ConnectionState::Configuration => match packet_id {
                 CLIENT_INFORMATION_PACKET_ID => {
                     // Client Information packet in Configuration
                     debug!("Received client information in Configuration state");
-                    let locale = PacketReader::read_string(&mut packet_data)?;
-                    let view_distance = PacketReader::read_byte(&mut packet_data)?;
-                    let chat_mode = PacketReader::read_varint(&mut packet_data)?;
-                    let chat_colors = PacketReader::read_boolean(&mut packet_data)?;
-                    let displayed_skin_parts = PacketReader::read_unsigned_byte(&mut packet_data)?;
-                    let main_hand = PacketReader::read_varint(&mut packet_data)?;
-                    let enable_text_filtering = PacketReader::read_boolean(&mut packet_data)?;
-                    let allow_server_listings = PacketReader::read_boolean(&mut packet_data)?;
-
-                    debug!(
-                        locale,
-                        view_distance,
-                        chat_mode,
-                        chat_colors,
-                        displayed_skin_parts,
-                        main_hand,
-                        enable_text_filtering,
-                        allow_server_listings
-                    );
+                    self.handle_client_information(&mut packet_data)?;
                 }
                 PLUGIN_MESSAGE_PACKET_ID => {
                     // Plugin message (minecraft:brand)
                     // TODO actually do something with the plugin
                     debug!("Received plugin message in Configuration state");
-                    let (_channel, _data) = PacketReader::read_plugin_message(&mut packet_data)?;
+                    let (_channel, _data) = packet_data.read_plugin_message()?;
                 }
                 FINISH_CONFIGURATION_PACKET_ID => {
                     debug!("Ack configuration finished, switching to Play state");
 
-                    self.state = ConnectionState::Play;
-                    // TODO self.send_play_login().await?;
+                    self.set_state(ConnectionState::Play)?;
+                    // the client counts its block sequences from zero again
+                    // in the world it's about to get
+                    self.block_sequence = None;
+                    self.entity_id = Some(
+                        self.world
+                            .register_player(&self.player.username, self.outbound_tx.clone()),
+                    );
+                    self.player.game_mode = self.config.game_mode;
+
+                    self.send_play_login().await?;
+                    self.send_plugin_message(BRAND_CHANNEL, &brand_data(SERVER_BRAND))
+                        .await?;
+                    self.send_render_distance(self.config.view_distance).await?;
+                    self.send_simulation_distance(self.config.simulation_distance)
+                        .await?;
+                    self.write_packet(&write_change_difficulty_packet(
+                        self.world.difficulty(),
+                        self.config.difficulty_locked,
+                    ))
+                    .await?;
+                    let border = self.config.world_border.clone();
+                    self.send_initialize_world_border(&border).await?;
+                    let weather = self.world.weather();
+                    if weather.raining {
+                        self.send_weather(true, weather.rain_level, weather.thunder_level)
+                            .await?;
+                    }
+                    self.send_player_abilities(
+                        self.player.game_mode.abilities(),
+                        DEFAULT_FLYING_SPEED,
+                        DEFAULT_FOV_MODIFIER,
+                    )
+                    .await?;
+                    self.write_packet(&self.world.declare_commands_packet())
+                        .await?;
+                    let Experience { bar, level, total } = self.player.xp;
+                    self.send_experience(bar, level, total).await?;
+                    let (health, food, saturation) =
+                        (self.player.health, self.player.food, self.player.saturation);
+                    self.send_health(health, food, saturation).await?;
                     // TODO self.send_chunk_data().await?;
+                    self.synchronize_position().await?;
                 }
                 KNOWN_PACKS_PACKET_ID => {
-                    let pack_count = PacketReader::read_varint(&mut packet_data)?;
+                    let pack_count = packet_data.read_varint()?;
                     debug!("Received Serverbound Known Packs request in Configuration state. packets={pack_count}");
 
                     let mut known_packs = Vec::new();
                     for _ in 0..pack_count {
-                        let namespace = PacketReader::read_string(&mut packet_data)?;
-                        let id = PacketReader::read_string(&mut packet_data)?;
-                        let version = PacketReader::read_string(&mut packet_data)?;
+                        let namespace = packet_data.read_string()?;
+                        let id = packet_data.read_string()?;
+                        let version = packet_data.read_string()?;
 
                         debug!(
                             "Known Pack - Namespace: {}, ID: {}, Version: {}",
@@ -226,114 +999,940 @@ impl Connection {
                     self.send_registry_data().await?;
                     self.send_finish_configuration().await?;
                 }
-                _ => warn!(packet_id, "Unknown packet ID in Configuration state"),
+                _ => {
+                    warn!(packet_id, "Unknown packet ID in Configuration state");
+                    self.skip_unknown_packet(packet_id, &mut packet_data)?;
+                }
             },
-            ConnectionState::Play => {
-                debug!("Client in Play state, processing packet {}", packet_id);
-                debug!(packet_id, "Unhandled Play state packet ID")
-            }
-        }
-
-        buffer.advance(total_size);
+            ConnectionState::Play => match packet_id {
+                ACKNOWLEDGE_CONFIGURATION_PACKET_ID => {
+                    if !self.awaiting_configuration_ack {
+                        return Err(MinecraftError::Protocol(
+                            "Acknowledge Configuration without Start Configuration".to_string(),
+                        ));
+                    }
+                    debug!("Client acknowledged configuration, switching to Configuration state");
 
-        Ok(true)
-    }
+                    self.awaiting_configuration_ack = false;
+                    self.set_state(ConnectionState::Configuration)?;
+                    // the client unloaded the world, it rejoins after configuration
+                    // without a second join message
+                    self.remove_from_world();
+                    self.pending_keep_alive = None;
+                    self.pending_teleport = None;
+                    self.send_known_packs().await?;
+                }
+                CONFIRM_TELEPORTATION_PACKET_ID => {
+                    let teleport_id = packet_data.read_varint()?;
+                    if self.pending_teleport != Some(teleport_id) {
+                        warn!(teleport_id, "Confirmed an unknown teleport");
+                    } else {
+                        self.pending_teleport = None;
+                        // the first confirmed teleport means the client is in the world
+                        if !self.joined {
+                            self.joined = true;
+                            self.world.broadcast_system_chat(
+                                &Component::translate(
+                                    "multiplayer.player.joined",
+                                    vec![Component::text(self.player.username.clone())],
+                                )
+                                .color("yellow"),
+                            );
+                        }
+                    }
+                }
+                KEEP_ALIVE_PACKET_ID => {
+                    let id = packet_data.read_long()?;
+                    self.handle_keep_alive(id);
+                }
+                CHANGE_DIFFICULTY_PACKET_ID => {
+                    let id = packet_data.read_unsigned_byte()?;
+                    let difficulty = Difficulty::from_id(id).ok_or_else(|| {
+                        MinecraftError::Protocol(format!("Invalid difficulty: {id}"))
+                    })?;
 
-    // packet length     varint
-    // packet id         varint
-    // response          string
-    async fn send_status_response(&mut self) -> Result<()> {
-        let response = json!({
-            "version": {
-                "name": "1.21.1",
-                "protocol": PROTOCOL_VERSION
-            },
-            "players": {
-                "max": 100,
-                "online": 4,
-                "sample": [
+                    if !self.config.operators.contains(&self.player.username) {
+                        warn!(
+                            username = self.player.username,
+                            "Non-operator tried to change difficulty"
+                        );
+                    } else if self.config.difficulty_locked {
+                        debug!("Difficulty is locked, ignoring change");
+                    } else {
+                        debug!(?difficulty, "Changing difficulty");
+                        self.world.set_difficulty(difficulty);
+                        self.world
+                            .broadcast(&write_change_difficulty_packet(difficulty, false));
+                    }
+                }
+                CHAT_COMMAND_PACKET_ID => {
+                    let command = packet_data.read_string_capped(MAX_COMMAND_LENGTH)?;
+                    debug!(command, "Received chat command");
+                    self.run_command(&command).await?;
+                }
+                CHUNK_BATCH_RECEIVED_PACKET_ID => {
+                    let chunks_per_tick = packet_data.read_float()?;
+                    debug!(chunks_per_tick, "Received chunk batch received");
+                    self.chunk_sender.batch_received(chunks_per_tick);
+                }
+                PLAY_CLIENT_INFORMATION_PACKET_ID => {
+                    debug!("Received client information in Play state");
+                    self.handle_client_information(&mut packet_data)?;
+                }
+                CLICK_CONTAINER_PACKET_ID => {
+                    if let Some(click) =
+                        decode_with_items(&mut packet_data, ClickContainer::decode)?
                     {
-                        "name": "Player",
-                        "id": "00000000-0000-0000-0000-000000000001"
+                        // TODO apply the click once there is an inventory
+                        debug!(?click, "Received click container");
                     }
-                ]
-            },
-            "description": {
-                "text": "Hello world!"
-            }
-        });
-
-        let response_str = response.to_string();
-        debug!(response = %response_str, "Sending status response");
-
-        let mut packet = BytesMut::new();
-        let string_length = response_str.len() as i32;
+                }
+                SET_CREATIVE_MODE_SLOT_PACKET_ID => {
+                    if let Some(SetCreativeModeSlot { slot, item }) =
+                        decode_with_items(&mut packet_data, SetCreativeModeSlot::decode)?
+                    {
+                        debug!(slot, ?item, "Received set creative mode slot");
 
-        let total_length = string_length
-            + (PacketReader::get_varint_size(string_length)
-                + PacketReader::get_varint_size(STATUS_RESPONSE_PACKET_ID)) as i32;
-        PacketReader::write_varint(&mut packet, total_length);
-        PacketReader::write_varint(&mut packet, STATUS_RESPONSE_PACKET_ID);
-        PacketReader::write_string(&mut packet, &response_str);
+                        if self.player.game_mode != GameMode::Creative {
+                            warn!(slot, "Set creative mode slot outside of creative mode");
+                        } else if slot == -1 {
+                            // TODO spawn the dropped item once there are item entities
+                        } else if !usize::try_from(slot)
+                            .is_ok_and(|slot| self.player.inventory.set(slot, item))
+                        {
+                            return Err(MinecraftError::Protocol(format!(
+                                "Invalid creative mode slot: {slot}"
+                            )));
+                        }
+                    }
+                }
+                CLOSE_CONTAINER_PACKET_ID => {
+                    let window_id = packet_data.read_unsigned_byte()?;
+                    debug!(window_id, "Received close container");
+                }
+                INTERACT_ENTITY_PACKET_ID => {
+                    let interaction = InteractEntity::decode(&mut packet_data)?;
+                    // TODO combat and entity interaction
+                    debug!(?interaction, "Received interact entity");
+                }
+                SET_PLAYER_POSITION_PACKET_ID => {
+                    self.player.x = packet_data.read_double()?;
+                    self.player.y = packet_data.read_double()?;
+                    self.player.z = packet_data.read_double()?;
+                    self.player.on_ground = packet_data.read_boolean()?;
+                }
+                SET_PLAYER_POSITION_AND_ROTATION_PACKET_ID => {
+                    self.player.x = packet_data.read_double()?;
+                    self.player.y = packet_data.read_double()?;
+                    self.player.z = packet_data.read_double()?;
+                    self.player.yaw = packet_data.read_float()?;
+                    self.player.pitch = packet_data.read_float()?;
+                    self.player.on_ground = packet_data.read_boolean()?;
+                }
+                SET_PLAYER_ROTATION_PACKET_ID => {
+                    self.player.yaw = packet_data.read_float()?;
+                    self.player.pitch = packet_data.read_float()?;
+                    self.player.on_ground = packet_data.read_boolean()?;
+                }
+                SET_PLAYER_ON_GROUND_PACKET_ID => {
+                    self.player.on_ground = packet_data.read_boolean()?;
+                }
+                MOVE_VEHICLE_PACKET_ID => {
+                    let movement = MoveVehicle::decode(&mut packet_data)?;
+                    debug!(?movement, "Received move vehicle");
+                    self.handle_move_vehicle(&movement);
+                }
+                PLAYER_ABILITIES_PACKET_ID => {
+                    let flags = packet_data.read_unsigned_byte()?;
+                    // the client only ever reports whether it's flying
+                    self.player.flying = flags & ABILITY_FLYING != 0;
+                    debug!(flying = self.player.flying, "Received player abilities");
+                }
+                PLAYER_ACTION_PACKET_ID => {
+                    let status = packet_data.read_varint()?;
+                    let location = packet_data.read_position()?;
+                    let face = packet_data.read_byte()?;
+                    let sequence = packet_data.read_varint()?;
+                    debug!(status, ?location, face, sequence, "Received player action");
 
-        self.socket.write_all(&packet).await?;
-        Ok(())
-    }
+                    // blocks break instantly, as if every player was in creative mode
+                    if status == STARTED_DIGGING || status == FINISHED_DIGGING {
+                        self.world.set_block(location, AIR);
+                        self.world
+                            .broadcast(&write_block_update_packet(location, AIR));
+                    }
 
-    // packet length  varint
-    // packet id      varint
-    async fn send_pong_response(&mut self, payload: i64) -> Result<()> {
-        debug!(payload, "Sending pong response");
+                    // TODO send the stages in between once blocks take time to break
+                    if status == CANCELLED_DIGGING || status == FINISHED_DIGGING {
+                        if let Some(entity_id) = self.entity_id {
+                            let packet = write_set_block_destroy_stage_packet(
+                                entity_id,
+                                location,
+                                CLEAR_DESTROY_STAGE,
+                            );
+                            self.world.broadcast_except(entity_id, &packet);
+                        }
+                    }
 
-        let mut packet = BytesMut::with_capacity(1024);
+                    // dropping, swapping or finishing an item carry a sequence
+                    // too, but the client doesn't predict anything for them
+                    if matches!(
+                        status,
+                        STARTED_DIGGING | CANCELLED_DIGGING | FINISHED_DIGGING
+                    ) {
+                        self.send_block_change_ack(sequence).await?;
+                    }
+                }
+                PLAYER_COMMAND_PACKET_ID => {
+                    let command = PlayerCommand::decode(&mut packet_data)?;
+                    debug!(?command, "Received player command");
+                    self.handle_player_command(command.action);
+                }
+                RESOURCE_PACK_RESPONSE_PACKET_ID => {
+                    let response = ResourcePackResponse::decode(&mut packet_data)?;
+                    match response.result {
+                        ResourcePackResult::Declined => {
+                            info!(uuid = %response.uuid, "Client declined resource pack")
+                        }
+                        ResourcePackResult::Accepted => {
+                            info!(uuid = %response.uuid, "Client accepted resource pack")
+                        }
+                        result => debug!(uuid = %response.uuid, ?result, "Resource pack status"),
+                    }
+                }
+                SEEN_ADVANCEMENTS_PACKET_ID => {
+                    let seen = SeenAdvancements::decode(&mut packet_data)?;
+                    debug!(?seen, "Received seen advancements");
+                }
+                SWING_ARM_PACKET_ID => {
+                    let hand = packet_data.read_varint()?;
+                    debug!(hand, "Received swing arm");
 
-        PacketReader::write_varint(&mut packet, 9); // payload is always 9 bytes
-        PacketReader::write_varint(&mut packet, PING_REQUEST_PACKET_ID);
+                    let animation = match hand {
+                        MAIN_HAND => SWING_MAIN_ARM_ANIMATION,
+                        OFF_HAND => SWING_OFFHAND_ANIMATION,
+                        _ => return Err(MinecraftError::Protocol(format!("Invalid hand: {hand}"))),
+                    };
 
-        packet.put_i64(payload);
+                    if let Some(entity_id) = self.entity_id {
+                        let packet = write_entity_animation_packet(entity_id, animation);
+                        self.world.broadcast_except(entity_id, &packet);
+                    }
+                }
+                USE_ITEM_ON_PACKET_ID => {
+                    let hand = packet_data.read_varint()?;
+                    let location = packet_data.read_position()?;
+                    let face = packet_data.read_varint()?;
+                    let cursor_x = packet_data.read_float()?;
+                    let cursor_y = packet_data.read_float()?;
+                    let cursor_z = packet_data.read_float()?;
+                    let inside_block = packet_data.read_boolean()?;
+                    let sequence = packet_data.read_varint()?;
+                    debug!(
+                        hand,
+                        ?location,
+                        face,
+                        cursor_x,
+                        cursor_y,
+                        cursor_z,
+                        inside_block,
+                        sequence,
+                        "Received use item on"
+                    );
+
+                    // TODO place the held item once there is an inventory
+                    let target = location.offset(face);
+                    self.world.set_block(target, STONE);
+                    self.world
+                        .broadcast(&write_block_update_packet(target, STONE));
+                    self.send_block_change_ack(sequence).await?;
+                }
+                USE_ITEM_PACKET_ID => {
+                    let use_item = UseItem::decode(&mut packet_data)?;
+                    debug!(?use_item, "Received use item");
+
+                    // TODO use the held item once there is an inventory, the client
+                    // still waits for the acknowledgment before it accepts block changes
+                    self.send_block_change_ack(use_item.sequence).await?;
+                }
+                _ => {
+                    debug!(packet_id, "Unhandled Play state packet ID");
+                    self.skip_unknown_packet(packet_id, &mut packet_data)?;
+                }
+            },
+        }
+
+        // the whole frame is skipped either way, so the next packet stays aligned
+        self.check_consumed(packet_id, data_length, packet_data.remaining());
+        buffer.advance(total_size);
+
+        Ok(true)
+    }
+
+    /// Skips a packet the current state has no handler for, in strict mode
+    /// the connection is closed instead
+    fn skip_unknown_packet(&self, packet_id: i32, packet_data: &mut PacketBuf) -> Result<()> {
+        if self.config.strict_protocol {
+            return Err(MinecraftError::Protocol(format!(
+                "Unknown packet ID {packet_id:#04x} in {:?} state",
+                self.state
+            )));
+        }
+
+        packet_data.skip_remaining();
+        Ok(())
+    }
+
+    /// Decodes and remembers the client's settings, sent in both Configuration and Play
+    fn handle_client_information(&mut self, packet_data: &mut PacketBuf) -> Result<()> {
+        let information = ClientInformation::decode(packet_data, self.protocol_version)?;
+        debug!(?information);
+        self.client_information = Some(information);
+        Ok(())
+    }
+
+    /// Frames a packet (id + body) for the current compression settings and queues it
+    async fn write_packet(&mut self, content: &[u8]) -> Result<()> {
+        let mut packet = frame_packet(
+            content,
+            self.compression_threshold,
+            self.compression_level(),
+        );
+        if let Some(cipher) = &mut self.cipher {
+            cipher.encrypt(&mut packet);
+        }
 
         self.socket.write_all(&packet).await?;
+        self.world.metrics().record_sent(1, packet.len());
+        Ok(())
+    }
+
+    /// Frames several packets into one buffer so they reach the socket in a single write
+    async fn write_packets(&mut self, contents: &[BytesMut]) -> Result<()> {
+        let mut packets = BytesMut::new();
+        for content in contents {
+            packets.extend_from_slice(&frame_packet(
+                content,
+                self.compression_threshold,
+                self.compression_level(),
+            ));
+        }
+        if let Some(cipher) = &mut self.cipher {
+            cipher.encrypt(&mut packets);
+        }
+
+        self.socket.write_all(&packets).await?;
+        self.world
+            .metrics()
+            .record_sent(contents.len(), packets.len());
+        Ok(())
+    }
+
+    // packet length     varint
+    // packet id         varint
+    // response          string
+    async fn send_status_response(&mut self) -> Result<()> {
+        let sample: Vec<_> = self
+            .config
+            .sample
+            .iter()
+            .take(self.config.max_sample)
+            .map(|(name, id)| json!({ "name": name, "id": id.to_string() }))
+            .collect();
+
+        let response = json!({
+            "version": {
+                "name": "1.21.1",
+                "protocol": PROTOCOL_VERSION
+            },
+            "players": {
+                "max": 100,
+                "online": self.world.online_count(),
+                "sample": sample
+            },
+            "description": self.config.motd.to_json(),
+            "enforcesSecureChat": self.config.enforce_secure_chat
+        });
+
+        let response_str = response.to_string();
+        debug!(response = %response_str, "Sending status response");
+
+        let mut packet = BytesMut::new();
+        PacketReader::write_varint(&mut packet, STATUS_RESPONSE_PACKET_ID);
+        PacketReader::write_string(&mut packet, &response_str);
+
+        self.write_packet(&packet).await
+    }
+
+    // packet length  varint
+    // packet id      varint
+    async fn send_pong_response(&mut self, payload: i64) -> Result<()> {
+        debug!(payload, "Sending pong response");
+
+        let mut packet = BytesMut::with_capacity(9);
+
+        PacketReader::write_varint(&mut packet, PING_REQUEST_PACKET_ID);
+        packet.put_i64(payload);
+
+        self.write_packet(&packet).await
+    }
+
+    fn compression_level(&self) -> Compression {
+        Compression::new(self.config.compression_level)
+    }
+
+    /// Enables compression, every packet after this one uses the compressed format
+    async fn send_set_compression(&mut self, threshold: usize) -> Result<()> {
+        debug!(threshold, "Enabling compression");
+        self.write_packet(&write_set_compression_packet(threshold))
+            .await?;
+        self.compression_threshold = Some(threshold);
 
         Ok(())
     }
 
+    /// Sends a Login Plugin Request and returns its message id, the
+    /// response is matched back to `channel` by that id
+    pub async fn send_login_plugin_request(&mut self, channel: &str, data: &[u8]) -> Result<i32> {
+        let message_id = self.next_plugin_message_id;
+        self.next_plugin_message_id += 1;
+        self.plugin_requests.insert(message_id, channel.to_string());
+
+        self.write_packet(&write_login_plugin_request_packet(
+            message_id, channel, data,
+        ))
+        .await?;
+        Ok(message_id)
+    }
+
+    /// Finishes a login forwarded by Velocity, returns false when the player was rejected
+    async fn handle_velocity_response(&mut self, data: Option<Vec<u8>>) -> Result<bool> {
+        let Some(secret) = self.config.velocity_secret.clone() else {
+            warn!("Unexpected Velocity forwarding response");
+            return Ok(true);
+        };
+        let Some(data) = data else {
+            self.disconnect("This server requires you to connect with Velocity.")
+                .await?;
+            return Ok(false);
+        };
+
+        let forwarded = match verify_player_info(secret.as_bytes(), &data) {
+            Ok(forwarded) => forwarded,
+            Err(e) => {
+                warn!(error = %e, "Rejected Velocity forwarding data");
+                self.disconnect("Unable to verify player details").await?;
+                return Ok(false);
+            }
+        };
+        debug!(?forwarded, "Player info forwarded by Velocity");
+
+        let port = self.peer_addr.map_or(0, |addr| addr.port());
+        self.peer_addr = Some(SocketAddr::new(forwarded.address, port));
+        self.player.username = forwarded.username;
+        self.player.uuid = forwarded.uuid;
+        self.player.properties = forwarded.properties;
+
+        self.finish_login().await?;
+        Ok(true)
+    }
+
+    /// Enables encryption and checks the player with the session server,
+    /// returns false when the player was rejected
+    async fn handle_encryption_response(&mut self, response: EncryptionResponse) -> Result<bool> {
+        if !self.config.online_mode || self.cipher.is_some() {
+            return Err(MinecraftError::Protocol(
+                "Unexpected encryption response".to_string(),
+            ));
+        }
+
+        let key = ServerKey::get();
+        let verify_token = key.decrypt(&response.verify_token)?;
+        if verify_token != self.verify_token {
+            return Err(MinecraftError::Protocol(
+                "Verify token doesn't match".to_string(),
+            ));
+        }
+
+        let shared_secret = key.decrypt(&response.shared_secret)?;
+        self.cipher = Some(PacketCipher::new(&shared_secret)?);
+        debug!("Encryption enabled");
+
+        let profile = match auth::has_joined(
+            &self.config.session_server,
+            &self.player.username,
+            &shared_secret,
+            &key.public_key_der,
+        )
+        .await
+        {
+            Ok(Some(profile)) => profile,
+            Ok(None) => {
+                debug!(
+                    username = self.player.username,
+                    "Session server rejected player"
+                );
+                self.disconnect("Failed to verify username!").await?;
+                return Ok(false);
+            }
+            Err(e) => {
+                warn!(error = %e, "Could not reach the session server");
+                self.disconnect("Authentication servers are down. Please try again later, sorry!")
+                    .await?;
+                return Ok(false);
+            }
+        };
+        debug!(?profile, "Player authenticated");
+
+        self.player.uuid = profile.id;
+        self.player.username = profile.name;
+        self.player.properties = profile.properties;
+
+        self.finish_login().await?;
+        Ok(true)
+    }
+
+    /// Completes the login once we know who the player is
+    async fn finish_login(&mut self) -> Result<()> {
+        let username = self.player.username.clone();
+        // most likely the same player reconnecting before their old connection timed out
+        if self
+            .world
+            .kick_by_username(&username, "You logged in from another location")
+        {
+            debug!(
+                username,
+                "Kicked existing connection with the same username"
+            );
+        }
+
+        if let Some(threshold) = self.config.compression_threshold {
+            self.send_set_compression(threshold).await?;
+        }
+        self.send_login_success().await
+    }
+
     // packet length    varint
     // UUID             string
     // username         string
     // properties       varint
     // chat validation  boolean
-    async fn send_login_success(&mut self, uuid_str: &str, username: &str) -> Result<()> {
+    async fn send_login_success(&mut self) -> Result<()> {
         let mut content = BytesMut::new();
 
         PacketReader::write_varint(&mut content, LOGIN_SUCCESS_PACKET_ID);
+        content.extend_from_slice(self.player.uuid.as_bytes());
+        PacketReader::write_string(&mut content, &self.player.username);
 
-        let uuid = Uuid::parse_str(uuid_str).unwrap();
-        content.extend_from_slice(uuid.as_bytes());
+        PacketReader::write_varint(&mut content, self.player.properties.len() as i32);
+        for property in &self.player.properties {
+            PacketReader::write_string(&mut content, &property.name);
+            PacketReader::write_string(&mut content, &property.value);
+            content.put_u8(property.signature.is_some() as u8);
+            if let Some(signature) = &property.signature {
+                PacketReader::write_string(&mut content, signature);
+            }
+        }
 
-        PacketReader::write_string(&mut content, username);
+        // strict error handling
+        content.put_u8(0);
 
-        // we have 0 properties
-        PacketReader::write_varint(&mut content, 0);
+        debug!("Sending login success packet: {:?}", content);
+        self.write_packet(&content).await
+    }
 
-        content.put_u8(0);
+    /// Sends a Keep Alive, the response tells us the player's latency
+    pub async fn send_keep_alive(&mut self) -> Result<()> {
+        // still waiting for the last one, don't lose track of its send time
+        if self.pending_keep_alive.is_some() {
+            return Ok(());
+        }
 
-        let mut packet = BytesMut::new();
-        PacketReader::write_varint(&mut packet, content.len() as i32);
-        packet.extend_from_slice(&content);
+        // like vanilla, the id is the current time in milliseconds
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as i64);
+        self.pending_keep_alive = Some((id, Instant::now()));
 
-        debug!("Sending login success packet: {:?}", packet);
-        self.socket.write_all(&packet).await?;
-        Ok(())
+        self.write_packet(&write_keep_alive_packet(id)).await
     }
 
-    #[allow(dead_code)]
-    async fn send_keep_alive(&mut self) -> Result<()> {
-        let mut packet = BytesMut::with_capacity(10);
-        PacketReader::write_varint(&mut packet, 0x21);
-        packet.put_i64(12345); // arbitrary payload, could be any number
+    /// Updates the player's latency from a Keep Alive response
+    fn handle_keep_alive(&mut self, id: i64) {
+        let Some((sent_id, sent_at)) = self.pending_keep_alive else {
+            warn!(id, "Unexpected keep alive response");
+            return;
+        };
+        if id != sent_id {
+            warn!(
+                id,
+                expected = sent_id,
+                "Keep alive response with the wrong id"
+            );
+            return;
+        }
+        self.pending_keep_alive = None;
+
+        let sample = sent_at.elapsed().as_millis().min(i32::MAX as u128) as i32;
+        // vanilla smooths the ping so one slow response doesn't make the bars jump
+        self.player.latency_ms = if self.player.latency_ms == 0 {
+            sample
+        } else {
+            (self.player.latency_ms * 3 + sample) / 4
+        };
+        debug!(
+            sample,
+            latency_ms = self.player.latency_ms,
+            "Keep alive round trip"
+        );
+        if let Some(entity_id) = self.entity_id {
+            self.world
+                .metrics()
+                .record_latency(entity_id, self.player.latency_ms);
+        }
 
-        self.socket.write_all(&packet).await?;
-        Ok(())
+        self.world.broadcast(&write_player_info_latency_packet(&[(
+            self.player.uuid,
+            self.player.latency_ms,
+        )]));
+    }
+
+    /// The player's smoothed round trip time in milliseconds
+    pub fn latency_ms(&self) -> i32 {
+        self.player.latency_ms
+    }
+
+    /// Sets the velocity of an entity, in blocks per tick
+    pub async fn send_entity_velocity(
+        &mut self,
+        entity_id: i32,
+        vx: f64,
+        vy: f64,
+        vz: f64,
+    ) -> Result<()> {
+        self.write_packet(&write_entity_velocity_packet(entity_id, vx, vy, vz))
+            .await
+    }
+
+    /// Spawns an entity other than a player, like a dropped item or a mob
+    pub async fn send_spawn_entity(&mut self, entity: &SpawnEntity) -> Result<()> {
+        self.write_packet(&write_spawn_entity_packet(entity)).await
+    }
+
+    /// Tells the client what it's allowed to do, see the `ABILITY_*` flags
+    pub async fn send_player_abilities(
+        &mut self,
+        flags: u8,
+        fly_speed: f32,
+        fov: f32,
+    ) -> Result<()> {
+        self.write_packet(&write_player_abilities_packet(flags, fly_speed, fov))
+            .await
+    }
+
+    /// Sends the Login (play) packet that spawns the player into the world
+    async fn send_play_login(&mut self) -> Result<()> {
+        let dimension_type = self
+            .registries()?
+            .dimension_type_id(OVERWORLD)
+            .ok_or_else(|| {
+                MinecraftError::Registry(format!("Dimension type {OVERWORLD} is not registered"))
+            })?;
+        let login = PlayLogin {
+            entity_id: self.entity_id.unwrap_or_default(),
+            hardcore: self.config.hardcore,
+            dimension_names: vec![("minecraft".to_string(), "overworld".to_string())],
+            max_players: 100,
+            view_distance: self.config.view_distance,
+            simulation_distance: self.config.simulation_distance,
+            reduced_debug_info: self.config.reduced_debug_info,
+            enable_respawn_screen: self.config.enable_respawn_screen,
+            do_limited_crafting: self.config.limited_crafting,
+            dimension_type,
+            dimension_name: OVERWORLD.to_string(),
+            hashed_seed: hashed_seed(self.config.seed),
+            game_mode: self.player.game_mode,
+            previous_game_mode: None,
+            is_debug: false,
+            is_flat: true,
+            portal_cooldown: self.config.portal_cooldown,
+            enforces_secure_chat: self.config.enforce_secure_chat,
+        };
+
+        debug!(?login, "Sending login (play) packet");
+        self.write_packet(&write_login_packet(&login)).await
+    }
+
+    /// Starts or stops rain for this player alone, levels go from 0.0 to 1.0
+    pub async fn send_weather(
+        &mut self,
+        raining: bool,
+        rain_level: f32,
+        thunder_level: f32,
+    ) -> Result<()> {
+        let weather = Weather {
+            raining,
+            rain_level,
+            thunder_level,
+        };
+        self.write_packets(&write_weather_packets(&weather)).await
+    }
+
+    /// Tells the client where the world border is, replacing whatever it had
+    pub async fn send_initialize_world_border(&mut self, border: &WorldBorder) -> Result<()> {
+        self.write_packet(&write_initialize_world_border_packet(border))
+            .await
+    }
+
+    /// Tells the client how far from it chunks are kept loaded, in chunks
+    pub async fn send_render_distance(&mut self, distance: i32) -> Result<()> {
+        self.write_packet(&write_set_render_distance_packet(distance))
+            .await
+    }
+
+    /// Tells the client how far from it entities are simulated, in chunks
+    pub async fn send_simulation_distance(&mut self, distance: i32) -> Result<()> {
+        self.write_packet(&write_set_simulation_distance_packet(distance))
+            .await
+    }
+
+    /// Switches the player to another game mode along with its abilities
+    pub async fn set_game_mode(&mut self, game_mode: GameMode) -> Result<()> {
+        self.player.game_mode = game_mode;
+
+        self.write_packet(&write_game_event_packet(
+            CHANGE_GAME_MODE_EVENT,
+            game_mode.id() as f32,
+        ))
+        .await?;
+        self.send_player_abilities(
+            game_mode.abilities(),
+            DEFAULT_FLYING_SPEED,
+            DEFAULT_FOV_MODIFIER,
+        )
+        .await
+    }
+
+    /// Sends a playing client back to the Configuration state, to send it new
+    /// registries for example. It keeps playing until it acknowledges, then
+    /// goes through configuration again and rejoins the world.
+    pub async fn enter_configuration(&mut self) -> Result<()> {
+        if self.state != ConnectionState::Play {
+            return Err(MinecraftError::Protocol(format!(
+                "Can't enter configuration from the {:?} state",
+                self.state
+            )));
+        }
+
+        debug!("Sending start configuration");
+        self.awaiting_configuration_ack = true;
+        self.write_packet(&write_start_configuration_packet()).await
+    }
+
+    /// Stores a cookie on the client, which it presents again after a transfer
+    pub async fn store_cookie(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        let packet_id = match self.state {
+            ConnectionState::Configuration => CONFIGURATION_STORE_COOKIE_PACKET_ID,
+            ConnectionState::Play => PLAY_STORE_COOKIE_PACKET_ID,
+            state => {
+                return Err(MinecraftError::Protocol(format!(
+                    "Can't store a cookie in the {state:?} state"
+                )))
+            }
+        };
+
+        debug!(key, length = value.len(), "Storing cookie");
+        self.write_packet(&write_store_cookie_packet(packet_id, key, value)?)
+            .await
+    }
+
+    /// Sends data on a plugin channel, like the server brand on `BRAND_CHANNEL`
+    pub async fn send_plugin_message(&mut self, channel: &str, data: &[u8]) -> Result<()> {
+        let packet_id = match self.state {
+            ConnectionState::Configuration => CONFIGURATION_PLUGIN_MESSAGE_PACKET_ID,
+            ConnectionState::Play => PLAY_PLUGIN_MESSAGE_PACKET_ID,
+            state => {
+                return Err(MinecraftError::Protocol(format!(
+                    "Can't send a plugin message in the {state:?} state"
+                )))
+            }
+        };
+
+        debug!(channel, length = data.len(), "Sending plugin message");
+        self.write_packet(&write_plugin_message_packet(packet_id, channel, data))
+            .await
+    }
+
+    /// Tells the client to reconnect to another server
+    pub async fn transfer(&mut self, host: &str, port: u16) -> Result<()> {
+        let packet_id = match self.state {
+            ConnectionState::Configuration => CONFIGURATION_TRANSFER_PACKET_ID,
+            ConnectionState::Play => PLAY_TRANSFER_PACKET_ID,
+            state => {
+                return Err(MinecraftError::Protocol(format!(
+                    "Can't transfer in the {state:?} state"
+                )))
+            }
+        };
+
+        debug!(host, port, "Transferring client");
+        self.write_packet(&write_transfer_packet(packet_id, host, port))
+            .await
+    }
+
+    /// Updates the player's experience and the client's xp bar
+    pub async fn send_experience(&mut self, bar: f32, level: i32, total: i32) -> Result<()> {
+        self.player.xp = Experience { bar, level, total };
+        self.write_packet(&write_set_experience_packet(bar, level, total))
+            .await
+    }
+
+    /// Updates the player's health and hunger and the client's hearts and food bar
+    pub async fn send_health(&mut self, health: f32, food: i32, saturation: f32) -> Result<()> {
+        self.player.health = health;
+        self.player.food = food;
+        self.player.saturation = saturation;
+        self.write_packet(&write_set_health_packet(health, food, saturation))
+            .await
+    }
+
+    /// Shows the cracks of a block `entity_id` is breaking, `CLEAR_DESTROY_STAGE` removes them
+    pub async fn send_block_destroy_stage(
+        &mut self,
+        entity_id: i32,
+        position: Position,
+        stage: u8,
+    ) -> Result<()> {
+        self.write_packet(&write_set_block_destroy_stage_packet(
+            entity_id, position, stage,
+        ))
+        .await
+    }
+
+    /// Seats `passengers` on a vehicle, replacing whoever was riding it. When
+    /// this player is one of them, their Move Vehicle packets move the vehicle.
+    pub async fn send_set_passengers(&mut self, vehicle_id: i32, passengers: &[i32]) -> Result<()> {
+        let riding = self
+            .entity_id
+            .is_some_and(|entity_id| passengers.contains(&entity_id));
+        if riding {
+            self.vehicle = Some(vehicle_id);
+        } else if self.vehicle == Some(vehicle_id) {
+            self.vehicle = None;
+        }
+
+        self.write_packet(&write_set_passengers_packet(vehicle_id, passengers))
+            .await
+    }
+
+    /// Despawns entities on the client
+    pub async fn send_remove_entities(&mut self, entity_ids: &[i32]) -> Result<()> {
+        self.write_packet(&write_remove_entities_packet(entity_ids))
+            .await
+    }
+
+    /// Runs a command the player typed, given without its '/', and shows
+    /// them what it replied
+    async fn run_command(&mut self, input: &str) -> Result<()> {
+        let (name, args) = parse_command(input);
+        let replies = match self.world.command(name) {
+            Some(handler) => {
+                let mut ctx = CommandContext::new(&self.player.username, &self.world);
+                handler(&mut ctx, &args);
+                ctx.into_replies()
+            }
+            None => vec![Component::translate("command.unknown.command", vec![]).color("red")],
+        };
+
+        let packets: Vec<_> = replies
+            .iter()
+            .map(|reply| write_system_chat_packet(reply, false))
+            .collect();
+        self.write_packets(&packets).await
+    }
+
+    /// Plays a sound at a position in the world
+    pub async fn send_sound_effect(&mut self, effect: &SoundEffect) -> Result<()> {
+        self.write_packet(&write_sound_effect_packet(effect)).await
+    }
+
+    /// Confirms the block changes the client predicted for its interactions
+    /// up to `sequence`, until then it keeps showing its own prediction
+    pub async fn send_block_change_ack(&mut self, sequence: i32) -> Result<()> {
+        // sequences only go up, acknowledging an older one again confirms nothing new
+        let sequence = self
+            .block_sequence
+            .map_or(sequence, |last| last.max(sequence));
+        self.block_sequence = Some(sequence);
+        self.write_packet(&write_acknowledge_block_change_packet(sequence))
+            .await
+    }
+
+    /// Queues an encoded Chunk Data packet, chunks go out in batches paced
+    /// by the client
+    pub fn queue_chunk_data(&mut self, chunk_data: BytesMut) {
+        self.chunk_sender.queue(chunk_data);
+    }
+
+    /// Sends the next batch of queued chunks, if the client is ready for one
+    async fn send_chunk_batch(&mut self) -> Result<()> {
+        let batch = self.chunk_sender.next_batch();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.write_packets(&batch).await
+    }
+
+    /// Greys out every stack of `item_id` in the player's inventory and
+    /// blocks using it for `ticks`, 0 ends a cooldown early
+    pub async fn send_cooldown(&mut self, item_id: i32, ticks: i32) -> Result<()> {
+        self.write_packet(&write_set_cooldown_packet(item_id, ticks))
+            .await
+    }
+
+    /// Shows text above the player's hotbar
+    pub async fn send_action_bar(&mut self, text: &Component) -> Result<()> {
+        self.write_packet(&write_set_action_bar_text_packet(text))
+            .await
+    }
+
+    /// Shows a title and subtitle in the middle of the screen, times are in ticks
+    pub async fn send_title(
+        &mut self,
+        title: &Component,
+        subtitle: &Component,
+        fade_in: i32,
+        stay: i32,
+        fade_out: i32,
+    ) -> Result<()> {
+        self.write_packets(&[
+            write_set_title_text_packet(title),
+            write_set_subtitle_text_packet(subtitle),
+            write_set_title_animation_times_packet(fade_in, stay, fade_out),
+        ])
+        .await
+    }
+
+    /// Moves the client to the player's position, it answers with Confirm Teleportation
+    pub async fn synchronize_position(&mut self) -> Result<()> {
+        let teleport_id = self.next_teleport_id;
+        self.next_teleport_id += 1;
+        self.pending_teleport = Some(teleport_id);
+
+        let teleport = PlayerTeleport {
+            teleport_id,
+            x: self.player.x,
+            y: self.player.y,
+            z: self.player.z,
+            yaw: self.player.yaw,
+            pitch: self.player.pitch,
+            ..Default::default()
+        };
+        self.write_packet(&write_synchronize_player_position_packet(
+            &teleport,
+            self.protocol_version,
+        ))
+        .await
     }
 
     /// Sends known packs
@@ -358,25 +1957,31 @@ impl Connection {
         PacketReader::write_string(&mut content, "core");
         PacketReader::write_string(&mut content, "1.21.1");
 
-        let mut packet = BytesMut::new();
-        PacketReader::write_varint(&mut packet, content.len() as i32);
-        packet.extend_from_slice(&content);
+        debug!("Sending known packs packet: {:?}", content);
+        self.write_packet(&content).await
+    }
 
-        debug!("Sending known packs packet: {:?}", packet);
-        self.socket.write_all(&packet).await?;
+    /// The registries sent to this client, loading the built-in ones the first
+    /// time when the server didn't hand any over. Later lookups by id have to
+    /// use the same instance the client got its registries from.
+    fn registries(&mut self) -> Result<Arc<RegistryManager>> {
+        if let Some(registries) = &self.registries {
+            return Ok(registries.clone());
+        }
 
-        Ok(())
+        let registries = Arc::new(RegistryManager::new()?);
+        self.registries = Some(registries.clone());
+        Ok(registries)
     }
 
-    /// Sends default registry data
+    /// Sends registry data and tags
     async fn send_registry_data(&mut self) -> Result<()> {
-        let manager = RegistryManager::new()?;
+        let manager = self.registries()?;
 
-        manager.write_registry_data(&mut self.socket).await?;
-        debug!("Sent registry data packet");
-
-        manager.write_update_tags(&mut self.socket).await?;
-        debug!("Sent update tags packet");
+        let mut packets = manager.registry_data_packets()?;
+        packets.push(manager.update_tags_packet()?);
+        self.write_packets(&packets).await?;
+        debug!(count = packets.len(), "Sent registry data and tags");
 
         Ok(())
     }
@@ -387,12 +1992,1899 @@ impl Connection {
         let mut packet = BytesMut::new();
         PacketReader::write_varint(&mut packet, 0x03);
 
-        let mut final_packet = BytesMut::new();
-        PacketReader::write_varint(&mut final_packet, packet.len() as i32);
-        final_packet.extend_from_slice(&packet);
+        self.write_packet(&packet).await
+    }
+}
 
-        self.socket.write_all(&final_packet).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::item::{write_item_stack, ItemStack};
+    use crate::packet::metadata::SNEAKING_FLAG;
+    use crate::packet::play::{write_chunk_batch_finished_packet, write_chunk_batch_start_packet};
+    use hmac::{Hmac, Mac};
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
+    use sha2::Sha256;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{DuplexStream, ReadBuf};
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
 
-        Ok(())
+    fn connection<S: AsyncRead + AsyncWrite + Unpin>(socket: S) -> Connection<S> {
+        Connection::new(
+            socket,
+            Arc::new(ServerConfig::default()),
+            Arc::new(SharedWorld::new()),
+        )
+    }
+
+    /// Prefixes packet content with its length, like a client would
+    fn frame(content: &[u8]) -> BytesMut {
+        let mut packet = BytesMut::new();
+        PacketReader::write_varint(&mut packet, content.len() as i32);
+        packet.extend_from_slice(content);
+        packet
+    }
+
+    /// Duplex stream that counts how many writes reach the underlying transport
+    struct CountingStream {
+        inner: DuplexStream,
+        writes: usize,
+    }
+
+    impl AsyncRead for CountingStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for CountingStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.writes += 1;
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    /// Fails every read, like a connection reset by the peer
+    struct BrokenStream {
+        writes: usize,
+    }
+
+    impl AsyncRead for BrokenStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Err(std::io::ErrorKind::ConnectionReset.into()))
+        }
+    }
+
+    impl AsyncWrite for BrokenStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.writes += 1;
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_protocol_error_disconnects_client() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        // player command action 9 doesn't exist
+        client
+            .write_all(&frame(&[PLAYER_COMMAND_PACKET_ID as u8, 0x01, 0x09, 0x00]))
+            .await
+            .unwrap();
+        let error = connection.handle_connection().await.unwrap_err();
+        assert!(matches!(error, MinecraftError::Protocol(_)));
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let disconnect = frame(&write_disconnect_packet(
+            PLAY_DISCONNECT_PACKET_ID,
+            &error.to_string(),
+        ));
+        assert_eq!(received, &disconnect[..]);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_past_the_cap_disconnects_client() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let config = ServerConfig {
+            max_buffered_bytes: 64,
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.state = ConnectionState::Status;
+
+        // the start of a packet that claims to be 1 MiB long
+        let mut partial = BytesMut::new();
+        PacketReader::write_varint(&mut partial, 1024 * 1024);
+        partial.put_slice(&[0; 100]);
+        client.write_all(&partial).await.unwrap();
+
+        let error = connection.handle_connection().await.unwrap_err();
+        assert!(
+            matches!(&error, MinecraftError::Protocol(message) if message.contains("64 bytes")),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_packet_over_the_size_limit_is_rejected() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        // rejected from the length alone, without waiting for the body
+        let mut buffer = BytesMut::new();
+        PacketReader::write_varint(&mut buffer, MAX_PACKET_SIZE as i32 + 1);
+        buffer.put_u8(SET_PLAYER_ON_GROUND_PACKET_ID as u8);
+        let error = connection.handle_packet(&mut buffer).await.unwrap_err();
+        assert!(
+            matches!(&error, MinecraftError::Protocol(message) if message.contains("over the limit")),
+            "{error:?}"
+        );
+
+        // a negative length is just as invalid
+        let mut buffer = BytesMut::from(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F][..]);
+        assert!(connection.handle_packet(&mut buffer).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_io_error_closes_without_writing() {
+        let mut connection = Connection::new(
+            BrokenStream { writes: 0 },
+            Arc::new(ServerConfig::default()),
+            Arc::new(SharedWorld::new()),
+        );
+        connection.state = ConnectionState::Play;
+        // even bytes that were already queued stay unsent
+        connection.write_packet(&[0x42]).await.unwrap();
+
+        let error = connection.handle_connection().await.unwrap_err();
+        assert!(matches!(error, MinecraftError::Io(_)));
+        assert!(connection.abandoned);
+        assert_eq!(connection.socket.get_ref().writes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_batches_queued_packets() {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let mut connection = Connection::new(
+            CountingStream {
+                inner: server,
+                writes: 0,
+            },
+            Arc::new(ServerConfig::default()),
+            Arc::new(SharedWorld::new()),
+        );
+
+        connection.send_status_response().await.unwrap();
+        connection.send_pong_response(42).await.unwrap();
+        connection.send_finish_configuration().await.unwrap();
+
+        let queued = connection.socket.buffer().len();
+        assert!(queued > 0);
+        assert_eq!(connection.socket.get_ref().writes, 0);
+
+        connection.flush().await.unwrap();
+
+        assert!(connection.socket.buffer().is_empty());
+        assert_eq!(connection.socket.get_ref().writes, 1);
+
+        let mut received = vec![0; queued];
+        client.read_exact(&mut received).await.unwrap();
+        // the last queued packet is finish configuration
+        assert_eq!(&received[queued - 2..], &[0x01, 0x03]);
+    }
+
+    #[tokio::test]
+    async fn test_finished_digging_broadcasts_block_update() {
+        let world = Arc::new(SharedWorld::new());
+        let (tx, mut rx) = Outbound::channel(16);
+        world.register_player("Alex", tx);
+
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection =
+            Connection::new(server, Arc::new(ServerConfig::default()), world.clone());
+        connection.state = ConnectionState::Play;
+
+        let position = Position::new(1, -61, -1);
+        assert_ne!(world.get_block(position), AIR);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, PLAYER_ACTION_PACKET_ID);
+        PacketReader::write_varint(&mut content, FINISHED_DIGGING);
+        PacketReader::write_position(&mut content, position);
+        content.put_i8(1); // top face
+        PacketReader::write_varint(&mut content, 7);
+
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+
+        let broadcast = rx.try_recv().unwrap();
+        assert_eq!(
+            broadcast,
+            OutboundMessage::Packet(write_block_update_packet(position, AIR))
+        );
+        assert_eq!(world.get_block(position), AIR);
+    }
+
+    #[tokio::test]
+    async fn test_use_item_is_acknowledged() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, USE_ITEM_PACKET_ID);
+        PacketReader::write_varint(&mut content, MAIN_HAND);
+        PacketReader::write_varint(&mut content, 42);
+        content.put_f32(180.0);
+        content.put_f32(10.0);
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            frame(&write_acknowledge_block_change_packet(42)).to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_interactions_echo_the_last_sequence() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        let position = Position::new(1, -61, -1);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, PLAYER_ACTION_PACKET_ID);
+        PacketReader::write_varint(&mut content, STARTED_DIGGING);
+        PacketReader::write_position(&mut content, position);
+        content.put_i8(1);
+        PacketReader::write_varint(&mut content, 7);
+        let mut buffer = frame(&content);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, USE_ITEM_ON_PACKET_ID);
+        PacketReader::write_varint(&mut content, MAIN_HAND);
+        PacketReader::write_position(&mut content, position);
+        PacketReader::write_varint(&mut content, 1);
+        content.put_f32(0.5);
+        content.put_f32(1.0);
+        content.put_f32(0.5);
+        content.put_u8(0);
+        PacketReader::write_varint(&mut content, 9);
+        buffer.extend_from_slice(&frame(&content));
+
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+        assert_eq!(connection.block_sequence, Some(9));
+
+        // a late lower sequence doesn't take back the acknowledgment
+        connection.send_block_change_ack(8).await.unwrap();
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            [
+                frame(&write_acknowledge_block_change_packet(7)),
+                frame(&write_acknowledge_block_change_packet(9)),
+                frame(&write_acknowledge_block_change_packet(9)),
+            ]
+            .concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_digging_acknowledges_block_changes() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, PLAYER_ACTION_PACKET_ID);
+        PacketReader::write_varint(&mut content, 4); // drop item
+        PacketReader::write_position(&mut content, Position::new(0, 0, 0));
+        content.put_i8(0);
+        PacketReader::write_varint(&mut content, 3);
+        assert!(connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap());
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconfiguration_resets_block_sequence() {
+        let (_client, server) = tokio::io::duplex(64 * 1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection.send_block_change_ack(40).await.unwrap();
+
+        connection.enter_configuration().await.unwrap();
+        let mut buffer = frame(&[ACKNOWLEDGE_CONFIGURATION_PACKET_ID as u8]);
+        buffer.extend_from_slice(&frame(&[FINISH_CONFIGURATION_PACKET_ID as u8]));
+        assert!(connection.handle_buffered(&mut buffer).await.unwrap());
+        assert_eq!(connection.state, ConnectionState::Play);
+
+        // the client starts over, its first dig isn't held back by the old sequence
+        connection.send_block_change_ack(1).await.unwrap();
+        assert_eq!(connection.block_sequence, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_chunk_batches_follow_the_received_rate() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        for id in 0..12 {
+            connection.queue_chunk_data(BytesMut::from(&[0x27, id][..]));
+        }
+
+        connection.send_chunk_batch().await.unwrap();
+        // nothing more until the client confirms the first batch
+        connection.send_chunk_batch().await.unwrap();
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, CHUNK_BATCH_RECEIVED_PACKET_ID);
+        content.put_f32(2.5);
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert_eq!(connection.chunk_sender.chunks_per_tick(), 2.5);
+
+        connection.send_chunk_batch().await.unwrap();
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut expected = vec![frame(&write_chunk_batch_start_packet())];
+        for id in 0..9 {
+            expected.push(frame(&[0x27, id]));
+        }
+        expected.push(frame(&write_chunk_batch_finished_packet(9)));
+        expected.push(frame(&write_chunk_batch_start_packet()));
+        expected.push(frame(&[0x27, 9]));
+        expected.push(frame(&[0x27, 10]));
+        expected.push(frame(&write_chunk_batch_finished_packet(2)));
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, expected.concat());
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+
+        connection.set_state(ConnectionState::Login).unwrap();
+        assert_eq!(connection.state, ConnectionState::Login);
+
+        connection
+            .set_state(ConnectionState::Configuration)
+            .unwrap();
+        assert_eq!(connection.state, ConnectionState::Configuration);
+    }
+
+    #[test]
+    fn test_illegal_state_transition() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.set_state(ConnectionState::Status).unwrap();
+
+        let result = connection.set_state(ConnectionState::Login);
+        assert!(matches!(result, Err(MinecraftError::Protocol(_))));
+        assert_eq!(connection.state, ConnectionState::Status);
+    }
+
+    #[tokio::test]
+    async fn test_registered_command_replies() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection
+            .world
+            .register_command("ping", Arc::new(|ctx, _args| ctx.reply("pong")));
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, CHAT_COMMAND_PACKET_ID);
+        PacketReader::write_string(&mut content, "ping");
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, CHAT_COMMAND_PACKET_ID);
+        PacketReader::write_string(&mut content, "nope");
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let unknown = Component::translate("command.unknown.command", vec![]).color("red");
+        assert_eq!(
+            received,
+            [
+                frame(&write_system_chat_packet(&Component::text("pong"), false)),
+                frame(&write_system_chat_packet(&unknown, false)),
+            ]
+            .concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_protocol_rejects_unknown_packets() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut lenient = connection(server);
+        lenient.state = ConnectionState::Status;
+        let mut buffer = frame(&[0x05, 0x01, 0x02]);
+        assert!(lenient.handle_packet(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+
+        let config = ServerConfig {
+            strict_protocol: true,
+            ..Default::default()
+        };
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut strict = Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        strict.state = ConnectionState::Status;
+        let mut buffer = frame(&[0x05, 0x01, 0x02]);
+        let result = strict.handle_packet(&mut buffer).await;
+        assert!(
+            matches!(result, Err(MinecraftError::Protocol(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_move_vehicle_moves_the_ridden_vehicle() {
+        let world = Arc::new(SharedWorld::new());
+        let (tx, mut rx) = Outbound::channel(16);
+        world.register_player("Alex", tx);
+
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection =
+            Connection::new(server, Arc::new(ServerConfig::default()), world.clone());
+        connection.state = ConnectionState::Play;
+        connection.entity_id = Some(world.entity_ids().next());
+        let boat = world.entity_ids().next();
+        connection
+            .send_set_passengers(boat, &[connection.entity_id.unwrap()])
+            .await
+            .unwrap();
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, MOVE_VEHICLE_PACKET_ID);
+        content.put_f64(1.0);
+        content.put_f64(-60.0);
+        content.put_f64(2.0);
+        content.put_f32(90.0);
+        content.put_f32(0.0);
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+
+        let moved = EntityLocation {
+            x: 1.0,
+            y: -60.0,
+            z: 2.0,
+            yaw: 90.0,
+            pitch: 0.0,
+        };
+        assert_eq!(world.entity_location(boat), Some(moved));
+        // the first move has nothing to be relative to
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_teleport_entity_packet(boat, &moved, false))
+        );
+
+        content.truncate(1);
+        content.put_f64(1.5);
+        content.put_f64(-60.0);
+        content.put_f64(2.0);
+        content.put_f32(90.0);
+        content.put_f32(0.0);
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_update_entity_position_and_rotation_packet(
+                boat,
+                (2048, 0, 0),
+                90.0,
+                0.0,
+                false
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_player_on_ground() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        let mut buffer = frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x01]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert!(connection.player.on_ground);
+
+        let mut buffer = frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x00]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert!(!connection.player.on_ground);
+    }
+
+    #[tokio::test]
+    async fn test_short_packet_does_not_read_into_next_packet() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        // on ground packet missing its boolean, then a complete one
+        let mut buffer = frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8]);
+        buffer.extend_from_slice(&frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x01]));
+
+        assert!(connection.handle_packet(&mut buffer).await.is_err());
+        assert!(!connection.player.on_ground);
+    }
+
+    #[tokio::test]
+    async fn test_trailing_bytes_are_skipped() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        // the on ground handler only reads one byte of the two it's given
+        let mut buffer = frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x00, 0x01]);
+        buffer.extend_from_slice(&frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x01]));
+
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert!(!connection.player.on_ground);
+        let warnings = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            warnings.contains("Handler did not read the whole packet")
+                && warnings.contains("remaining=1"),
+            "{warnings}"
+        );
+
+        // a fully read packet doesn't warn
+        logs.0.lock().unwrap().clear();
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert!(connection.player.on_ground);
+        assert!(buffer.is_empty());
+        assert!(logs.0.lock().unwrap().is_empty());
+    }
+
+    async fn login(compression_threshold: Option<usize>) -> Vec<u8> {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            compression_threshold,
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.state = ConnectionState::Login;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, LOGIN_START_PACKET_ID);
+        PacketReader::write_string(&mut content, "Notch");
+        content.put_u128(0);
+        connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap();
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn test_velocity_response_pipelined_with_login_acknowledged() {
+        let (_client, server) = tokio::io::duplex(64 * 1024);
+        let config = ServerConfig {
+            compression_threshold: Some(256),
+            velocity_secret: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.state = ConnectionState::Login;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, LOGIN_START_PACKET_ID);
+        PacketReader::write_string(&mut content, "Notch");
+        content.put_u128(0);
+        connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap();
+        let message_id = *connection.plugin_requests.keys().next().unwrap();
+
+        let mut payload = BytesMut::new();
+        PacketReader::write_varint(&mut payload, MODERN_DEFAULT_VERSION as i32);
+        PacketReader::write_string(&mut payload, "203.0.113.7");
+        payload.put_u128(0x1234);
+        PacketReader::write_string(&mut payload, "Steve");
+        PacketReader::write_varint(&mut payload, 0);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(&payload);
+        let mut data = mac.finalize().into_bytes().to_vec();
+        data.extend_from_slice(&payload);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, LOGIN_PLUGIN_RESPONSE_PACKET_ID);
+        PacketReader::write_varint(&mut content, message_id);
+        content.put_u8(1);
+        content.extend_from_slice(&data);
+
+        // the client sends Login Acknowledged compressed as soon as it reads
+        // Set Compression, often in the same read as its plugin response
+        let mut buffer = frame(&content);
+        buffer.extend_from_slice(&frame(&[0x00, LOGIN_ACKNOWLEDGED_PACKET_ID as u8]));
+
+        assert!(connection.handle_buffered(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+        assert_eq!(connection.player.username, "Steve");
+        assert_eq!(connection.state, ConnectionState::Configuration);
+    }
+
+    #[tokio::test]
+    async fn test_encryption_response_pipelined_with_encrypted_packet() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let n = socket.read(&mut request).await.unwrap();
+            assert!(request[..n].starts_with(b"GET /session/minecraft/hasJoined?username=Notch"));
+            let body =
+                r#"{"id": "069a79f444e94726a5befca90e38aaf5", "name": "Notch", "properties": []}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let (_client, server) = tokio::io::duplex(64 * 1024);
+        let config = ServerConfig {
+            online_mode: true,
+            session_server: format!("http://{address}"),
+            compression_threshold: None,
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.state = ConnectionState::Login;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, LOGIN_START_PACKET_ID);
+        PacketReader::write_string(&mut content, "Notch");
+        content.put_u128(0);
+        connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap();
+
+        let public_key =
+            RsaPublicKey::from_public_key_der(&ServerKey::get().public_key_der).unwrap();
+        let mut rng = rand::thread_rng();
+        let shared_secret = [7; 16];
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, ENCRYPTION_RESPONSE_PACKET_ID);
+        for data in [&shared_secret[..], &connection.verify_token] {
+            let encrypted = public_key.encrypt(&mut rng, Pkcs1v15Encrypt, data).unwrap();
+            PacketReader::write_varint(&mut content, encrypted.len() as i32);
+            content.extend_from_slice(&encrypted);
+        }
+
+        // everything after the Encryption Response is already encrypted,
+        // even when it arrives in the same read
+        let mut acknowledged = frame(&[LOGIN_ACKNOWLEDGED_PACKET_ID as u8]);
+        PacketCipher::new(&shared_secret)
+            .unwrap()
+            .encrypt(&mut acknowledged);
+        let mut buffer = frame(&content);
+        buffer.extend_from_slice(&acknowledged);
+
+        assert!(connection.handle_buffered(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+        assert_eq!(
+            connection.player.uuid,
+            Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+        assert_eq!(connection.state, ConnectionState::Configuration);
+    }
+
+    #[tokio::test]
+    async fn test_login_without_compression() {
+        let received = login(None).await;
+
+        // the first packet is login success, framed without a data length
+        assert_eq!(received[1], LOGIN_SUCCESS_PACKET_ID as u8);
+        assert_eq!(received[0] as usize, received.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn test_login_with_compression() {
+        let received = login(Some(256)).await;
+
+        // set compression with the configured threshold, still uncompressed
+        assert_eq!(&received[..4], b"\x03\x03\x80\x02");
+        // login success in the compressed format, too small to be compressed
+        assert_eq!(&received[5..7], b"\x00\x02");
+        assert_eq!(received[4] as usize, received.len() - 5);
+    }
+
+    #[tokio::test]
+    async fn test_play_login_carries_game_mode() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            game_mode: GameMode::Creative,
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.state = ConnectionState::Configuration;
+
+        let mut buffer = frame(&[FINISH_CONFIGURATION_PACKET_ID as u8]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        connection.flush().await.unwrap();
+
+        let mut received = vec![0; 256];
+        let n = client.read(&mut received).await.unwrap();
+        let mut buf = &received[..n];
+
+        PacketReader::read_varint(&mut buf).unwrap(); // packet length
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x2B);
+        PacketReader::read_int(&mut buf).unwrap(); // entity id
+        PacketReader::read_boolean(&mut buf).unwrap(); // hardcore
+        PacketReader::read_identifier_array(&mut buf).unwrap(); // dimension names
+        for _ in 0..3 {
+            PacketReader::read_varint(&mut buf).unwrap(); // max players, view & simulation distance
+        }
+        for _ in 0..3 {
+            PacketReader::read_boolean(&mut buf).unwrap(); // debug info, respawn screen, crafting
+        }
+        PacketReader::read_varint(&mut buf).unwrap(); // dimension type
+        PacketReader::read_string(&mut buf).unwrap(); // dimension name
+        PacketReader::read_long(&mut buf).unwrap(); // hashed seed
+
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 1);
+        assert_eq!(PacketReader::read_byte(&mut buf).unwrap(), -1);
+    }
+
+    #[tokio::test]
+    async fn test_play_login_carries_debug_info_and_respawn_screen() {
+        for (reduced_debug_info, enable_respawn_screen) in [(true, false), (false, true)] {
+            let (mut client, server) = tokio::io::duplex(1024);
+            let config = ServerConfig {
+                reduced_debug_info,
+                enable_respawn_screen,
+                ..Default::default()
+            };
+            let mut connection =
+                Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+            connection.send_play_login().await.unwrap();
+            connection.flush().await.unwrap();
+
+            let mut received = vec![0; 256];
+            let n = client.read(&mut received).await.unwrap();
+            let mut buf = &received[..n];
+
+            PacketReader::read_varint(&mut buf).unwrap(); // packet length
+            assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x2B);
+            PacketReader::read_int(&mut buf).unwrap(); // entity id
+            PacketReader::read_boolean(&mut buf).unwrap(); // hardcore
+            PacketReader::read_identifier_array(&mut buf).unwrap(); // dimension names
+            for _ in 0..3 {
+                PacketReader::read_varint(&mut buf).unwrap(); // max players, view & simulation distance
+            }
+            assert_eq!(
+                PacketReader::read_boolean(&mut buf).unwrap(),
+                reduced_debug_info
+            );
+            assert_eq!(
+                PacketReader::read_boolean(&mut buf).unwrap(),
+                enable_respawn_screen
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_play_login_body() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            hardcore: true,
+            seed: 12345,
+            limited_crafting: true,
+            portal_cooldown: 300,
+            enforce_secure_chat: true,
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.entity_id = Some(42);
+        connection.send_play_login().await.unwrap();
+        connection.flush().await.unwrap();
+
+        let mut received = vec![0; 256];
+        let n = client.read(&mut received).await.unwrap();
+        let mut buf = &received[..n];
+
+        let length = PacketReader::read_varint(&mut buf).unwrap() as usize;
+        assert_eq!(buf.len(), length);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x2B);
+        assert_eq!(PacketReader::read_int(&mut buf).unwrap(), 42); // entity id
+        assert!(PacketReader::read_boolean(&mut buf).unwrap()); // hardcore
+        assert_eq!(
+            PacketReader::read_identifier_array(&mut buf).unwrap(),
+            vec![("minecraft".to_string(), "overworld".to_string())]
+        );
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 100); // max players
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 10); // view distance
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 10); // simulation distance
+        assert!(!PacketReader::read_boolean(&mut buf).unwrap()); // reduced debug info
+        assert!(PacketReader::read_boolean(&mut buf).unwrap()); // respawn screen
+        assert!(PacketReader::read_boolean(&mut buf).unwrap()); // limited crafting
+        let overworld = connection
+            .registries()
+            .unwrap()
+            .dimension_type_id(OVERWORLD);
+        assert_eq!(PacketReader::read_varint(&mut buf).ok(), overworld); // dimension type
+        assert_eq!(
+            PacketReader::read_string(&mut buf).unwrap(),
+            "minecraft:overworld"
+        );
+        // sha-256 of 12345 as little-endian bytes starts with e1 54 35 51 24 91 13 04
+        assert_eq!(
+            PacketReader::read_long(&mut buf).unwrap(),
+            i64::from_le_bytes([0xe1, 0x54, 0x35, 0x51, 0x24, 0x91, 0x13, 0x04])
+        );
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 0); // game mode
+        assert_eq!(PacketReader::read_byte(&mut buf).unwrap(), -1); // previous game mode
+        assert!(!PacketReader::read_boolean(&mut buf).unwrap()); // debug
+        assert!(PacketReader::read_boolean(&mut buf).unwrap()); // flat
+        assert!(!PacketReader::read_boolean(&mut buf).unwrap()); // death location
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 300); // portal cooldown
+        assert!(PacketReader::read_boolean(&mut buf).unwrap()); // secure chat
+        assert!(buf.is_empty());
+    }
+
+    fn handshake(next_state: i32) -> BytesMut {
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, HANDSHAKE_PACKET_ID);
+        PacketReader::write_varint(&mut content, PROTOCOL_VERSION);
+        PacketReader::write_string(&mut content, "localhost");
+        content.put_u16(25565);
+        PacketReader::write_varint(&mut content, next_state);
+        frame(&content)
+    }
+
+    /// Splits a stream of uncompressed frames into (packet id, body) pairs
+    fn split_frames(mut buf: &[u8]) -> Vec<(i32, Vec<u8>)> {
+        let mut packets = Vec::new();
+        while !buf.is_empty() {
+            let length = PacketReader::read_varint(&mut buf).unwrap() as usize;
+            let (mut packet, rest) = buf.split_at(length);
+            buf = rest;
+            let packet_id = PacketReader::read_varint(&mut packet).unwrap();
+            packets.push((packet_id, packet.to_vec()));
+        }
+        packets
+    }
+
+    fn status_ping_bytes() -> BytesMut {
+        let mut bytes = handshake(1);
+        bytes.extend_from_slice(&frame(&[STATUS_REQUEST_PACKET_ID as u8]));
+        let mut ping = BytesMut::new();
+        PacketReader::write_varint(&mut ping, PING_REQUEST_PACKET_ID);
+        ping.put_i64(42);
+        bytes.extend_from_slice(&frame(&ping));
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_packets_arriving_one_byte_at_a_time() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let mut connection = connection(server);
+        let running = tokio::spawn(async move { connection.handle_connection().await });
+
+        for byte in status_ping_bytes() {
+            client.write_all(&[byte]).await.unwrap();
+            // give the connection a chance to read this byte on its own
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        running.await.unwrap().unwrap();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let packets = split_frames(&received);
+        // one status response and one pong, nothing handled twice or dropped
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].0, STATUS_RESPONSE_PACKET_ID);
+        assert_eq!(packets[1], (0x01, 42i64.to_be_bytes().to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_packets_split_across_the_same_read() {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let mut connection = connection(server);
+        let bytes = status_ping_bytes();
+        let handshake_length = handshake(1).len();
+
+        // the handshake and the first byte of the status request, then the
+        // rest of the status request and half of the ping
+        let splits = [handshake_length + 1, bytes.len() - 5, bytes.len()];
+        let mut buffer = BytesMut::new();
+        let mut start = 0;
+        let mut keep_open = true;
+        for end in splits {
+            buffer.extend_from_slice(&bytes[start..end]);
+            keep_open = connection.handle_buffered(&mut buffer).await.unwrap();
+            start = end;
+        }
+        // the pong ends the connection
+        assert!(!keep_open);
+        connection.close().await;
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let packets = split_frames(&received);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].0, STATUS_RESPONSE_PACKET_ID);
+        assert_eq!(packets[1], (0x01, 42i64.to_be_bytes().to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_reconfiguration_round_trip() {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection.joined = true;
+        connection.entity_id = Some(
+            connection
+                .world
+                .register_player("Steve", connection.outbound_tx.clone()),
+        );
+
+        // an acknowledgment nobody asked for is rejected
+        let mut buffer = frame(&[ACKNOWLEDGE_CONFIGURATION_PACKET_ID as u8]);
+        assert!(connection.handle_packet(&mut buffer).await.is_err());
+
+        connection.enter_configuration().await.unwrap();
+        assert_eq!(connection.state, ConnectionState::Play);
+
+        let mut buffer = frame(&[ACKNOWLEDGE_CONFIGURATION_PACKET_ID as u8]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert_eq!(connection.state, ConnectionState::Configuration);
+        assert_eq!(connection.world.online_count(), 0);
+
+        let mut buffer = frame(&[FINISH_CONFIGURATION_PACKET_ID as u8]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert_eq!(connection.state, ConnectionState::Play);
+        assert_eq!(connection.world.online_count(), 1);
+        connection.close().await;
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let packet_ids: Vec<i32> = split_frames(&received)
+            .into_iter()
+            .map(|(packet_id, _)| packet_id)
+            .collect();
+        // Start Configuration, then Known Packs, then Login (play) again
+        assert_eq!(&packet_ids[..3], [0x69, 0x0E, 0x2B]);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_transfer_logs_in() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+
+        let mut buffer = handshake(NEXT_STATE_TRANSFER);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert_eq!(connection.state, ConnectionState::Login);
+        assert!(connection.transferred);
+    }
+
+    #[test]
+    fn test_split_forge_marker() {
+        assert_eq!(split_forge_marker("localhost"), ("localhost", None));
+        assert_eq!(
+            split_forge_marker("example.com\0FML3\0"),
+            ("example.com", Some("FML3"))
+        );
+        assert_eq!(
+            split_forge_marker("example.com\0FML\0"),
+            ("example.com", Some("FML"))
+        );
+        // BungeeCord forwarding puts the address, uuid and profile there instead
+        assert_eq!(
+            split_forge_marker("example.com\0localhost\0abc"),
+            ("example.com", None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forge_client_is_told_the_server_is_not_modded() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, HANDSHAKE_PACKET_ID);
+        PacketReader::write_varint(&mut content, PROTOCOL_VERSION);
+        PacketReader::write_string(&mut content, "localhost\0FML3\0");
+        content.put_u16(25565);
+        PacketReader::write_varint(&mut content, NEXT_STATE_LOGIN);
+        let mut buffer = frame(&content);
+        assert!(!connection.handle_packet(&mut buffer).await.unwrap());
+        connection.flush().await.unwrap();
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            frame(&write_login_disconnect_packet(NOT_MODDED_REASON))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forge_client_can_ping() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, HANDSHAKE_PACKET_ID);
+        PacketReader::write_varint(&mut content, PROTOCOL_VERSION);
+        PacketReader::write_string(&mut content, "localhost\0FML3\0");
+        content.put_u16(25565);
+        PacketReader::write_varint(&mut content, NEXT_STATE_STATUS);
+        let mut buffer = frame(&content);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert_eq!(connection.state, ConnectionState::Status);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_invalid_next_state_disconnects() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+
+        let mut buffer = handshake(99);
+        assert!(!connection.handle_packet(&mut buffer).await.unwrap());
+        connection.flush().await.unwrap();
+
+        let mut received = vec![0; 256];
+        let n = client.read(&mut received).await.unwrap();
+        let mut buf = &received[..n];
+        PacketReader::read_varint(&mut buf).unwrap(); // packet length
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x00);
+        assert!(PacketReader::read_string(&mut buf)
+            .unwrap()
+            .contains("Unexpected next state: 99"));
+        assert_eq!(connection.state, ConnectionState::Handshake);
+    }
+
+    #[tokio::test]
+    async fn test_zero_length_packet_is_rejected() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+
+        let mut buffer = BytesMut::from(&[0x00, 0x01, 0x00][..]);
+        let error = connection.handle_packet(&mut buffer).await.unwrap_err();
+        assert!(
+            matches!(error, MinecraftError::Protocol(message) if message.contains("length is 0"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_flushes_pending_bytes() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection.entity_id = Some(
+            connection
+                .world
+                .register_player("Steve", connection.outbound_tx.clone()),
+        );
+        assert_eq!(connection.world.online_count(), 1);
+
+        // queued but never flushed by the read loop
+        connection.write_packet(&[0x42]).await.unwrap();
+        client.shutdown().await.unwrap();
+        connection.handle_connection().await.unwrap();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, [0x01, 0x42]);
+        assert_eq!(connection.world.online_count(), 0);
+    }
+
+    /// A player connection whose queue already overflowed before it got to run
+    fn overflowed_connection<S: AsyncRead + AsyncWrite + Unpin>(socket: S) -> Connection<S> {
+        let config = ServerConfig {
+            outbound_queue_capacity: 2,
+            ..ServerConfig::default()
+        };
+        let world = Arc::new(SharedWorld::new());
+        let mut connection = Connection::new(socket, Arc::new(config), world.clone());
+        connection.state = ConnectionState::Play;
+        connection.entity_id = Some(world.register_player("Steve", connection.outbound_tx.clone()));
+        for _ in 0..3 {
+            world.broadcast(&BytesMut::from(&[0x42][..]));
+        }
+        connection
+    }
+
+    #[tokio::test]
+    async fn test_full_outbound_queue_disconnects_client() {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let mut connection = overflowed_connection(server);
+
+        tokio::time::timeout(Duration::from_secs(5), connection.handle_connection())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let disconnect = frame(&write_disconnect_packet(
+            PLAY_DISCONNECT_PACKET_ID,
+            "Client too slow",
+        ));
+        assert!(received.ends_with(&disconnect));
+        assert_eq!(connection.world.online_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_does_not_block_close() {
+        // too small for even the disconnect packet, and the client never reads
+        let (_client, server) = tokio::io::duplex(4);
+        let mut connection = overflowed_connection(server);
+
+        tokio::time::timeout(Duration::from_secs(5), connection.handle_connection())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(connection.abandoned);
+        assert_eq!(connection.world.online_count(), 0);
+    }
+
+    async fn status_response(config: ServerConfig) -> serde_json::Value {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+        connection.state = ConnectionState::Status;
+
+        let mut buffer = frame(&[STATUS_REQUEST_PACKET_ID as u8]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        connection.flush().await.unwrap();
+
+        let mut received = vec![0; 4096];
+        let n = client.read(&mut received).await.unwrap();
+        let mut buf = &received[..n];
+        PacketReader::read_varint(&mut buf).unwrap(); // packet length
+        PacketReader::read_varint(&mut buf).unwrap(); // packet id
+        serde_json::from_str(&PacketReader::read_string(&mut buf).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_status_sample_reflects_config() {
+        let steve = Uuid::from_u128(1);
+        let alex = Uuid::from_u128(2);
+        let config = ServerConfig {
+            sample: vec![
+                ("Steve".to_string(), steve),
+                ("Alex".to_string(), alex),
+                ("Notch".to_string(), Uuid::from_u128(3)),
+            ],
+            max_sample: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            status_response(config).await["players"]["sample"],
+            json!([
+                { "name": "Steve", "id": steve.to_string() },
+                { "name": "Alex", "id": alex.to_string() },
+            ])
+        );
+        assert_eq!(
+            status_response(ServerConfig::default()).await["players"]["sample"],
+            json!([])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_secure_chat() {
+        let response = status_response(ServerConfig::default()).await;
+        assert_eq!(response["enforcesSecureChat"], json!(false));
+
+        let config = ServerConfig {
+            enforce_secure_chat: true,
+            ..Default::default()
+        };
+        let response = status_response(config).await;
+        assert_eq!(response["enforcesSecureChat"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn test_status_description_is_motd() {
+        let config = ServerConfig {
+            motd: Component::from_legacy("&aHello"),
+            ..Default::default()
+        };
+        let response = status_response(config).await;
+        assert_eq!(
+            response["description"],
+            json!({ "text": "", "extra": [{ "text": "Hello", "color": "green" }] })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_data_is_written_at_once() {
+        let (mut client, server) = tokio::io::duplex(1 << 20);
+        let mut connection = Connection::new(
+            CountingStream {
+                inner: server,
+                writes: 0,
+            },
+            Arc::new(ServerConfig::default()),
+            Arc::new(SharedWorld::new()),
+        );
+
+        connection.send_registry_data().await.unwrap();
+        connection.flush().await.unwrap();
+        assert_eq!(connection.socket.get_ref().writes, 1);
+        drop(connection);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+
+        let mut packets = Vec::new();
+        let mut buf = &received[..];
+        while !buf.is_empty() {
+            let length = PacketReader::read_varint(&mut buf).unwrap() as usize;
+            let (mut packet, rest) = buf.split_at(length);
+            buf = rest;
+
+            let packet_id = PacketReader::read_varint(&mut packet).unwrap();
+            let name = if packet_id == 0x07 {
+                PacketReader::read_string(&mut packet).unwrap()
+            } else {
+                String::new()
+            };
+            packets.push((packet_id, name));
+        }
+
+        let registries = [
+            "worldgen/biome",
+            "chat_type",
+            "trim_pattern",
+            "trim_material",
+            "wolf_variant",
+            "painting_variant",
+            "dimension_type",
+            "damage_type",
+            "banner_pattern",
+            "enchantment",
+            "jukebox_song",
+        ];
+        let mut expected: Vec<_> = registries
+            .iter()
+            .map(|name| (0x07, format!("minecraft:{name}")))
+            .collect();
+        expected.push((0x0D, String::new()));
+        assert_eq!(packets, expected);
+    }
+
+    #[tokio::test]
+    async fn test_only_operators_change_difficulty() {
+        let config = ServerConfig {
+            operators: vec!["Steve".to_string()],
+            ..Default::default()
+        };
+        let world = Arc::new(SharedWorld::new());
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server, Arc::new(config), world.clone());
+        connection.state = ConnectionState::Play;
+
+        connection.player.username = "Alex".to_string();
+        let mut buffer = frame(&[CHANGE_DIFFICULTY_PACKET_ID as u8, 3]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert_eq!(world.difficulty(), Difficulty::Normal);
+
+        connection.player.username = "Steve".to_string();
+        let mut buffer = frame(&[CHANGE_DIFFICULTY_PACKET_ID as u8, 3]);
+        connection.handle_packet(&mut buffer).await.unwrap();
+        assert_eq!(world.difficulty(), Difficulty::Hard);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_packets_yield_to_other_tasks() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        let mut buffer = BytesMut::new();
+        for _ in 0..PACKETS_PER_YIELD * 4 {
+            buffer.extend_from_slice(&frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x01]));
+        }
+        // the start of a packet whose body hasn't arrived yet
+        buffer.extend_from_slice(&[0x02, SET_PLAYER_ON_GROUND_PACKET_ID as u8]);
+
+        // on the current-thread runtime this only runs if the handler yields
+        let other = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = other.clone();
+        tokio::spawn(async move { flag.store(true, std::sync::atomic::Ordering::SeqCst) });
+
+        assert!(connection.handle_buffered(&mut buffer).await.unwrap());
+        assert!(other.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(&buffer[..], &[0x02, SET_PLAYER_ON_GROUND_PACKET_ID as u8]);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_login_kicks_first_connection() {
+        let world = Arc::new(SharedWorld::new());
+        let config = Arc::new(ServerConfig {
+            compression_threshold: None,
+            ..Default::default()
+        });
+
+        let (mut first_client, first_server) = tokio::io::duplex(1024);
+        let mut first = Connection::new(first_server, config.clone(), world.clone());
+        first.state = ConnectionState::Play;
+        first.player.username = "Steve".to_string();
+        first.entity_id = Some(world.register_player("Steve", first.outbound_tx.clone()));
+
+        let (_second_client, second_server) = tokio::io::duplex(1024);
+        let mut second = Connection::new(second_server, config, world.clone());
+        second.state = ConnectionState::Login;
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, LOGIN_START_PACKET_ID);
+        PacketReader::write_string(&mut content, "Steve");
+        content.put_u128(0);
+        second.handle_packet(&mut frame(&content)).await.unwrap();
+
+        // the first connection disconnects as soon as it sees the kick
+        first.handle_connection().await.unwrap();
+        assert_eq!(world.online_count(), 0);
+
+        let mut received = Vec::new();
+        first_client.read_to_end(&mut received).await.unwrap();
+        let mut buf = &received[..];
+        PacketReader::read_varint(&mut buf).unwrap(); // packet length
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x1D);
+        assert_eq!(
+            crate::nbt::Nbt::read_nameless(&mut buf).unwrap(),
+            crate::nbt::Nbt::String("You logged in from another location".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_silent_connection_is_dropped_after_handshake_timeout() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            handshake_timeout: std::time::Duration::from_millis(50),
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+
+        tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            connection.handle_connection(),
+        )
+        .await
+        .expect("connection should give up on the handshake")
+        .unwrap();
+
+        // the client sees the connection closed
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_sets_peer_address() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            proxy_protocol: true,
+            ..Default::default()
+        };
+        let mut connection =
+            Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()))
+                .with_peer_addr("10.0.0.2:40000".parse().unwrap());
+
+        let mut data = crate::proxy_protocol::SIGNATURE.to_vec();
+        data.extend_from_slice(&[0x21, 0x11, 0x00, 0x0C]);
+        data.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1]);
+        data.extend_from_slice(&51234u16.to_be_bytes());
+        data.extend_from_slice(&25565u16.to_be_bytes());
+        data.extend_from_slice(&handshake(NEXT_STATE_STATUS));
+        client.write_all(&data).await.unwrap();
+        client.shutdown().await.unwrap();
+
+        connection.handle_connection().await.unwrap();
+        assert_eq!(
+            connection.peer_addr,
+            Some("203.0.113.7:51234".parse().unwrap())
+        );
+        assert_eq!(connection.state, ConnectionState::Status);
+    }
+
+    #[tokio::test]
+    async fn test_join_and_leave_messages() {
+        let world = Arc::new(SharedWorld::new());
+        let (survivor_tx, mut survivor_rx) = Outbound::channel(16);
+        world.register_player("Alex", survivor_tx);
+
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut steve = Connection::new(server, Arc::new(ServerConfig::default()), world.clone());
+        steve.state = ConnectionState::Play;
+        steve.player.username = "Steve".to_string();
+        steve.entity_id = Some(world.register_player("Steve", steve.outbound_tx.clone()));
+        steve.synchronize_position().await.unwrap();
+
+        // nobody is told before the client confirms its spawn position
+        assert!(survivor_rx.try_recv().is_err());
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, CONFIRM_TELEPORTATION_PACKET_ID);
+        PacketReader::write_varint(&mut content, 0);
+        steve.handle_packet(&mut frame(&content)).await.unwrap();
+
+        let name = vec![Component::text("Steve")];
+        let joined =
+            Component::translate("multiplayer.player.joined", name.clone()).color("yellow");
+        assert_eq!(
+            survivor_rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_system_chat_packet(&joined, false))
+        );
+
+        let entity_id = steve.entity_id.unwrap();
+        steve.close().await;
+        assert_eq!(
+            survivor_rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_remove_entities_packet(&[entity_id]))
+        );
+        let left = Component::translate("multiplayer.player.left", name).color("yellow");
+        assert_eq!(
+            survivor_rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_system_chat_packet(&left, false))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_title_sends_three_packets() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+
+        connection
+            .send_title(
+                &Component::text("Welcome"),
+                &Component::text("to octavia"),
+                10,
+                70,
+                20,
+            )
+            .await
+            .unwrap();
+        connection.close().await;
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let mut buf = &received[..];
+
+        let mut packets = Vec::new();
+        while !buf.is_empty() {
+            let length = PacketReader::read_varint(&mut buf).unwrap() as usize;
+            packets.push(&buf[..length]);
+            buf = &buf[length..];
+        }
+        assert_eq!(packets.len(), 3);
+
+        let mut title = packets[0];
+        assert_eq!(PacketReader::read_varint(&mut title).unwrap(), 0x65);
+        let mut subtitle = packets[1];
+        assert_eq!(PacketReader::read_varint(&mut subtitle).unwrap(), 0x63);
+
+        // 0x66 Packet ID, then Fade In, Stay and Fade Out as ints
+        assert_eq!(
+            packets[2],
+            b"\x66\x00\x00\x00\x0A\x00\x00\x00\x46\x00\x00\x00\x14"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_packet_0x00_is_confirm_teleportation() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection.pending_teleport = Some(3);
+
+        // the same bytes are a Status Request in Status and Login Start in Login
+        let mut buffer = frame(&[CONFIRM_TELEPORTATION_PACKET_ID as u8, 0x03]);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert!(buffer.is_empty());
+        assert_eq!(connection.pending_teleport, None);
+        assert!(connection.joined);
+
+        // confirming a teleport has no response
+        connection.close().await;
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sneaking_is_shown_to_other_players() {
+        let world = Arc::new(SharedWorld::new());
+        let (other_tx, mut other_rx) = Outbound::channel(16);
+        world.register_player("Alex", other_tx);
+
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection =
+            Connection::new(server, Arc::new(ServerConfig::default()), world.clone());
+        connection.state = ConnectionState::Play;
+        let entity_id = world.register_player("Steve", connection.outbound_tx.clone());
+        connection.entity_id = Some(entity_id);
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, PLAYER_COMMAND_PACKET_ID);
+        PacketReader::write_varint(&mut content, entity_id);
+        PacketReader::write_varint(&mut content, 0); // start sneaking
+        PacketReader::write_varint(&mut content, 0);
+        connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap();
+
+        assert!(connection.player.sneaking);
+        let metadata = EntityMetadata::new()
+            .byte(ENTITY_FLAGS_INDEX, SNEAKING_FLAG)
+            .pose(POSE_INDEX, Pose::Sneaking);
+        assert_eq!(
+            other_rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_entity_metadata_packet(entity_id, &metadata))
+        );
+        // the sneaking player isn't told about itself
+        assert!(connection.outbound_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_round_trip_measures_latency() {
+        let world = Arc::new(SharedWorld::new());
+        let (other_tx, mut other_rx) = Outbound::channel(16);
+        world.register_player("Alex", other_tx);
+
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server, Arc::new(ServerConfig::default()), world);
+        connection.state = ConnectionState::Play;
+        connection.player.uuid = Uuid::from_u128(7);
+        connection.entity_id = Some(connection.world.entity_ids().next());
+
+        connection.send_keep_alive().await.unwrap();
+        let (id, _) = connection.pending_keep_alive.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, KEEP_ALIVE_PACKET_ID);
+        content.put_i64(id);
+        connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap();
+
+        let latency = connection.latency_ms();
+        assert!((20..1000).contains(&latency), "latency was {latency}");
+        assert!(connection.pending_keep_alive.is_none());
+        assert_eq!(connection.world.metrics().ping().average_ms, latency as f64);
+
+        // the tab list of everyone else is updated
+        assert_eq!(
+            other_rx.try_recv().unwrap(),
+            OutboundMessage::Packet(write_player_info_latency_packet(&[(
+                Uuid::from_u128(7),
+                latency
+            )]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_item_component_skips_only_the_packet() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection.player.game_mode = GameMode::Creative;
+
+        // a stone with an enchantments component, which has no codec yet
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, SET_CREATIVE_MODE_SLOT_PACKET_ID);
+        content.put_i16(36);
+        // count, item id, 1 added, 0 removed, then enchantments (9): none, shown
+        content.put_slice(&[0x01, 0x01, 0x01, 0x00, 0x09, 0x00, 0x01]);
+        let mut buffer = frame(&content);
+        buffer.extend_from_slice(&frame(&[SET_PLAYER_ON_GROUND_PACKET_ID as u8, 0x01]));
+
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert_eq!(connection.player.inventory.get(36), None);
+        assert!(connection.handle_packet(&mut buffer).await.unwrap());
+        assert!(connection.player.on_ground);
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_creative_mode_slot_updates_inventory() {
+        let (_client, server) = tokio::io::duplex(1024);
+        let mut connection = connection(server);
+        connection.state = ConnectionState::Play;
+        connection.player.game_mode = GameMode::Creative;
+
+        let mut content = BytesMut::new();
+        PacketReader::write_varint(&mut content, SET_CREATIVE_MODE_SLOT_PACKET_ID);
+        content.put_i16(36);
+        write_item_stack(&mut content, Some(&ItemStack::new(1, 64)));
+        connection
+            .handle_packet(&mut frame(&content))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            connection.player.inventory.get(36),
+            Some(&ItemStack::new(1, 64))
+        );
+    }
+
+    /// Collects formatted log output so tests can look at it
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connections_log_with_their_id() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut ids = Vec::new();
+        for port in [1000, 2000] {
+            let (client, server) = tokio::io::duplex(1024);
+            let mut connection =
+                connection(server).with_peer_addr(SocketAddr::from(([10, 0, 0, 1], port)));
+            ids.push(connection.id());
+
+            // the peer hanging up is logged inside the connection's span
+            drop(client);
+            connection.handle_connection().await.unwrap();
+        }
+        assert!(ids[0] < ids[1]);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        for (id, port) in ids.iter().zip([1000, 2000]) {
+            let span = format!("connection{{id={id} peer=10.0.0.1:{port}}}");
+            assert!(logs.contains(&span), "no {span} in {logs}");
+        }
+    }
+
+    /// Clientbound packets read back field by field, so a field written in
+    /// the wrong order or encoding shows up as a mismatch
+    mod symmetry {
+        use super::*;
+        use crate::player::ProfileProperty;
+        use serde_json::Value;
+
+        /// Everything the connection wrote, split into (packet id, body)
+        async fn sent(
+            mut connection: Connection<DuplexStream>,
+            mut client: DuplexStream,
+        ) -> Vec<(i32, Vec<u8>)> {
+            connection.flush().await.unwrap();
+            drop(connection);
+
+            let mut received = Vec::new();
+            client.read_to_end(&mut received).await.unwrap();
+            split_frames(&received)
+        }
+
+        #[tokio::test]
+        async fn test_status_response() {
+            let config = ServerConfig {
+                motd: Component::text("Symmetry"),
+                enforce_secure_chat: true,
+                sample: vec![("Alex".to_string(), Uuid::from_u128(7))],
+                ..Default::default()
+            };
+            let (client, server) = tokio::io::duplex(4096);
+            let mut connection =
+                Connection::new(server, Arc::new(config), Arc::new(SharedWorld::new()));
+            connection.send_status_response().await.unwrap();
+
+            let packets = sent(connection, client).await;
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].0, STATUS_RESPONSE_PACKET_ID);
+            let mut buf = PacketBuf::new(&packets[0].1);
+            let response: Value = serde_json::from_str(&buf.read_string().unwrap()).unwrap();
+            assert!(buf.is_empty());
+
+            assert_eq!(response["version"]["protocol"], PROTOCOL_VERSION);
+            assert_eq!(response["players"]["online"], 0);
+            assert_eq!(response["players"]["sample"][0]["name"], "Alex");
+            assert_eq!(
+                response["players"]["sample"][0]["id"],
+                Uuid::from_u128(7).to_string()
+            );
+            assert_eq!(
+                response["description"],
+                Component::text("Symmetry").to_json()
+            );
+            assert_eq!(response["enforcesSecureChat"], true);
+        }
+
+        #[tokio::test]
+        async fn test_pong_response() {
+            let (client, server) = tokio::io::duplex(1024);
+            let mut connection = connection(server);
+            connection.send_pong_response(i64::MIN + 1).await.unwrap();
+
+            let packets = sent(connection, client).await;
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].0, PING_REQUEST_PACKET_ID);
+            let mut buf = PacketBuf::new(&packets[0].1);
+            assert_eq!(buf.read_long().unwrap(), i64::MIN + 1);
+            assert!(buf.is_empty());
+        }
+
+        fn properties() -> Vec<ProfileProperty> {
+            vec![
+                ProfileProperty {
+                    name: "textures".to_string(),
+                    value: "e30=".to_string(),
+                    signature: Some("c2ln".to_string()),
+                },
+                ProfileProperty {
+                    name: "unsigned".to_string(),
+                    value: "dmFsdWU=".to_string(),
+                    signature: None,
+                },
+            ]
+        }
+
+        #[tokio::test]
+        async fn test_login_success() {
+            let (client, server) = tokio::io::duplex(1024);
+            let mut connection = connection(server);
+            connection.player.username = "Notch".to_string();
+            connection.player.uuid = Uuid::from_u128(0x069a79f444e94726a5befca90e38aaf5);
+            connection.player.properties = properties();
+            connection.send_login_success().await.unwrap();
+
+            let packets = sent(connection, client).await;
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].0, LOGIN_SUCCESS_PACKET_ID);
+            let mut buf = PacketBuf::new(&packets[0].1);
+            assert_eq!(
+                buf.read_uuid().unwrap(),
+                Uuid::from_u128(0x069a79f444e94726a5befca90e38aaf5)
+            );
+            assert_eq!(buf.read_string().unwrap(), "Notch");
+
+            let mut properties = Vec::new();
+            for _ in 0..buf.read_varint().unwrap() {
+                let name = buf.read_string().unwrap();
+                let value = buf.read_string().unwrap();
+                let signature = match buf.read_boolean().unwrap() {
+                    true => Some(buf.read_string().unwrap()),
+                    false => None,
+                };
+                properties.push(ProfileProperty {
+                    name,
+                    value,
+                    signature,
+                });
+            }
+            assert_eq!(properties, self::properties());
+            // strict error handling
+            assert!(!buf.read_boolean().unwrap());
+            assert!(buf.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_known_packs() {
+            let (client, server) = tokio::io::duplex(1024);
+            let mut connection = connection(server);
+            connection.send_known_packs().await.unwrap();
+
+            let packets = sent(connection, client).await;
+            assert_eq!(packets.len(), 1);
+            assert_eq!(packets[0].0, 0x0E);
+            let mut buf = PacketBuf::new(&packets[0].1);
+            assert_eq!(buf.read_varint().unwrap(), 1);
+            assert_eq!(buf.read_string().unwrap(), "minecraft");
+            assert_eq!(buf.read_string().unwrap(), "core");
+            assert_eq!(buf.read_string().unwrap(), "1.21.1");
+            assert!(buf.is_empty());
+        }
     }
 }
@@ -0,0 +1,407 @@
+//! Just enough of a DNS client to look up the SRV records Minecraft servers
+//! advertise their port with
+
+use crate::error::{MinecraftError, Result};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, BytesMut};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// How long to wait for the name server before giving up on the record
+pub const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DNS_PORT: u16 = 53;
+const SRV_TYPE: u16 = 33;
+const IN_CLASS: u16 = 1;
+const RECURSION_DESIRED: u16 = 0x0100;
+// labels starting with these two bits set point to an earlier name instead
+const COMPRESSION_POINTER: u8 = 0xC0;
+
+/// Where an SRV record says a service runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct SrvTarget {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Looks up the `_minecraft._tcp` SRV record of a host, so servers can run
+/// on another host or port than the address players type
+#[async_trait]
+pub trait SrvResolver: Send + Sync {
+    /// `None` when the host has no record
+    async fn resolve_minecraft(&self, host: &str) -> Result<Option<SrvTarget>>;
+}
+
+/// Asks the first name server in /etc/resolv.conf
+#[derive(Debug, Clone, Default)]
+pub struct SystemResolver;
+
+#[async_trait]
+impl SrvResolver for SystemResolver {
+    async fn resolve_minecraft(&self, host: &str) -> Result<Option<SrvTarget>> {
+        let resolv_conf = tokio::fs::read_to_string("/etc/resolv.conf").await?;
+        let name_server = resolv_conf
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("nameserver"))
+            .find_map(|address| address.trim().parse::<IpAddr>().ok())
+            .ok_or_else(|| MinecraftError::Dns("No name server configured".to_string()))?;
+
+        lookup_srv(
+            SocketAddr::new(name_server, DNS_PORT),
+            &format!("_minecraft._tcp.{host}"),
+        )
+        .await
+    }
+}
+
+/// Queries `name_server` for the SRV records of `name` and picks the one to
+/// connect to, the lowest priority and then the highest weight
+pub async fn lookup_srv(name_server: SocketAddr, name: &str) -> Result<Option<SrvTarget>> {
+    let bind_address: SocketAddr = match name_server {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_address).await?;
+    socket.connect(name_server).await?;
+
+    let id: u16 = rand::random();
+    socket.send(&write_srv_query(id, name)?).await?;
+
+    let mut response = vec![0; 512];
+    let n = tokio::time::timeout(LOOKUP_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| MinecraftError::Dns(format!("No answer for {name}")))??;
+    response.truncate(n);
+
+    let records = read_srv_response(id, &response)?;
+    Ok(records
+        .into_iter()
+        .min_by_key(|(priority, weight, _)| (*priority, u16::MAX - weight))
+        .map(|(_, _, target)| target))
+}
+
+// ID                    u16
+// Flags                 u16
+// Question Count        u16
+// Answer Count          u16
+// Authority Count       u16
+// Additional Count      u16
+// Question              name, type u16, class u16
+fn write_srv_query(id: u16, name: &str) -> Result<BytesMut> {
+    let mut query = BytesMut::new();
+    query.put_u16(id);
+    query.put_u16(RECURSION_DESIRED);
+    query.put_u16(1);
+    query.put_u16(0);
+    query.put_u16(0);
+    query.put_u16(0);
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(MinecraftError::Dns(format!("Invalid name: {name}")));
+        }
+        query.put_u8(label.len() as u8);
+        query.put_slice(label.as_bytes());
+    }
+    query.put_u8(0);
+    query.put_u16(SRV_TYPE);
+    query.put_u16(IN_CLASS);
+
+    Ok(query)
+}
+
+/// The (priority, weight, target) of every SRV record answering query `id`
+fn read_srv_response(id: u16, message: &[u8]) -> Result<Vec<(u16, u16, SrvTarget)>> {
+    let short = || MinecraftError::Dns("Truncated DNS response".to_string());
+    let mut buf = message;
+    if buf.remaining() < 12 {
+        return Err(short());
+    }
+    if buf.get_u16() != id {
+        return Err(MinecraftError::Dns("Response to another query".to_string()));
+    }
+    let flags = buf.get_u16();
+    let questions = buf.get_u16();
+    let answers = buf.get_u16();
+    buf.advance(4);
+
+    // NXDOMAIN just means there's no record
+    match flags & 0x000F {
+        0 => {}
+        3 => return Ok(Vec::new()),
+        rcode => return Err(MinecraftError::Dns(format!("Lookup failed, rcode {rcode}"))),
+    }
+
+    for _ in 0..questions {
+        read_name(message, &mut buf)?;
+        if buf.remaining() < 4 {
+            return Err(short());
+        }
+        buf.advance(4);
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        read_name(message, &mut buf)?;
+        if buf.remaining() < 10 {
+            return Err(short());
+        }
+        let record_type = buf.get_u16();
+        buf.advance(6); // class and TTL
+        let length = buf.get_u16() as usize;
+        if buf.remaining() < length {
+            return Err(short());
+        }
+        // what's left of the message after this record
+        let after_record = buf.remaining() - length;
+
+        // CNAMEs can come along with the records
+        if record_type != SRV_TYPE {
+            buf.advance(length);
+            continue;
+        }
+        if length < 6 {
+            return Err(short());
+        }
+        let priority = buf.get_u16();
+        let weight = buf.get_u16();
+        let port = buf.get_u16();
+        // read in place rather than from a copy of the record data, the
+        // pointer check needs the name's real position in the message
+        let host = read_name(message, &mut buf)?;
+        if buf.remaining() < after_record {
+            return Err(MinecraftError::Dns(
+                "SRV target runs past its record".to_string(),
+            ));
+        }
+        buf.advance(buf.remaining() - after_record);
+        records.push((priority, weight, SrvTarget { host, port }));
+    }
+
+    Ok(records)
+}
+
+/// Reads a possibly compressed name at `buf`, which has to be a suffix of
+/// `message` since pointers are offsets into the whole message
+fn read_name<'a>(message: &'a [u8], buf: &mut &'a [u8]) -> Result<String> {
+    let short = || MinecraftError::Dns("Truncated name".to_string());
+    let mut labels = Vec::new();
+    let mut cursor = *buf;
+    let mut jumped = false;
+
+    loop {
+        let length = *cursor.first().ok_or_else(short)?;
+        if length & COMPRESSION_POINTER == COMPRESSION_POINTER {
+            if cursor.len() < 2 {
+                return Err(short());
+            }
+            let offset = (u16::from_be_bytes([length, cursor[1]]) & 0x3FFF) as usize;
+            if !jumped {
+                *buf = &cursor[2..];
+                jumped = true;
+            }
+            // pointers may only go backwards, so a malicious message can't loop
+            let position = message.len() - cursor.len();
+            if offset >= position {
+                return Err(MinecraftError::Dns("Invalid name pointer".to_string()));
+            }
+            cursor = &message[offset..];
+            continue;
+        }
+
+        cursor = &cursor[1..];
+        if length == 0 {
+            break;
+        }
+        let length = length as usize;
+        if cursor.len() < length {
+            return Err(short());
+        }
+        labels.push(String::from_utf8_lossy(&cursor[..length]).into_owned());
+        cursor = &cursor[length..];
+    }
+
+    if !jumped {
+        *buf = cursor;
+    }
+    Ok(labels.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_name(buf: &mut BytesMut, name: &str) {
+        for label in name.split('.') {
+            buf.put_u8(label.len() as u8);
+            buf.put_slice(label.as_bytes());
+        }
+        buf.put_u8(0);
+    }
+
+    #[test]
+    fn test_srv_query() {
+        let query = write_srv_query(0x1234, "_minecraft._tcp.example.com").unwrap();
+
+        let mut expected = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00".to_vec();
+        expected.extend_from_slice(b"\x0A_minecraft\x04_tcp\x07example\x03com\x00");
+        expected.extend_from_slice(b"\x00\x21\x00\x01");
+        assert_eq!(&query[..], &expected[..]);
+
+        assert!(write_srv_query(1, "a..b").is_err());
+    }
+
+    #[test]
+    fn test_srv_response() {
+        let mut response = BytesMut::new();
+        response.put_slice(b"\x12\x34\x81\x80\x00\x01\x00\x02\x00\x00\x00\x00");
+        let question = response.len();
+        write_name(&mut response, "_minecraft._tcp.example.com");
+        response.put_slice(b"\x00\x21\x00\x01");
+
+        for (priority, target) in [(10, "backup"), (5, "mc")] {
+            // the record name points back at the question
+            response.put_u16(0xC000 | question as u16);
+            response.put_u16(SRV_TYPE);
+            response.put_u16(IN_CLASS);
+            response.put_u32(300);
+            let mut data = BytesMut::new();
+            data.put_u16(priority);
+            data.put_u16(0);
+            data.put_u16(25566);
+            // "<target>." followed by a pointer to "example.com" in the question
+            data.put_u8(target.len() as u8);
+            data.put_slice(target.as_bytes());
+            data.put_u16(0xC000 | (question + 16) as u16);
+            response.put_u16(data.len() as u16);
+            response.put_slice(&data);
+        }
+
+        let records = read_srv_response(0x1234, &response).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                (
+                    10,
+                    0,
+                    SrvTarget {
+                        host: "backup.example.com".to_string(),
+                        port: 25566,
+                    }
+                ),
+                (
+                    5,
+                    0,
+                    SrvTarget {
+                        host: "mc.example.com".to_string(),
+                        port: 25566,
+                    }
+                ),
+            ]
+        );
+
+        assert!(read_srv_response(0x4321, &response).is_err());
+    }
+
+    #[test]
+    fn test_missing_record_is_no_answer() {
+        // NXDOMAIN, without any answers
+        let response = b"\x00\x01\x81\x83\x00\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(read_srv_response(1, response).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_name_pointer_loop_is_rejected() {
+        let message = b"\xC0\x00";
+        assert!(read_name(message, &mut &message[..]).is_err());
+
+        // "a" then a pointer forward to a pointer back to the start
+        let message = b"\x01a\xC0\x04\xC0\x00";
+        assert!(read_name(message, &mut &message[..]).is_err());
+    }
+
+    /// A response with one SRV record claiming `length` bytes of data, ending in `target`
+    fn srv_response(target: &[u8], length: u16) -> BytesMut {
+        let mut response = BytesMut::new();
+        response.put_slice(b"\x00\x01\x81\x80\x00\x00\x00\x01\x00\x00\x00\x00");
+        write_name(&mut response, "mc");
+        response.put_u16(SRV_TYPE);
+        response.put_u16(IN_CLASS);
+        response.put_u32(300);
+        response.put_u16(length);
+        response.put_slice(b"\x00\x00\x00\x00\x63\xDD");
+        response.put_slice(target);
+        response
+    }
+
+    #[test]
+    fn test_srv_target_pointer_checks_its_position() {
+        // the target points forward at a name after the record
+        // header, record name, record fields, SRV fields and the pointer
+        let after_record: u16 = 12 + 4 + 10 + 6 + 2;
+        let mut response = srv_response(&(0xC000 | after_record).to_be_bytes(), 8);
+        assert_eq!(response.len(), after_record as usize);
+        write_name(&mut response, "ahead.example.com");
+        assert!(read_srv_response(1, &response).is_err());
+
+        // the target's labels run past the record length
+        let response = srv_response(b"\x02mc\x00", 8);
+        assert!(read_srv_response(1, &response).is_err());
+
+        let response = srv_response(b"\x02mc\x00", 10);
+        assert_eq!(
+            read_srv_response(1, &response).unwrap(),
+            vec![(
+                0,
+                0,
+                SrvTarget {
+                    host: "mc".to_string(),
+                    port: 25565,
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lookup_srv_picks_lowest_priority() {
+        let name_server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = name_server.local_addr().unwrap();
+        let answering = tokio::spawn(async move {
+            let mut query = vec![0; 512];
+            let (n, client) = name_server.recv_from(&mut query).await.unwrap();
+            query.truncate(n);
+
+            let mut response = BytesMut::from(&query[..]);
+            response[2] = 0x81;
+            response[3] = 0x80;
+            for (priority, weight, target) in [(1, 5, "low"), (1, 10, "high"), (0, 1, "first")] {
+                response.put_u16(0xC00C);
+                response.put_u16(SRV_TYPE);
+                response.put_u16(IN_CLASS);
+                response.put_u32(300);
+                let mut data = BytesMut::new();
+                data.put_u16(priority);
+                data.put_u16(weight);
+                data.put_u16(25565);
+                write_name(&mut data, target);
+                response.put_u16(data.len() as u16);
+                response.put_slice(&data);
+            }
+            response[6..8].copy_from_slice(&3u16.to_be_bytes());
+            name_server.send_to(&response, client).await.unwrap();
+        });
+
+        let target = lookup_srv(address, "_minecraft._tcp.example.com")
+            .await
+            .unwrap();
+        answering.await.unwrap();
+        assert_eq!(
+            target,
+            Some(SrvTarget {
+                host: "first".to_string(),
+                port: 25565,
+            })
+        );
+    }
+}
@@ -19,6 +19,23 @@ pub enum MinecraftError {
     #[error("Protocol error: {0}")]
     Protocol(String),
 
+    #[error("NBT error: {0}")]
+    Nbt(String),
+
+    /// An item carried a data component the server has no codec for, so the
+    /// rest of the packet can't be read
+    #[error("Unsupported item component: {0}")]
+    UnsupportedItemComponent(i32),
+
+    #[error("Registry error: {0}")]
+    Registry(String),
+
+    #[error("Invalid config: {0}")]
+    Config(String),
+
+    #[error("DNS error: {0}")]
+    Dns(String),
+
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 }
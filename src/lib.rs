@@ -0,0 +1,21 @@
+pub mod auth;
+pub mod command;
+pub mod config;
+pub mod connection;
+pub mod dns;
+pub mod error;
+pub mod metrics;
+pub mod nbt;
+pub mod packet;
+pub mod player;
+pub mod proxy_protocol;
+pub mod registry;
+pub mod scheduler;
+pub mod server;
+pub mod status;
+pub mod tag;
+pub mod text;
+pub mod velocity;
+pub mod world;
+
+pub use error::{MinecraftError, Result};
@@ -1,16 +1,10 @@
-use crate::error::Result;
-use server::Server;
+use octavia::config::ServerConfig;
+use octavia::server::Server;
+use octavia::Result;
 use tracing::info;
 use tracing_subscriber::fmt;
 use tracing_subscriber::prelude::*;
 
-mod connection;
-mod error;
-mod packet;
-mod registry;
-mod server;
-mod tag;
-
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     tracing_subscriber::registry()
@@ -24,7 +18,7 @@ async fn main() -> Result<()> {
         .init();
 
     info!("Starting Minecraft server");
-    let mut server = Server::new().await?;
+    let mut server = Server::new(ServerConfig::default()).await?;
 
     server.run().await
 }
@@ -0,0 +1,353 @@
+//! Numbers operators can watch to tell whether the server is healthy
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Ping of the players online, from their smoothed keep-alive round trips
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PingSummary {
+    /// Players with a measured ping, those who just joined don't have one yet
+    pub players: usize,
+    pub average_ms: f64,
+    /// 99th percentile, the ping that 99% of players are at or below
+    pub p99_ms: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    connections: AtomicU64,
+    players: AtomicU64,
+    packets_received: AtomicU64,
+    packets_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    // latest latency of every player, by entity id
+    latencies: Mutex<HashMap<i32, i32>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connections open right now, counting those that never log in
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    pub fn connection_opened(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Players in the Play state right now
+    pub fn players(&self) -> u64 {
+        self.players.load(Ordering::Relaxed)
+    }
+
+    pub fn player_joined(&self) {
+        self.players.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Forgets a player that left
+    pub fn player_left(&self, entity_id: i32) {
+        self.players.fetch_sub(1, Ordering::Relaxed);
+        self.latencies.lock().unwrap().remove(&entity_id);
+    }
+
+    /// Packets received since the server started
+    pub fn packets_received(&self) -> u64 {
+        self.packets_received.load(Ordering::Relaxed)
+    }
+
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent.load(Ordering::Relaxed)
+    }
+
+    /// Bytes read from sockets since the server started, as they came over the wire
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_packet_received(&self) {
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts `packets` packets written to a socket in `bytes` bytes
+    pub fn record_sent(&self, packets: usize, bytes: usize) {
+        self.packets_sent
+            .fetch_add(packets as u64, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Replaces the latency of a player, called on every Keep Alive response
+    pub fn record_latency(&self, entity_id: i32, latency_ms: i32) {
+        self.latencies.lock().unwrap().insert(entity_id, latency_ms);
+    }
+
+    pub fn ping(&self) -> PingSummary {
+        let mut latencies: Vec<i32> = self.latencies.lock().unwrap().values().copied().collect();
+        if latencies.is_empty() {
+            return PingSummary::default();
+        }
+        latencies.sort_unstable();
+
+        let total: i64 = latencies.iter().map(|&latency| latency as i64).sum();
+        // nearest rank, so the result is always one of the samples
+        let rank = (latencies.len() * 99).div_ceil(100);
+        PingSummary {
+            players: latencies.len(),
+            average_ms: total as f64 / latencies.len() as f64,
+            p99_ms: latencies[rank - 1],
+        }
+    }
+
+    /// Every metric in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let ping = self.ping();
+        let metrics: [(&str, &str, &str, String); 8] = [
+            (
+                "octavia_connections",
+                "gauge",
+                "Open connections",
+                self.connections().to_string(),
+            ),
+            (
+                "octavia_players",
+                "gauge",
+                "Players online",
+                self.players().to_string(),
+            ),
+            (
+                "octavia_packets_received_total",
+                "counter",
+                "Packets received from clients",
+                self.packets_received().to_string(),
+            ),
+            (
+                "octavia_packets_sent_total",
+                "counter",
+                "Packets sent to clients",
+                self.packets_sent().to_string(),
+            ),
+            (
+                "octavia_bytes_received_total",
+                "counter",
+                "Bytes received from clients",
+                self.bytes_received().to_string(),
+            ),
+            (
+                "octavia_bytes_sent_total",
+                "counter",
+                "Bytes sent to clients",
+                self.bytes_sent().to_string(),
+            ),
+            (
+                "octavia_ping_average_milliseconds",
+                "gauge",
+                "Average ping of the players online",
+                ping.average_ms.to_string(),
+            ),
+            (
+                "octavia_ping_p99_milliseconds",
+                "gauge",
+                "99th percentile ping of the players online",
+                ping.p99_ms.to_string(),
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            // writing to a String can't fail
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} {kind}");
+            let _ = writeln!(text, "{name} {value}");
+        }
+        text
+    }
+}
+
+/// Serves the metrics of a server over HTTP, so Prometheus can scrape them
+#[cfg(feature = "metrics-http")]
+pub mod http {
+    use crate::world::SharedWorld;
+    use crate::Result;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tracing::debug;
+
+    /// Requests with a bigger head than this are answered with an error
+    const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+    /// Answers `GET /metrics` on every connection to `listener`, until the
+    /// returned future is dropped
+    pub async fn serve(listener: TcpListener, world: Arc<SharedWorld>) -> Result<()> {
+        loop {
+            let (socket, addr) = listener.accept().await?;
+            let world = world.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(socket, &world).await {
+                    debug!(%addr, error = %e, "Failed to serve metrics");
+                }
+            });
+        }
+    }
+
+    async fn respond(mut socket: TcpStream, world: &SharedWorld) -> Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let n = socket.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            request.extend_from_slice(&buf[..n]);
+            if request.len() > MAX_REQUEST_SIZE {
+                return write_response(&mut socket, "431 Request Header Fields Too Large", "")
+                    .await;
+            }
+        }
+
+        let request_line = request.split(|&byte| byte == b'\r').next().unwrap_or(&[]);
+        let mut parts = request_line.split(|&byte| byte == b' ');
+        match (parts.next(), parts.next()) {
+            (Some(b"GET"), Some(b"/metrics")) => {
+                let body = world.metrics().to_prometheus();
+                write_response(&mut socket, "200 OK", &body).await
+            }
+            _ => write_response(&mut socket, "404 Not Found", "").await,
+        }
+    }
+
+    async fn write_response(socket: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+        let response = format!(
+            "HTTP/1.1 {status}\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {body}",
+            body.len()
+        );
+        socket.write_all(response.as_bytes()).await?;
+        socket.shutdown().await?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        async fn get(world: Arc<SharedWorld>, path: &str) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let server = tokio::spawn(serve(listener, world));
+
+            let mut client = TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            client.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+
+            server.abort();
+            response
+        }
+
+        #[tokio::test]
+        async fn test_metrics_endpoint() {
+            let world = Arc::new(SharedWorld::new());
+            world.metrics().connection_opened();
+            world.metrics().connection_opened();
+
+            let response = get(world, "/metrics").await;
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+            assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+            assert!(
+                body.starts_with(
+                    "# HELP octavia_connections Open connections\n\
+                     # TYPE octavia_connections gauge\n\
+                     octavia_connections 2\n"
+                ),
+                "{body}"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_other_paths_are_not_found() {
+            let response = get(Arc::new(SharedWorld::new()), "/").await;
+            assert!(
+                response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+                "{response}"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping_summary() {
+        let metrics = Metrics::new();
+        assert_eq!(metrics.ping(), PingSummary::default());
+
+        metrics.record_latency(1, 20);
+        metrics.record_latency(2, 40);
+        metrics.record_latency(3, 90);
+        // only the latest latency of a player counts
+        metrics.record_latency(1, 30);
+
+        let ping = metrics.ping();
+        assert_eq!(ping.players, 3);
+        assert_eq!(ping.average_ms, 160.0 / 3.0);
+        assert_eq!(ping.p99_ms, 90);
+
+        metrics.player_joined();
+        metrics.player_left(3);
+        assert_eq!(metrics.ping().average_ms, 35.0);
+    }
+
+    #[test]
+    fn test_p99_ignores_the_slowest_percent() {
+        let metrics = Metrics::new();
+        for entity_id in 0..200 {
+            metrics.record_latency(entity_id, entity_id);
+        }
+
+        // 198 of the 200 players are at or below it
+        assert_eq!(metrics.ping().p99_ms, 197);
+    }
+
+    #[test]
+    fn test_prometheus_exposition() {
+        let metrics = Metrics::new();
+        metrics.record_sent(2, 30);
+        metrics.record_sent(1, 12);
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains(
+            "# HELP octavia_packets_sent_total Packets sent to clients\n\
+             # TYPE octavia_packets_sent_total counter\n\
+             octavia_packets_sent_total 3\n"
+        ));
+        assert!(text.contains("\noctavia_bytes_sent_total 42\n"));
+        assert!(text.ends_with("octavia_ping_p99_milliseconds 0\n"));
+    }
+}
@@ -0,0 +1,315 @@
+#![allow(unused)]
+
+use crate::error::{MinecraftError, Result};
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+use std::collections::BTreeMap;
+
+// Tag ids
+pub const TAG_END: u8 = 0;
+pub const TAG_BYTE: u8 = 1;
+pub const TAG_SHORT: u8 = 2;
+pub const TAG_INT: u8 = 3;
+pub const TAG_LONG: u8 = 4;
+pub const TAG_FLOAT: u8 = 5;
+pub const TAG_DOUBLE: u8 = 6;
+pub const TAG_BYTE_ARRAY: u8 = 7;
+pub const TAG_STRING: u8 = 8;
+pub const TAG_LIST: u8 = 9;
+pub const TAG_COMPOUND: u8 = 10;
+pub const TAG_INT_ARRAY: u8 = 11;
+pub const TAG_LONG_ARRAY: u8 = 12;
+
+// compounds nested deeper than this are rejected, like vanilla does
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Nbt>),
+    Compound(BTreeMap<String, Nbt>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Nbt {
+    pub fn tag_id(&self) -> u8 {
+        match self {
+            Nbt::Byte(_) => TAG_BYTE,
+            Nbt::Short(_) => TAG_SHORT,
+            Nbt::Int(_) => TAG_INT,
+            Nbt::Long(_) => TAG_LONG,
+            Nbt::Float(_) => TAG_FLOAT,
+            Nbt::Double(_) => TAG_DOUBLE,
+            Nbt::ByteArray(_) => TAG_BYTE_ARRAY,
+            Nbt::String(_) => TAG_STRING,
+            Nbt::List(_) => TAG_LIST,
+            Nbt::Compound(_) => TAG_COMPOUND,
+            Nbt::IntArray(_) => TAG_INT_ARRAY,
+            Nbt::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Looks up a child of a compound
+    pub fn get(&self, name: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(children) => children.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_i8(&self) -> Option<i8> {
+        match self {
+            Nbt::Byte(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Nbt::Byte(value) => Some(*value as i32),
+            Nbt::Short(value) => Some(*value as i32),
+            Nbt::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Nbt::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Nbt]> {
+        match self {
+            Nbt::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Nbt::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Reads a named root tag, as stored in files
+    pub fn read_named(buf: &mut &[u8]) -> Result<(String, Nbt)> {
+        let tag_id = PacketReader::read_unsigned_byte(buf)?;
+        if tag_id == TAG_END {
+            return Err(MinecraftError::Nbt("Root tag cannot be TAG_End".into()));
+        }
+
+        let name = Self::read_string(buf)?;
+        let value = Self::read_payload(buf, tag_id, 0)?;
+
+        Ok((name, value))
+    }
+
+    /// Reads a nameless root tag, as sent over the network since 1.20.2
+    pub fn read_nameless(buf: &mut &[u8]) -> Result<Nbt> {
+        let tag_id = PacketReader::read_unsigned_byte(buf)?;
+        if tag_id == TAG_END {
+            return Err(MinecraftError::Nbt("Root tag cannot be TAG_End".into()));
+        }
+
+        Self::read_payload(buf, tag_id, 0)
+    }
+
+    pub fn write_named(&self, buf: &mut BytesMut, name: &str) {
+        buf.put_u8(self.tag_id());
+        Self::write_string(buf, name);
+        self.write_payload(buf);
+    }
+
+    pub fn write_nameless(&self, buf: &mut BytesMut) {
+        buf.put_u8(self.tag_id());
+        self.write_payload(buf);
+    }
+
+    fn read_payload(buf: &mut &[u8], tag_id: u8, depth: usize) -> Result<Nbt> {
+        if depth > MAX_DEPTH {
+            return Err(MinecraftError::Nbt("NBT nested too deeply".into()));
+        }
+
+        let value = match tag_id {
+            TAG_BYTE => Nbt::Byte(PacketReader::read_byte(buf)?),
+            TAG_SHORT => Nbt::Short(PacketReader::read_short(buf)?),
+            TAG_INT => Nbt::Int(PacketReader::read_int(buf)?),
+            TAG_LONG => Nbt::Long(PacketReader::read_long(buf)?),
+            TAG_FLOAT => Nbt::Float(PacketReader::read_float(buf)?),
+            TAG_DOUBLE => Nbt::Double(PacketReader::read_double(buf)?),
+            TAG_BYTE_ARRAY => {
+                let length = Self::read_length(buf, 1)?;
+                let values = buf[..length].iter().map(|&b| b as i8).collect();
+                *buf = &buf[length..];
+                Nbt::ByteArray(values)
+            }
+            TAG_STRING => Nbt::String(Self::read_string(buf)?),
+            TAG_LIST => {
+                let element_id = PacketReader::read_unsigned_byte(buf)?;
+                let length = PacketReader::read_int(buf)?.max(0) as usize;
+
+                let mut values = Vec::with_capacity(length.min(buf.len()));
+                if element_id != TAG_END {
+                    for _ in 0..length {
+                        values.push(Self::read_payload(buf, element_id, depth + 1)?);
+                    }
+                }
+                Nbt::List(values)
+            }
+            TAG_COMPOUND => {
+                let mut children = BTreeMap::new();
+                loop {
+                    let child_id = PacketReader::read_unsigned_byte(buf)?;
+                    if child_id == TAG_END {
+                        break;
+                    }
+
+                    let name = Self::read_string(buf)?;
+                    let value = Self::read_payload(buf, child_id, depth + 1)?;
+                    children.insert(name, value);
+                }
+                Nbt::Compound(children)
+            }
+            TAG_INT_ARRAY => {
+                let length = Self::read_length(buf, 4)?;
+                let mut values = Vec::with_capacity(length);
+                for _ in 0..length {
+                    values.push(PacketReader::read_int(buf)?);
+                }
+                Nbt::IntArray(values)
+            }
+            TAG_LONG_ARRAY => {
+                let length = Self::read_length(buf, 8)?;
+                let mut values = Vec::with_capacity(length);
+                for _ in 0..length {
+                    values.push(PacketReader::read_long(buf)?);
+                }
+                Nbt::LongArray(values)
+            }
+            _ => return Err(MinecraftError::Nbt(format!("Unknown tag id: {tag_id}"))),
+        };
+
+        Ok(value)
+    }
+
+    /// Reads an array length and checks the buffer holds that many elements
+    fn read_length(buf: &mut &[u8], element_size: usize) -> Result<usize> {
+        let length = PacketReader::read_int(buf)?;
+        if length < 0 {
+            return Err(MinecraftError::Nbt(
+                "Array length cannot be negative".into(),
+            ));
+        }
+
+        if buf.len() < length as usize * element_size {
+            return Err(MinecraftError::BufferUnderrun(
+                "Array length exceeds buffer size".into(),
+            ));
+        }
+
+        Ok(length as usize)
+    }
+
+    // NBT strings use an unsigned short length instead of a varint
+    fn read_string(buf: &mut &[u8]) -> Result<String> {
+        let length = PacketReader::read_unsigned_short(buf)? as usize;
+        if buf.len() < length {
+            return Err(MinecraftError::BufferUnderrun(
+                "String length exceeds buffer size".into(),
+            ));
+        }
+
+        // java's modified UTF-8 only differs for nul and supplementary characters
+        let string = String::from_utf8_lossy(&buf[..length]).into_owned();
+        *buf = &buf[length..];
+
+        Ok(string)
+    }
+
+    fn write_string(buf: &mut BytesMut, value: &str) {
+        buf.put_u16(value.len() as u16);
+        buf.put(value.as_bytes());
+    }
+
+    fn write_payload(&self, buf: &mut BytesMut) {
+        match self {
+            Nbt::Byte(value) => buf.put_i8(*value),
+            Nbt::Short(value) => buf.put_i16(*value),
+            Nbt::Int(value) => buf.put_i32(*value),
+            Nbt::Long(value) => buf.put_i64(*value),
+            Nbt::Float(value) => buf.put_f32(*value),
+            Nbt::Double(value) => buf.put_f64(*value),
+            Nbt::ByteArray(values) => {
+                buf.put_i32(values.len() as i32);
+                for &value in values {
+                    buf.put_i8(value);
+                }
+            }
+            Nbt::String(value) => Self::write_string(buf, value),
+            Nbt::List(values) => {
+                buf.put_u8(values.first().map_or(TAG_END, Nbt::tag_id));
+                buf.put_i32(values.len() as i32);
+                for value in values {
+                    value.write_payload(buf);
+                }
+            }
+            Nbt::Compound(children) => {
+                for (name, value) in children {
+                    buf.put_u8(value.tag_id());
+                    Self::write_string(buf, name);
+                    value.write_payload(buf);
+                }
+                buf.put_u8(TAG_END);
+            }
+            Nbt::IntArray(values) => {
+                buf.put_i32(values.len() as i32);
+                for &value in values {
+                    buf.put_i32(value);
+                }
+            }
+            Nbt::LongArray(values) => {
+                buf.put_i32(values.len() as i32);
+                for &value in values {
+                    buf.put_i64(value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_round_trip() {
+        let nbt = Nbt::Compound(BTreeMap::from([
+            ("name".to_string(), Nbt::String("Bananrama".into())),
+            (
+                "scores".to_string(),
+                Nbt::List(vec![Nbt::Int(1), Nbt::Int(2)]),
+            ),
+            ("data".to_string(), Nbt::LongArray(vec![-1, 42])),
+        ]));
+
+        let mut buf = BytesMut::new();
+        nbt.write_named(&mut buf, "hello world");
+
+        let mut data = &buf[..];
+        let (name, read) = Nbt::read_named(&mut data).unwrap();
+        assert!(data.is_empty());
+        assert_eq!(name, "hello world");
+        assert_eq!(read, nbt);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::buf::PacketBuf;
+
+// Seen Advancements actions
+pub const OPENED_TAB: i32 = 0;
+pub const CLOSED_SCREEN: i32 = 1;
+
+/// The client opening a tab of the advancements screen or closing it
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeenAdvancements {
+    OpenedTab { tab: (String, String) },
+    ClosedScreen,
+}
+
+impl SeenAdvancements {
+    // Action                varint
+    // Tab ID                identifier (only when a tab was opened)
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        match buf.read_varint()? {
+            OPENED_TAB => Ok(SeenAdvancements::OpenedTab {
+                tab: buf.read_identifier()?,
+            }),
+            CLOSED_SCREEN => Ok(SeenAdvancements::ClosedScreen),
+            action => Err(MinecraftError::Protocol(format!(
+                "Invalid seen advancements action: {action}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_opened_tab() {
+        let mut packet = vec![0x00, 0x0F];
+        packet.extend_from_slice(b"minecraft:story");
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            SeenAdvancements::decode(&mut buf).unwrap(),
+            SeenAdvancements::OpenedTab {
+                tab: ("minecraft".to_string(), "story".to_string())
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_closed_screen() {
+        // no tab follows
+        let packet = [0x01];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            SeenAdvancements::decode(&mut buf).unwrap(),
+            SeenAdvancements::ClosedScreen
+        );
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::Result;
+use crate::nbt::Nbt;
+use crate::packet::reader::PacketReader;
+use crate::world::Position;
+use uuid::Uuid;
+
+/// Read cursor over the bytes of exactly one packet
+///
+/// Reads past the end of the packet fail instead of running into whatever
+/// follows it in the connection's buffer.
+pub struct PacketBuf<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PacketBuf<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Number of bytes that haven't been read yet
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Discards the rest of the packet
+    pub fn skip_remaining(&mut self) {
+        self.data = &[];
+    }
+
+    /// Returns and consumes the rest of the packet
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        PacketReader::read_remaining(&mut self.data)
+    }
+
+    pub fn read_varint(&mut self) -> Result<i32> {
+        PacketReader::read_varint(&mut self.data)
+    }
+
+    pub fn read_string(&mut self) -> Result<String> {
+        PacketReader::read_string(&mut self.data)
+    }
+
+    pub fn read_string_capped(&mut self, max_code_units: usize) -> Result<String> {
+        PacketReader::read_string_capped(&mut self.data, max_code_units)
+    }
+
+    pub fn read_byte(&mut self) -> Result<i8> {
+        PacketReader::read_byte(&mut self.data)
+    }
+
+    pub fn read_unsigned_byte(&mut self) -> Result<u8> {
+        PacketReader::read_unsigned_byte(&mut self.data)
+    }
+
+    pub fn read_boolean(&mut self) -> Result<bool> {
+        PacketReader::read_boolean(&mut self.data)
+    }
+
+    pub fn read_unsigned_short(&mut self) -> Result<u16> {
+        PacketReader::read_unsigned_short(&mut self.data)
+    }
+
+    pub fn read_short(&mut self) -> Result<i16> {
+        PacketReader::read_short(&mut self.data)
+    }
+
+    pub fn read_int(&mut self) -> Result<i32> {
+        PacketReader::read_int(&mut self.data)
+    }
+
+    pub fn read_long(&mut self) -> Result<i64> {
+        PacketReader::read_long(&mut self.data)
+    }
+
+    pub fn read_float(&mut self) -> Result<f32> {
+        PacketReader::read_float(&mut self.data)
+    }
+
+    pub fn read_double(&mut self) -> Result<f64> {
+        PacketReader::read_double(&mut self.data)
+    }
+
+    pub fn read_fixed_point_i32(&mut self, fraction_bits: u32) -> Result<f64> {
+        PacketReader::read_fixed_point_i32(&mut self.data, fraction_bits)
+    }
+
+    pub fn read_uuid(&mut self) -> Result<Uuid> {
+        PacketReader::read_uuid(&mut self.data)
+    }
+
+    pub fn read_angle(&mut self) -> Result<f32> {
+        PacketReader::read_angle(&mut self.data)
+    }
+
+    pub fn read_position(&mut self) -> Result<Position> {
+        PacketReader::read_position(&mut self.data)
+    }
+
+    pub fn read_plugin_message(&mut self) -> Result<(String, Vec<u8>)> {
+        PacketReader::read_plugin_message(&mut self.data)
+    }
+
+    pub fn read_byte_array(&mut self) -> Result<Vec<u8>> {
+        PacketReader::read_byte_array(&mut self.data)
+    }
+
+    pub fn read_identifier(&mut self) -> Result<(String, String)> {
+        PacketReader::read_identifier(&mut self.data)
+    }
+
+    /// Reads a nameless NBT tag
+    pub fn read_nbt(&mut self) -> Result<Nbt> {
+        Nbt::read_nameless(&mut self.data)
+    }
+
+    pub fn read_identifier_array(&mut self) -> Result<Vec<(String, String)>> {
+        PacketReader::read_identifier_array(&mut self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_past_packet_boundary() {
+        // a boolean packet (0x1D) missing its boolean, followed by another packet
+        let buffer = [0x01, 0x1D, 0x02, 0x1D, 0x01];
+
+        let mut packet = PacketBuf::new(&buffer[1..2]);
+        assert_eq!(packet.read_varint().unwrap(), 0x1D);
+        assert!(packet.is_empty());
+        assert!(packet.read_boolean().is_err());
+        assert!(packet.read_varint().is_err());
+        assert_eq!(packet.remaining(), 0);
+    }
+}
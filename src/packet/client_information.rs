@@ -0,0 +1,101 @@
+use crate::error::Result;
+use crate::packet::buf::PacketBuf;
+
+/// First protocol version (1.21.2) whose Client Information ends with the particle status
+pub const PARTICLE_STATUS_PROTOCOL_VERSION: i32 = 768;
+
+/// Client settings, sent during Configuration and again whenever they change in Play
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientInformation {
+    pub locale: String,
+    pub view_distance: i8,
+    pub chat_mode: i32,
+    pub chat_colors: bool,
+    pub displayed_skin_parts: u8,
+    pub main_hand: i32,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+    // only sent by 1.21.2+ clients, older packets end after allow_server_listings
+    pub particle_status: Option<i32>,
+}
+
+impl ClientInformation {
+    // Locale                string (16)
+    // View Distance         byte
+    // Chat Mode             varint
+    // Chat Colors           boolean
+    // Displayed Skin Parts  unsigned byte
+    // Main Hand             varint
+    // Text Filtering        boolean
+    // Server Listings       boolean
+    // Particle Status       varint (protocol 768+)
+    /// Decodes the layout used by `protocol_version`, the version from the client's handshake
+    pub fn decode(buf: &mut PacketBuf, protocol_version: i32) -> Result<Self> {
+        Ok(Self {
+            locale: buf.read_string()?,
+            view_distance: buf.read_byte()?,
+            chat_mode: buf.read_varint()?,
+            chat_colors: buf.read_boolean()?,
+            displayed_skin_parts: buf.read_unsigned_byte()?,
+            main_hand: buf.read_varint()?,
+            enable_text_filtering: buf.read_boolean()?,
+            allow_server_listings: buf.read_boolean()?,
+            particle_status: if protocol_version >= PARTICLE_STATUS_PROTOCOL_VERSION {
+                Some(buf.read_varint()?)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::reader::PacketReader;
+    use bytes::{BufMut, BytesMut};
+
+    #[test]
+    fn test_decode_client_information() {
+        let mut packet = BytesMut::new();
+        PacketReader::write_string(&mut packet, "en_us");
+        packet.put_i8(12);
+        PacketReader::write_varint(&mut packet, 0);
+        packet.put_u8(1);
+        packet.put_u8(0x7F);
+        PacketReader::write_varint(&mut packet, 1);
+        packet.put_u8(0);
+        packet.put_u8(1);
+        PacketReader::write_varint(&mut packet, 2);
+
+        let mut buf = PacketBuf::new(&packet);
+        let information =
+            ClientInformation::decode(&mut buf, PARTICLE_STATUS_PROTOCOL_VERSION).unwrap();
+
+        assert_eq!(
+            information,
+            ClientInformation {
+                locale: "en_us".to_string(),
+                view_distance: 12,
+                chat_mode: 0,
+                chat_colors: true,
+                displayed_skin_parts: 0x7F,
+                main_hand: 1,
+                enable_text_filtering: false,
+                allow_server_listings: true,
+                particle_status: Some(2),
+            }
+        );
+        assert!(buf.is_empty());
+
+        // a 767 client leaves the particle status out
+        let mut buf = PacketBuf::new(&packet[..packet.len() - 1]);
+        let information = ClientInformation::decode(&mut buf, 767).unwrap();
+        assert_eq!(information.particle_status, None);
+        assert!(buf.is_empty());
+
+        // and a 768 client can't
+        let mut buf = PacketBuf::new(&packet[..packet.len() - 1]);
+        assert!(ClientInformation::decode(&mut buf, PARTICLE_STATUS_PROTOCOL_VERSION).is_err());
+    }
+}
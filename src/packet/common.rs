@@ -0,0 +1,142 @@
+//! Packets that exist in both Configuration and Play and only differ in their ids
+
+use crate::error::{MinecraftError, Result};
+use crate::nbt::Nbt;
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+
+pub const CONFIGURATION_PLUGIN_MESSAGE_PACKET_ID: i32 = 0x01;
+pub const CONFIGURATION_DISCONNECT_PACKET_ID: i32 = 0x02;
+pub const CONFIGURATION_STORE_COOKIE_PACKET_ID: i32 = 0x0A;
+pub const CONFIGURATION_TRANSFER_PACKET_ID: i32 = 0x0B;
+pub const PLAY_PLUGIN_MESSAGE_PACKET_ID: i32 = 0x19;
+pub const PLAY_DISCONNECT_PACKET_ID: i32 = 0x1D;
+pub const PLAY_STORE_COOKIE_PACKET_ID: i32 = 0x6B;
+pub const PLAY_TRANSFER_PACKET_ID: i32 = 0x73;
+
+/// Plugin channel the server brand is sent on, shown in the client's debug screen
+pub const BRAND_CHANNEL: &str = "minecraft:brand";
+
+/// Largest cookie payload the client accepts
+pub const MAX_COOKIE_SIZE: usize = 5120;
+
+// Packet ID             varint
+// Reason                text component (NBT)
+pub fn write_disconnect_packet(packet_id: i32, reason: &str) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, packet_id);
+    // a plain string tag is the simplest text component
+    Nbt::String(reason.to_string()).write_nameless(&mut packet);
+
+    packet
+}
+
+// Packet ID             varint
+// Channel               identifier
+// Data                  byte array (rest of the packet, no length prefix)
+pub fn write_plugin_message_packet(packet_id: i32, channel: &str, data: &[u8]) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, packet_id);
+    PacketReader::write_string(&mut packet, channel);
+    packet.put_slice(data);
+
+    packet
+}
+
+/// Data of a plugin message on `BRAND_CHANNEL`, the brand as a string
+pub fn brand_data(brand: &str) -> BytesMut {
+    let mut data = BytesMut::new();
+    PacketReader::write_string(&mut data, brand);
+    data
+}
+
+// Packet ID             varint
+// Key                   identifier
+// Payload Length        varint
+// Payload               byte array (max 5 KiB)
+pub fn write_store_cookie_packet(packet_id: i32, key: &str, payload: &[u8]) -> Result<BytesMut> {
+    if payload.len() > MAX_COOKIE_SIZE {
+        return Err(MinecraftError::Protocol(format!(
+            "Cookie payload is {} bytes, the limit is {MAX_COOKIE_SIZE}",
+            payload.len()
+        )));
+    }
+
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, packet_id);
+    PacketReader::write_string(&mut packet, key);
+    PacketReader::write_varint(&mut packet, payload.len() as i32);
+    packet.put_slice(payload);
+
+    Ok(packet)
+}
+
+// Packet ID             varint
+// Host                  string
+// Port                  varint
+pub fn write_transfer_packet(packet_id: i32, host: &str, port: u16) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, packet_id);
+    PacketReader::write_string(&mut packet, host);
+    PacketReader::write_varint(&mut packet, port as i32);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disconnect_packet() {
+        let packet = write_disconnect_packet(PLAY_DISCONNECT_PACKET_ID, "bye");
+
+        // 0x1D                          Packet ID             varint
+        // 0x08 0x00 0x03 "bye"          Reason                NBT string
+        assert_eq!(&packet[..], b"\x1D\x08\x00\x03bye");
+    }
+
+    #[test]
+    fn test_brand_packet() {
+        let packet = write_plugin_message_packet(
+            PLAY_PLUGIN_MESSAGE_PACKET_ID,
+            BRAND_CHANNEL,
+            &brand_data("octavia"),
+        );
+
+        // 0x19                          Packet ID             varint
+        // 0x0F "minecraft:brand"        Channel               identifier
+        // 0x07 "octavia"                Data                  string
+        assert_eq!(&packet[..], b"\x19\x0Fminecraft:brand\x07octavia");
+
+        let mut buf = &packet[..];
+        PacketReader::read_varint(&mut buf).unwrap();
+        let (channel, data) = PacketReader::read_plugin_message(&mut buf).unwrap();
+        assert_eq!(channel, BRAND_CHANNEL);
+        assert_eq!(
+            PacketReader::read_string(&mut &data[..]).unwrap(),
+            "octavia"
+        );
+    }
+
+    #[test]
+    fn test_store_cookie_packet() {
+        let packet =
+            write_store_cookie_packet(CONFIGURATION_STORE_COOKIE_PACKET_ID, "a:b", b"hi").unwrap();
+
+        // 0x0A                          Packet ID             varint
+        // 0x03 "a:b"                    Key                   identifier
+        // 0x02 "hi"                     Payload               byte array
+        assert_eq!(&packet[..], b"\x0A\x03a:b\x02hi");
+
+        let packet = write_store_cookie_packet(PLAY_STORE_COOKIE_PACKET_ID, "a:b", &[]).unwrap();
+        assert_eq!(&packet[..], b"\x6B\x03a:b\x00");
+
+        let oversized = vec![0; MAX_COOKIE_SIZE + 1];
+        assert!(write_store_cookie_packet(PLAY_STORE_COOKIE_PACKET_ID, "a:b", &oversized).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::buf::PacketBuf;
+use crate::packet::item::{read_item_stack, ItemStack};
+
+/// Most slots a single click can change, vanilla rejects anything above this
+pub const MAX_CHANGED_SLOTS: i32 = 128;
+
+/// A click in an open container window
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickContainer {
+    pub window_id: u8,
+    pub state_id: i32,
+    pub slot: i16,
+    pub button: i8,
+    pub mode: i32,
+    /// Slots the client thinks changed, with their new contents
+    pub changed_slots: Vec<(i16, Option<ItemStack>)>,
+    /// Item left on the cursor
+    pub carried_item: Option<ItemStack>,
+}
+
+impl ClickContainer {
+    // Window ID             unsigned byte
+    // State ID              varint
+    // Slot                  short
+    // Button                byte
+    // Mode                  varint
+    // Changed Slot Count    varint
+    //   Slot Number         short
+    //   Slot Data           slot
+    // Carried Item          slot
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        let window_id = buf.read_unsigned_byte()?;
+        let state_id = buf.read_varint()?;
+        let slot = buf.read_short()?;
+        let button = buf.read_byte()?;
+        let mode = buf.read_varint()?;
+
+        let changed_count = buf.read_varint()?;
+        if !(0..=MAX_CHANGED_SLOTS).contains(&changed_count) {
+            return Err(MinecraftError::Protocol(format!(
+                "Invalid changed slot count: {changed_count}"
+            )));
+        }
+
+        let mut changed_slots = Vec::with_capacity(changed_count as usize);
+        for _ in 0..changed_count {
+            let slot = buf.read_short()?;
+            changed_slots.push((slot, read_item_stack(buf)?));
+        }
+
+        Ok(Self {
+            window_id,
+            state_id,
+            slot,
+            button,
+            mode,
+            changed_slots,
+            carried_item: read_item_stack(buf)?,
+        })
+    }
+}
+
+/// A creative mode player taking an item out of the creative inventory or
+/// clearing a slot
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetCreativeModeSlot {
+    /// Slot in the player inventory window, -1 drops the item
+    pub slot: i16,
+    pub item: Option<ItemStack>,
+}
+
+impl SetCreativeModeSlot {
+    // Slot                  short
+    // Clicked Item          slot
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        Ok(Self {
+            slot: buf.read_short()?,
+            item: read_item_stack(buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_set_creative_mode_slot() {
+        let packet = [
+            0x00, 0x24, // slot 36, the first hotbar slot
+            0x40, 0x01, 0x00, 0x00, // 64 stone
+        ];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            SetCreativeModeSlot::decode(&mut buf).unwrap(),
+            SetCreativeModeSlot {
+                slot: 36,
+                item: Some(ItemStack::new(1, 64)),
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_click_container() {
+        let packet = [
+            0x01, // window id
+            0x07, // state id
+            0x00, 0x24, // slot 36
+            0x00, // button
+            0x00, // mode
+            0x02, // changed slots
+            0x00, 0x24, 0x00, // slot 36 is now empty
+            0x00, 0x25, 0x10, 0x01, 0x00, 0x00, // slot 37 holds 16 of item 1
+            0x01, 0x02, 0x00, 0x00, // one of item 2 on the cursor
+        ];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            ClickContainer::decode(&mut buf).unwrap(),
+            ClickContainer {
+                window_id: 1,
+                state_id: 7,
+                slot: 36,
+                button: 0,
+                mode: 0,
+                changed_slots: vec![(36, None), (37, Some(ItemStack::new(1, 16))),],
+                carried_item: Some(ItemStack::new(2, 1)),
+            }
+        );
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::reader::PacketReader;
+use bytes::BytesMut;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+// vanilla refuses to inflate packets larger than this
+pub const MAX_UNCOMPRESSED_SIZE: usize = 8 * 1024 * 1024;
+// vanilla writes packet lengths as at most 3 varint bytes
+pub const MAX_PACKET_SIZE: usize = (1 << 21) - 1;
+
+/// Frames a packet (id + body) for the wire
+///
+/// Without compression a frame is
+// Packet Length         varint
+// Packet ID + data
+///
+/// Once compression is enabled it becomes
+// Packet Length         varint (length of everything below)
+// Data Length           varint (uncompressed length, 0 when below the threshold)
+// Packet ID + data      zlib compressed when Data Length isn't 0
+pub fn frame_packet(
+    content: &[u8],
+    compression_threshold: Option<usize>,
+    compression_level: Compression,
+) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    let Some(threshold) = compression_threshold else {
+        PacketReader::write_varint(&mut packet, content.len() as i32);
+        packet.extend_from_slice(content);
+        return packet;
+    };
+
+    if content.len() < threshold {
+        PacketReader::write_varint(&mut packet, content.len() as i32 + 1);
+        PacketReader::write_varint(&mut packet, 0);
+        packet.extend_from_slice(content);
+        return packet;
+    }
+
+    let compressed = compress(content, compression_level);
+    let data_length = content.len() as i32;
+    let packet_length = PacketReader::get_varint_size(data_length) + compressed.len();
+
+    PacketReader::write_varint(&mut packet, packet_length as i32);
+    PacketReader::write_varint(&mut packet, data_length);
+    packet.extend_from_slice(&compressed);
+
+    packet
+}
+
+pub fn compress(data: &[u8], level: Compression) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    // writing into a Vec can't fail
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Inflates a compressed packet, checking it matches the length the client declared
+pub fn decompress(data: &[u8], data_length: usize) -> Result<Vec<u8>> {
+    if data_length > MAX_UNCOMPRESSED_SIZE {
+        return Err(MinecraftError::Protocol(format!(
+            "Compressed packet too large: {data_length} bytes"
+        )));
+    }
+
+    let mut decompressed = Vec::with_capacity(data_length);
+    ZlibDecoder::new(data)
+        .take(data_length as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+
+    if decompressed.len() != data_length {
+        return Err(MinecraftError::Protocol(format!(
+            "Decompressed {} bytes, expected {data_length}",
+            decompressed.len()
+        )));
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_without_compression() {
+        let packet = frame_packet(b"\x1D\x01", None, Compression::default());
+        assert_eq!(&packet[..], b"\x02\x1D\x01");
+    }
+
+    #[test]
+    fn test_frame_with_compression() {
+        // below the threshold the data is sent as is, behind a 0 data length
+        let packet = frame_packet(b"\x1D\x01", Some(256), Compression::default());
+        assert_eq!(&packet[..], b"\x03\x00\x1D\x01");
+
+        let content = vec![0x2A; 300];
+        let packet = frame_packet(&content, Some(256), Compression::default());
+
+        let mut buf = &packet[..];
+        let packet_length = PacketReader::read_varint(&mut buf).unwrap();
+        assert_eq!(packet_length as usize, buf.len());
+
+        let data_length = PacketReader::read_varint(&mut buf).unwrap();
+        assert_eq!(data_length, 300);
+        assert_eq!(decompress(buf, 300).unwrap(), content);
+    }
+
+    #[test]
+    fn test_compression_levels() {
+        let content = b"minecraft:overworld ".repeat(100);
+        let fast = compress(&content, Compression::new(1));
+        let best = compress(&content, Compression::new(9));
+
+        // level 1 skips the lazy matching that finds the longer runs
+        assert_ne!(fast.len(), best.len());
+        assert_eq!(decompress(&fast, content.len()).unwrap(), content);
+        assert_eq!(decompress(&best, content.len()).unwrap(), content);
+
+        // level 0 only wraps the data
+        let stored = compress(&content, Compression::new(0));
+        assert!(stored.len() > content.len());
+        assert_eq!(decompress(&stored, content.len()).unwrap(), content);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::buf::PacketBuf;
+
+// Interaction types
+pub const INTERACT: i32 = 0;
+pub const ATTACK: i32 = 1;
+pub const INTERACT_AT: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InteractAction {
+    Interact {
+        hand: i32,
+    },
+    Attack,
+    /// Interacting with a specific spot on the entity, used by armor stands
+    InteractAt {
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+        hand: i32,
+    },
+}
+
+/// A player right or left clicking an entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct InteractEntity {
+    pub entity_id: i32,
+    pub action: InteractAction,
+    pub sneaking: bool,
+}
+
+impl InteractEntity {
+    // Entity ID             varint
+    // Type                  varint
+    // Target X/Y/Z          float (only for interact at)
+    // Hand                  varint (only for interact and interact at)
+    // Sneaking              boolean
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        let entity_id = buf.read_varint()?;
+        let interaction = buf.read_varint()?;
+
+        let action = match interaction {
+            INTERACT => InteractAction::Interact {
+                hand: buf.read_varint()?,
+            },
+            ATTACK => InteractAction::Attack,
+            INTERACT_AT => InteractAction::InteractAt {
+                target_x: buf.read_float()?,
+                target_y: buf.read_float()?,
+                target_z: buf.read_float()?,
+                hand: buf.read_varint()?,
+            },
+            _ => {
+                return Err(MinecraftError::Protocol(format!(
+                    "Invalid interaction type: {interaction}"
+                )))
+            }
+        };
+
+        Ok(Self {
+            entity_id,
+            action,
+            sneaking: buf.read_boolean()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_interact() {
+        let packet = [0x05, 0x00, 0x01, 0x01];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            InteractEntity::decode(&mut buf).unwrap(),
+            InteractEntity {
+                entity_id: 5,
+                action: InteractAction::Interact { hand: 1 },
+                sneaking: true,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_attack() {
+        // no hand follows an attack
+        let packet = [0x05, 0x01, 0x00];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            InteractEntity::decode(&mut buf).unwrap(),
+            InteractEntity {
+                entity_id: 5,
+                action: InteractAction::Attack,
+                sneaking: false,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_interact_at() {
+        let mut packet = vec![0x05, 0x02];
+        packet.extend_from_slice(&0.5f32.to_be_bytes());
+        packet.extend_from_slice(&1.5f32.to_be_bytes());
+        packet.extend_from_slice(&(-0.25f32).to_be_bytes());
+        packet.extend_from_slice(&[0x00, 0x00]);
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            InteractEntity::decode(&mut buf).unwrap(),
+            InteractEntity {
+                entity_id: 5,
+                action: InteractAction::InteractAt {
+                    target_x: 0.5,
+                    target_y: 1.5,
+                    target_z: -0.25,
+                    hand: 0,
+                },
+                sneaking: false,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::error::{MinecraftError, Result};
+use crate::nbt::Nbt;
+use crate::packet::buf::PacketBuf;
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+
+// Data component type ids
+pub const MAX_STACK_SIZE_COMPONENT: i32 = 1;
+pub const MAX_DAMAGE_COMPONENT: i32 = 2;
+pub const DAMAGE_COMPONENT: i32 = 3;
+pub const UNBREAKABLE_COMPONENT: i32 = 4;
+pub const CUSTOM_NAME_COMPONENT: i32 = 5;
+
+/// A data component attached to an item, only the common ones are supported
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemComponent {
+    MaxStackSize(i32),
+    MaxDamage(i32),
+    Damage(i32),
+    Unbreakable {
+        show_in_tooltip: bool,
+    },
+    /// Text component, sent as NBT
+    CustomName(Nbt),
+}
+
+impl ItemComponent {
+    pub fn type_id(&self) -> i32 {
+        match self {
+            ItemComponent::MaxStackSize(_) => MAX_STACK_SIZE_COMPONENT,
+            ItemComponent::MaxDamage(_) => MAX_DAMAGE_COMPONENT,
+            ItemComponent::Damage(_) => DAMAGE_COMPONENT,
+            ItemComponent::Unbreakable { .. } => UNBREAKABLE_COMPONENT,
+            ItemComponent::CustomName(_) => CUSTOM_NAME_COMPONENT,
+        }
+    }
+
+    fn read(buf: &mut PacketBuf) -> Result<Self> {
+        let type_id = buf.read_varint()?;
+        Ok(match type_id {
+            MAX_STACK_SIZE_COMPONENT => ItemComponent::MaxStackSize(buf.read_varint()?),
+            MAX_DAMAGE_COMPONENT => ItemComponent::MaxDamage(buf.read_varint()?),
+            DAMAGE_COMPONENT => ItemComponent::Damage(buf.read_varint()?),
+            UNBREAKABLE_COMPONENT => ItemComponent::Unbreakable {
+                show_in_tooltip: buf.read_boolean()?,
+            },
+            CUSTOM_NAME_COMPONENT => ItemComponent::CustomName(buf.read_nbt()?),
+            // every component has its own encoding, without knowing it we
+            // can't tell where the item ends
+            _ => return Err(MinecraftError::UnsupportedItemComponent(type_id)),
+        })
+    }
+
+    fn write(&self, buf: &mut BytesMut) {
+        PacketReader::write_varint(buf, self.type_id());
+        match self {
+            ItemComponent::MaxStackSize(value)
+            | ItemComponent::MaxDamage(value)
+            | ItemComponent::Damage(value) => PacketReader::write_varint(buf, *value),
+            ItemComponent::Unbreakable { show_in_tooltip } => buf.put_u8(*show_in_tooltip as u8),
+            ItemComponent::CustomName(name) => name.write_nameless(buf),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    pub item_id: i32,
+    pub count: i32,
+    /// Components added on top of the item's defaults
+    pub components: Vec<ItemComponent>,
+    /// Default components this stack doesn't have, by type id
+    pub removed_components: Vec<i32>,
+}
+
+impl ItemStack {
+    pub fn new(item_id: i32, count: i32) -> Self {
+        Self {
+            item_id,
+            count,
+            components: Vec::new(),
+            removed_components: Vec::new(),
+        }
+    }
+
+    pub fn with_component(mut self, component: ItemComponent) -> Self {
+        self.components.push(component);
+        self
+    }
+}
+
+// Item Count            varint (0 means the slot is empty and nothing follows)
+// Item ID               varint
+// Components to add     varint
+// Components to remove  varint
+// Components            type varint + data, for each added component
+// Removed components    type varint, for each removed component
+pub fn read_item_stack(buf: &mut PacketBuf) -> Result<Option<ItemStack>> {
+    let count = buf.read_varint()?;
+    if count <= 0 {
+        return Ok(None);
+    }
+
+    let item_id = buf.read_varint()?;
+    let added = buf.read_varint()?;
+    let removed = buf.read_varint()?;
+    if added < 0 || removed < 0 {
+        return Err(MinecraftError::Protocol(format!(
+            "Invalid component counts for item {item_id}: {added} added, {removed} removed"
+        )));
+    }
+
+    let components = (0..added)
+        .map(|_| ItemComponent::read(buf))
+        .collect::<Result<_>>()?;
+    let removed_components = (0..removed)
+        .map(|_| buf.read_varint())
+        .collect::<Result<_>>()?;
+
+    Ok(Some(ItemStack {
+        item_id,
+        count,
+        components,
+        removed_components,
+    }))
+}
+
+pub fn write_item_stack(buf: &mut BytesMut, item: Option<&ItemStack>) {
+    let Some(item) = item.filter(|item| item.count > 0) else {
+        PacketReader::write_varint(buf, 0);
+        return;
+    };
+
+    PacketReader::write_varint(buf, item.count);
+    PacketReader::write_varint(buf, item.item_id);
+    PacketReader::write_varint(buf, item.components.len() as i32);
+    PacketReader::write_varint(buf, item.removed_components.len() as i32);
+    for component in &item.components {
+        component.write(buf);
+    }
+    for &type_id in &item.removed_components {
+        PacketReader::write_varint(buf, type_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_item_stack() {
+        let mut buf = BytesMut::new();
+        write_item_stack(&mut buf, None);
+        assert_eq!(&buf[..], &[0x00]);
+
+        let mut packet = PacketBuf::new(&buf);
+        assert_eq!(read_item_stack(&mut packet).unwrap(), None);
+        assert!(packet.is_empty());
+    }
+
+    #[test]
+    fn test_item_stack_with_component() {
+        let item = ItemStack::new(1, 16)
+            .with_component(ItemComponent::CustomName(Nbt::String("Rock".to_string())));
+
+        let mut buf = BytesMut::new();
+        write_item_stack(&mut buf, Some(&item));
+
+        // 0x10                          Count                 varint
+        // 0x01                          Item ID               varint
+        // 0x01 0x00                     Added / removed       varint
+        // 0x05                          Custom name           varint
+        // 0x08 0x00 0x04 "Rock"         Name                  NBT string
+        assert_eq!(&buf[..], b"\x10\x01\x01\x00\x05\x08\x00\x04Rock");
+
+        let mut packet = PacketBuf::new(&buf);
+        assert_eq!(read_item_stack(&mut packet).unwrap(), Some(item));
+        assert!(packet.is_empty());
+    }
+}
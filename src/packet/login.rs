@@ -0,0 +1,189 @@
+use crate::error::Result;
+use crate::packet::buf::PacketBuf;
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+use serde_json::json;
+
+pub const DISCONNECT_PACKET_ID: i32 = 0x00;
+pub const ENCRYPTION_REQUEST_PACKET_ID: i32 = 0x01;
+pub const SET_COMPRESSION_PACKET_ID: i32 = 0x03;
+pub const LOGIN_PLUGIN_REQUEST_PACKET_ID: i32 = 0x04;
+
+// Packet ID             varint
+// Reason                string (JSON text component)
+pub fn write_login_disconnect_packet(reason: &str) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, DISCONNECT_PACKET_ID);
+    PacketReader::write_string(&mut packet, &json!({ "text": reason }).to_string());
+
+    packet
+}
+
+// Packet ID             varint
+// Server ID             string, always empty since 1.7
+// Public Key            byte array (DER encoded)
+// Verify Token          byte array
+// Should Authenticate   boolean
+pub fn write_encryption_request_packet(
+    public_key: &[u8],
+    verify_token: &[u8],
+    should_authenticate: bool,
+) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, ENCRYPTION_REQUEST_PACKET_ID);
+    PacketReader::write_string(&mut packet, "");
+    PacketReader::write_byte_array(&mut packet, public_key);
+    PacketReader::write_byte_array(&mut packet, verify_token);
+    packet.put_u8(should_authenticate as u8);
+
+    packet
+}
+
+// Packet ID             varint
+// Threshold             varint
+pub fn write_set_compression_packet(threshold: usize) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_COMPRESSION_PACKET_ID);
+    PacketReader::write_varint(&mut packet, threshold as i32);
+
+    packet
+}
+
+// Packet ID             varint
+// Message ID            varint
+// Channel               identifier
+// Data                  rest of the packet
+pub fn write_login_plugin_request_packet(message_id: i32, channel: &str, data: &[u8]) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, LOGIN_PLUGIN_REQUEST_PACKET_ID);
+    PacketReader::write_varint(&mut packet, message_id);
+    PacketReader::write_string(&mut packet, channel);
+    packet.put_slice(data);
+
+    packet
+}
+
+/// Shared secret and verify token, both encrypted with the server's public key
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptionResponse {
+    pub shared_secret: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+impl EncryptionResponse {
+    // Shared Secret         byte array
+    // Verify Token          byte array
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        Ok(Self {
+            shared_secret: buf.read_byte_array()?,
+            verify_token: buf.read_byte_array()?,
+        })
+    }
+}
+
+/// A client's answer to a Login Plugin Request
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginPluginResponse {
+    pub message_id: i32,
+    /// `None` when the client didn't understand the request
+    pub data: Option<Vec<u8>>,
+}
+
+impl LoginPluginResponse {
+    // Message ID            varint
+    // Successful            boolean
+    // Data                  rest of the packet, only when successful
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        let message_id = buf.read_varint()?;
+        let understood = buf.read_boolean()?;
+        let data = understood.then(|| buf.read_remaining().to_vec());
+
+        Ok(Self { message_id, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_compression_packet() {
+        let packet = write_set_compression_packet(256);
+
+        // 0x03                          Packet ID             varint
+        // 0x80 0x02                     Threshold             varint
+        assert_eq!(&packet[..], b"\x03\x80\x02");
+    }
+
+    #[test]
+    fn test_login_plugin_request_packet() {
+        let packet = write_login_plugin_request_packet(7, "a:b", b"\x01");
+
+        // 0x04                          Packet ID             varint
+        // 0x07                          Message ID            varint
+        // 0x03 "a:b"                    Channel               identifier
+        // 0x01                          Data                  rest of the packet
+        assert_eq!(&packet[..], b"\x04\x07\x03a:b\x01");
+    }
+
+    #[test]
+    fn test_encryption_request_packet() {
+        let packet = write_encryption_request_packet(b"key", b"tok", true);
+
+        // 0x01                          Packet ID             varint
+        // 0x00                          Server ID             string
+        // 0x03 "key"                    Public Key            byte array
+        // 0x03 "tok"                    Verify Token          byte array
+        // 0x01                          Should Authenticate   boolean
+        assert_eq!(&packet[..], b"\x01\x00\x03key\x03tok\x01");
+
+        let mut buf = PacketBuf::new(b"\x02ab\x03tok");
+        assert_eq!(
+            EncryptionResponse::decode(&mut buf).unwrap(),
+            EncryptionResponse {
+                shared_secret: b"ab".to_vec(),
+                verify_token: b"tok".to_vec(),
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_login_plugin_response() {
+        let mut buf = PacketBuf::new(b"\x07\x01hi");
+        assert_eq!(
+            LoginPluginResponse::decode(&mut buf).unwrap(),
+            LoginPluginResponse {
+                message_id: 7,
+                data: Some(b"hi".to_vec()),
+            }
+        );
+        assert!(buf.is_empty());
+
+        // a client that doesn't know the channel answers without data
+        let mut buf = PacketBuf::new(b"\x07\x00");
+        assert_eq!(
+            LoginPluginResponse::decode(&mut buf).unwrap(),
+            LoginPluginResponse {
+                message_id: 7,
+                data: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_login_disconnect_packet() {
+        let packet = write_login_disconnect_packet("bye");
+
+        let mut buf = &packet[..];
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x00);
+        assert_eq!(
+            PacketReader::read_string(&mut buf).unwrap(),
+            r#"{"text":"bye"}"#
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+
+// Metadata value types
+pub const BYTE_TYPE: i32 = 0;
+pub const VARINT_TYPE: i32 = 1;
+pub const FLOAT_TYPE: i32 = 3;
+pub const STRING_TYPE: i32 = 4;
+pub const BOOLEAN_TYPE: i32 = 8;
+pub const POSE_TYPE: i32 = 21;
+
+/// Ends the list of metadata entries
+pub const METADATA_END: u8 = 0xFF;
+
+// Entity
+pub const ENTITY_FLAGS_INDEX: u8 = 0;
+pub const POSE_INDEX: u8 = 6;
+// Living Entity
+pub const HEALTH_INDEX: u8 = 9;
+// Player
+pub const DISPLAYED_SKIN_PARTS_INDEX: u8 = 17;
+pub const MAIN_HAND_INDEX: u8 = 18;
+
+// Entity flags
+pub const ON_FIRE_FLAG: u8 = 0x01;
+pub const SNEAKING_FLAG: u8 = 0x02;
+pub const SPRINTING_FLAG: u8 = 0x08;
+pub const SWIMMING_FLAG: u8 = 0x10;
+pub const INVISIBLE_FLAG: u8 = 0x20;
+pub const GLOWING_FLAG: u8 = 0x40;
+pub const ELYTRA_FLYING_FLAG: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pose {
+    Standing,
+    FallFlying,
+    Sleeping,
+    Swimming,
+    SpinAttack,
+    Sneaking,
+    LongJumping,
+    Dying,
+}
+
+impl Pose {
+    pub fn id(self) -> i32 {
+        match self {
+            Pose::Standing => 0,
+            Pose::FallFlying => 1,
+            Pose::Sleeping => 2,
+            Pose::Swimming => 3,
+            Pose::SpinAttack => 4,
+            Pose::Sneaking => 5,
+            Pose::LongJumping => 6,
+            Pose::Dying => 7,
+        }
+    }
+}
+
+/// Entries of a Set Entity Metadata packet, each an index, a type and a value
+///
+/// Only the entries that changed need to be sent, the client keeps the rest.
+#[derive(Debug, Default, Clone)]
+pub struct EntityMetadata {
+    entries: BytesMut,
+}
+
+impl EntityMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(mut self, index: u8, value_type: i32) -> Self {
+        self.entries.put_u8(index);
+        PacketReader::write_varint(&mut self.entries, value_type);
+        self
+    }
+
+    pub fn byte(self, index: u8, value: u8) -> Self {
+        let mut metadata = self.entry(index, BYTE_TYPE);
+        metadata.entries.put_u8(value);
+        metadata
+    }
+
+    pub fn varint(self, index: u8, value: i32) -> Self {
+        let mut metadata = self.entry(index, VARINT_TYPE);
+        PacketReader::write_varint(&mut metadata.entries, value);
+        metadata
+    }
+
+    pub fn float(self, index: u8, value: f32) -> Self {
+        let mut metadata = self.entry(index, FLOAT_TYPE);
+        metadata.entries.put_f32(value);
+        metadata
+    }
+
+    pub fn string(self, index: u8, value: &str) -> Self {
+        let mut metadata = self.entry(index, STRING_TYPE);
+        PacketReader::write_string(&mut metadata.entries, value);
+        metadata
+    }
+
+    pub fn boolean(self, index: u8, value: bool) -> Self {
+        let mut metadata = self.entry(index, BOOLEAN_TYPE);
+        metadata.entries.put_u8(value as u8);
+        metadata
+    }
+
+    pub fn pose(self, index: u8, pose: Pose) -> Self {
+        let mut metadata = self.entry(index, POSE_TYPE);
+        PacketReader::write_varint(&mut metadata.entries, pose.id());
+        metadata
+    }
+
+    /// Writes the entries followed by the terminator
+    pub fn encode(&self, buf: &mut BytesMut) {
+        buf.extend_from_slice(&self.entries);
+        buf.put_u8(METADATA_END);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_entity_metadata() {
+        let metadata = EntityMetadata::new()
+            .byte(ENTITY_FLAGS_INDEX, SNEAKING_FLAG)
+            .pose(POSE_INDEX, Pose::Sneaking)
+            .float(HEALTH_INDEX, 20.0);
+
+        let mut buf = BytesMut::new();
+        metadata.encode(&mut buf);
+
+        // 0x00 0x00 0x02                Entity flags          byte
+        // 0x06 0x15 0x05                Pose                  pose
+        // 0x09 0x03 0x41 0xA0 0x00 0x00 Health                float
+        // 0xFF                          End of metadata
+        assert_eq!(
+            &buf[..],
+            b"\x00\x00\x02\x06\x15\x05\x09\x03\x41\xA0\x00\x00\xFF"
+        );
+
+        let mut buf = BytesMut::new();
+        EntityMetadata::new().encode(&mut buf);
+        assert_eq!(&buf[..], &[METADATA_END]);
+    }
+}
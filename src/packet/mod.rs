@@ -1 +1,18 @@
+pub mod advancements;
+pub mod buf;
+pub mod cipher;
+pub mod client_information;
+pub mod common;
+pub mod container;
+pub mod frame;
+pub mod interact_entity;
+pub mod item;
+pub mod login;
+pub mod metadata;
+pub mod play;
+pub mod player_command;
 pub mod reader;
+pub mod resource_pack;
+pub mod sound;
+pub mod use_item;
+pub mod vehicle;
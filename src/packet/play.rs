@@ -0,0 +1,1461 @@
+use crate::packet::metadata::EntityMetadata;
+use crate::packet::reader::PacketReader;
+use crate::player::{GameMode, PlayerState, ProfileProperty};
+use crate::text::Component;
+use crate::world::Difficulty;
+use crate::world::Position;
+use crate::world::{EntityLocation, Weather, WorldBorder};
+use bytes::{BufMut, BytesMut};
+use uuid::Uuid;
+
+pub const SPAWN_ENTITY_PACKET_ID: i32 = 0x01;
+pub const ENTITY_ANIMATION_PACKET_ID: i32 = 0x03;
+pub const ACKNOWLEDGE_BLOCK_CHANGE_PACKET_ID: i32 = 0x05;
+pub const SET_BLOCK_DESTROY_STAGE_PACKET_ID: i32 = 0x06;
+pub const BLOCK_UPDATE_PACKET_ID: i32 = 0x09;
+pub const CHANGE_DIFFICULTY_PACKET_ID: i32 = 0x0B;
+pub const CHUNK_BATCH_FINISHED_PACKET_ID: i32 = 0x0C;
+pub const CHUNK_BATCH_START_PACKET_ID: i32 = 0x0D;
+pub const SET_COOLDOWN_PACKET_ID: i32 = 0x17;
+pub const GAME_EVENT_PACKET_ID: i32 = 0x22;
+pub const INITIALIZE_WORLD_BORDER_PACKET_ID: i32 = 0x25;
+pub const KEEP_ALIVE_PACKET_ID: i32 = 0x26;
+pub const LOGIN_PACKET_ID: i32 = 0x2B;
+pub const UPDATE_ENTITY_POSITION_AND_ROTATION_PACKET_ID: i32 = 0x2F;
+pub const PLAYER_ABILITIES_PACKET_ID: i32 = 0x38;
+pub const PLAYER_CHAT_MESSAGE_PACKET_ID: i32 = 0x39;
+pub const PLAYER_INFO_UPDATE_PACKET_ID: i32 = 0x3E;
+pub const SYNCHRONIZE_PLAYER_POSITION_PACKET_ID: i32 = 0x40;
+pub const REMOVE_ENTITIES_PACKET_ID: i32 = 0x42;
+pub const SET_ACTION_BAR_TEXT_PACKET_ID: i32 = 0x4C;
+pub const SET_RENDER_DISTANCE_PACKET_ID: i32 = 0x55;
+pub const SET_ENTITY_METADATA_PACKET_ID: i32 = 0x58;
+pub const SET_ENTITY_VELOCITY_PACKET_ID: i32 = 0x5A;
+pub const SET_EXPERIENCE_PACKET_ID: i32 = 0x5C;
+pub const SET_HEALTH_PACKET_ID: i32 = 0x5D;
+pub const SET_PASSENGERS_PACKET_ID: i32 = 0x5F;
+pub const SET_SIMULATION_DISTANCE_PACKET_ID: i32 = 0x62;
+pub const SET_SUBTITLE_TEXT_PACKET_ID: i32 = 0x63;
+pub const SET_TITLE_TEXT_PACKET_ID: i32 = 0x65;
+pub const SET_TITLE_ANIMATION_TIMES_PACKET_ID: i32 = 0x66;
+pub const START_CONFIGURATION_PACKET_ID: i32 = 0x69;
+pub const SYSTEM_CHAT_PACKET_ID: i32 = 0x6C;
+pub const TELEPORT_ENTITY_PACKET_ID: i32 = 0x70;
+
+// Player Abilities flags
+pub const ABILITY_INVULNERABLE: u8 = 0x01;
+pub const ABILITY_FLYING: u8 = 0x02;
+pub const ABILITY_ALLOW_FLYING: u8 = 0x04;
+pub const ABILITY_INSTANT_BREAK: u8 = 0x08;
+
+pub const DEFAULT_FLYING_SPEED: f32 = 0.05;
+pub const DEFAULT_FOV_MODIFIER: f32 = 0.1;
+
+// Player Info Update actions
+pub const ADD_PLAYER_ACTION: u8 = 0x01;
+pub const INITIALIZE_CHAT_ACTION: u8 = 0x02;
+pub const UPDATE_GAME_MODE_ACTION: u8 = 0x04;
+pub const UPDATE_LISTED_ACTION: u8 = 0x08;
+pub const UPDATE_LATENCY_ACTION: u8 = 0x10;
+pub const UPDATE_DISPLAY_NAME_ACTION: u8 = 0x20;
+
+/// Destroy stage that removes the cracks, 0 to 9 show them
+pub const CLEAR_DESTROY_STAGE: u8 = 255;
+
+// Game Event events
+pub const END_RAINING_EVENT: u8 = 1;
+pub const BEGIN_RAINING_EVENT: u8 = 2;
+pub const CHANGE_GAME_MODE_EVENT: u8 = 3;
+pub const RAIN_LEVEL_CHANGE_EVENT: u8 = 7;
+pub const THUNDER_LEVEL_CHANGE_EVENT: u8 = 8;
+
+// Entity Animation ids
+pub const SWING_MAIN_ARM_ANIMATION: u8 = 0;
+pub const SWING_OFFHAND_ANIMATION: u8 = 3;
+
+// Entity type ids
+pub const EXPERIENCE_ORB_ENTITY_TYPE: i32 = 38;
+pub const ITEM_ENTITY_TYPE: i32 = 58;
+
+/// Fields of the Spawn Entity packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnEntity {
+    pub entity_id: i32,
+    pub uuid: Uuid,
+    /// See the `*_ENTITY_TYPE` ids
+    pub entity_type: i32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub head_yaw: f32,
+    /// Meaning depends on the entity type, like the block state of a falling block
+    pub data: i32,
+    /// In blocks per tick
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub velocity_z: f64,
+}
+
+// Packet ID             varint
+// Entity ID             varint
+// Entity UUID           uuid
+// Type                  varint
+// X                     double
+// Y                     double
+// Z                     double
+// Pitch                 angle
+// Yaw                   angle
+// Head Yaw              angle
+// Data                  varint
+// Velocity X            short
+// Velocity Y            short
+// Velocity Z            short
+pub fn write_spawn_entity_packet(entity: &SpawnEntity) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SPAWN_ENTITY_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity.entity_id);
+    packet.put_slice(entity.uuid.as_bytes());
+    PacketReader::write_varint(&mut packet, entity.entity_type);
+    packet.put_f64(entity.x);
+    packet.put_f64(entity.y);
+    packet.put_f64(entity.z);
+    PacketReader::write_angle(&mut packet, entity.pitch);
+    PacketReader::write_angle(&mut packet, entity.yaw);
+    PacketReader::write_angle(&mut packet, entity.head_yaw);
+    PacketReader::write_varint(&mut packet, entity.data);
+    packet.put_i16(velocity_to_fixed_point(entity.velocity_x));
+    packet.put_i16(velocity_to_fixed_point(entity.velocity_y));
+    packet.put_i16(velocity_to_fixed_point(entity.velocity_z));
+
+    packet
+}
+
+// Packet ID             varint
+// Entity ID             varint
+// Animation             unsigned byte
+pub fn write_entity_animation_packet(entity_id: i32, animation: u8) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, ENTITY_ANIMATION_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_id);
+    packet.put_u8(animation);
+
+    packet
+}
+
+// Packet ID             varint
+// Sequence ID           varint
+pub fn write_acknowledge_block_change_packet(sequence: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, ACKNOWLEDGE_BLOCK_CHANGE_PACKET_ID);
+    PacketReader::write_varint(&mut packet, sequence);
+
+    packet
+}
+
+// Packet ID             varint
+// Entity ID             varint (the player breaking the block)
+// Location              position
+// Destroy Stage         unsigned byte (0-9, anything else clears it)
+pub fn write_set_block_destroy_stage_packet(
+    entity_id: i32,
+    position: Position,
+    stage: u8,
+) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_BLOCK_DESTROY_STAGE_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_id);
+    PacketReader::write_position(&mut packet, position);
+    packet.put_u8(stage);
+
+    packet
+}
+
+// Packet ID             varint
+// Location              position
+// Block ID              varint
+pub fn write_block_update_packet(position: Position, block_state: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, BLOCK_UPDATE_PACKET_ID);
+    PacketReader::write_position(&mut packet, position);
+    PacketReader::write_varint(&mut packet, block_state);
+
+    packet
+}
+
+/// Fields of the Login (play) packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayLogin {
+    pub entity_id: i32,
+    pub hardcore: bool,
+    pub dimension_names: Vec<(String, String)>,
+    pub max_players: i32,
+    pub view_distance: i32,
+    pub simulation_distance: i32,
+    pub reduced_debug_info: bool,
+    pub enable_respawn_screen: bool,
+    pub do_limited_crafting: bool,
+    // index into the dimension_type registry
+    pub dimension_type: i32,
+    pub dimension_name: String,
+    pub hashed_seed: i64,
+    pub game_mode: GameMode,
+    pub previous_game_mode: Option<GameMode>,
+    pub is_debug: bool,
+    pub is_flat: bool,
+    pub portal_cooldown: i32,
+    pub enforces_secure_chat: bool,
+}
+
+// Packet ID             varint
+// Entity ID             int
+// Is Hardcore           boolean
+// Dimension Count       varint
+// Dimension Names       identifier array
+// Max Players           varint
+// View Distance         varint
+// Simulation Distance   varint
+// Reduced Debug Info    boolean
+// Respawn Screen        boolean
+// Limited Crafting      boolean
+// Dimension Type        varint
+// Dimension Name        identifier
+// Hashed Seed           long
+// Game Mode             unsigned byte
+// Previous Game Mode    byte (-1 when there is none)
+// Is Debug              boolean
+// Is Flat               boolean
+// Has Death Location    boolean
+// Portal Cooldown       varint
+// Secure Chat           boolean
+pub fn write_login_packet(login: &PlayLogin) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, LOGIN_PACKET_ID);
+    packet.put_i32(login.entity_id);
+    packet.put_u8(login.hardcore as u8);
+    PacketReader::write_identifier_array(&mut packet, &login.dimension_names);
+    PacketReader::write_varint(&mut packet, login.max_players);
+    PacketReader::write_varint(&mut packet, login.view_distance);
+    PacketReader::write_varint(&mut packet, login.simulation_distance);
+    packet.put_u8(login.reduced_debug_info as u8);
+    packet.put_u8(login.enable_respawn_screen as u8);
+    packet.put_u8(login.do_limited_crafting as u8);
+    PacketReader::write_varint(&mut packet, login.dimension_type);
+    PacketReader::write_string(&mut packet, &login.dimension_name);
+    packet.put_i64(login.hashed_seed);
+    packet.put_u8(login.game_mode.id());
+    packet.put_i8(login.previous_game_mode.map_or(-1, |mode| mode.id() as i8));
+    packet.put_u8(login.is_debug as u8);
+    packet.put_u8(login.is_flat as u8);
+    packet.put_u8(0); // no death location
+    PacketReader::write_varint(&mut packet, login.portal_cooldown);
+    packet.put_u8(login.enforces_secure_chat as u8);
+
+    packet
+}
+
+// Packet ID             varint
+// Difficulty            unsigned byte
+// Locked                boolean
+pub fn write_change_difficulty_packet(difficulty: Difficulty, locked: bool) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, CHANGE_DIFFICULTY_PACKET_ID);
+    packet.put_u8(difficulty.id());
+    packet.put_u8(locked as u8);
+
+    packet
+}
+
+// Packet ID             varint
+pub fn write_chunk_batch_start_packet() -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, CHUNK_BATCH_START_PACKET_ID);
+
+    packet
+}
+
+// Packet ID             varint
+// Batch Size            varint (Chunk Data packets since the batch started)
+pub fn write_chunk_batch_finished_packet(batch_size: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, CHUNK_BATCH_FINISHED_PACKET_ID);
+    PacketReader::write_varint(&mut packet, batch_size);
+
+    packet
+}
+
+// Packet ID             varint
+// Item ID               varint
+// Cooldown Ticks        varint (0 clears the cooldown)
+pub fn write_set_cooldown_packet(item_id: i32, ticks: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_COOLDOWN_PACKET_ID);
+    PacketReader::write_varint(&mut packet, item_id);
+    PacketReader::write_varint(&mut packet, ticks);
+
+    packet
+}
+
+// Packet ID             varint
+// Event                 unsigned byte
+// Value                 float
+pub fn write_game_event_packet(event: u8, value: f32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, GAME_EVENT_PACKET_ID);
+    packet.put_u8(event);
+    packet.put_f32(value);
+
+    packet
+}
+
+/// Game Events that switch the client to `weather`, the levels are only
+/// used by the client while it's raining
+pub fn write_weather_packets(weather: &Weather) -> Vec<BytesMut> {
+    let raining_event = if weather.raining {
+        BEGIN_RAINING_EVENT
+    } else {
+        END_RAINING_EVENT
+    };
+
+    vec![
+        write_game_event_packet(raining_event, 0.0),
+        write_game_event_packet(RAIN_LEVEL_CHANGE_EVENT, weather.rain_level),
+        write_game_event_packet(THUNDER_LEVEL_CHANGE_EVENT, weather.thunder_level),
+    ]
+}
+
+// Packet ID             varint
+// Flags                 byte
+// Flying Speed          float
+// Field of View         float
+pub fn write_player_abilities_packet(flags: u8, fly_speed: f32, fov: f32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, PLAYER_ABILITIES_PACKET_ID);
+    packet.put_u8(flags);
+    packet.put_f32(fly_speed);
+    packet.put_f32(fov);
+
+    packet
+}
+
+/// Converts a velocity in blocks per tick to the protocol's fixed-point short
+pub fn velocity_to_fixed_point(velocity: f64) -> i16 {
+    (velocity * 8000.0).clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+/// Change in position as sent in Update Entity Position, in 1/4096 blocks.
+/// `None` when the entity moved 8 blocks or more, it has to be teleported then.
+pub fn position_delta(from: f64, to: f64) -> Option<i16> {
+    // both ends are rounded on their own, like the client does, so a run of
+    // deltas adds up to the same position a teleport would give
+    let delta = PacketReader::fixed_point_steps(to, 12) - PacketReader::fixed_point_steps(from, 12);
+    if delta < i16::MIN as f64 || delta > i16::MAX as f64 {
+        return None;
+    }
+    Some(delta as i16)
+}
+
+// Packet ID             varint
+// Entity ID             varint
+// Delta X               short (see `position_delta`)
+// Delta Y               short
+// Delta Z               short
+// Yaw                   angle
+// Pitch                 angle
+// On Ground             boolean
+pub fn write_update_entity_position_and_rotation_packet(
+    entity_id: i32,
+    delta: (i16, i16, i16),
+    yaw: f32,
+    pitch: f32,
+    on_ground: bool,
+) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, UPDATE_ENTITY_POSITION_AND_ROTATION_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_id);
+    packet.put_i16(delta.0);
+    packet.put_i16(delta.1);
+    packet.put_i16(delta.2);
+    PacketReader::write_angle(&mut packet, yaw);
+    PacketReader::write_angle(&mut packet, pitch);
+    packet.put_u8(on_ground as u8);
+
+    packet
+}
+
+// Packet ID             varint
+// Entity ID             varint
+// X                     double
+// Y                     double
+// Z                     double
+// Yaw                   angle
+// Pitch                 angle
+// On Ground             boolean
+pub fn write_teleport_entity_packet(
+    entity_id: i32,
+    location: &EntityLocation,
+    on_ground: bool,
+) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, TELEPORT_ENTITY_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_id);
+    packet.put_f64(location.x);
+    packet.put_f64(location.y);
+    packet.put_f64(location.z);
+    PacketReader::write_angle(&mut packet, location.yaw);
+    PacketReader::write_angle(&mut packet, location.pitch);
+    packet.put_u8(on_ground as u8);
+
+    packet
+}
+
+// Packet ID             varint
+// Entity ID             varint
+// Velocity X            short
+// Velocity Y            short
+// Velocity Z            short
+pub fn write_entity_velocity_packet(entity_id: i32, vx: f64, vy: f64, vz: f64) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_ENTITY_VELOCITY_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_id);
+    packet.put_i16(velocity_to_fixed_point(vx));
+    packet.put_i16(velocity_to_fixed_point(vy));
+    packet.put_i16(velocity_to_fixed_point(vz));
+
+    packet
+}
+
+// Packet ID             varint
+// Entity ID             varint
+// Metadata              entity metadata, terminated by 0xFF
+pub fn write_entity_metadata_packet(entity_id: i32, metadata: &EntityMetadata) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_ENTITY_METADATA_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_id);
+    metadata.encode(&mut packet);
+
+    packet
+}
+
+// Packet ID             varint
+// Experience Bar        float (0.0 to 1.0)
+// Level                 varint
+// Total Experience      varint
+pub fn write_set_experience_packet(bar: f32, level: i32, total: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_EXPERIENCE_PACKET_ID);
+    packet.put_f32(bar);
+    PacketReader::write_varint(&mut packet, level);
+    PacketReader::write_varint(&mut packet, total);
+
+    packet
+}
+
+// Packet ID             varint
+// Health                float (0 or less means dead)
+// Food                  varint (0 to 20)
+// Food Saturation       float (0.0 to 5.0)
+pub fn write_set_health_packet(health: f32, food: i32, saturation: f32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_HEALTH_PACKET_ID);
+    packet.put_f32(health);
+    PacketReader::write_varint(&mut packet, food);
+    packet.put_f32(saturation);
+
+    packet
+}
+
+/// First protocol version (1.21.2) with velocity in Synchronize Player
+/// Position and its flags widened to an int
+pub const TELEPORT_FLAGS_PROTOCOL_VERSION: i32 = 768;
+
+/// Synchronize Player Position from TELEPORT_FLAGS_PROTOCOL_VERSION on,
+/// packets added before it in 1.21.2 moved it up
+pub const TELEPORT_FLAGS_SYNCHRONIZE_PLAYER_POSITION_PACKET_ID: i32 = 0x42;
+
+/// Which fields of a teleport are relative to what the client has, the rest
+/// replace it
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TeleportFlags(u32);
+
+impl TeleportFlags {
+    pub const RELATIVE_X: u32 = 0x01;
+    pub const RELATIVE_Y: u32 = 0x02;
+    pub const RELATIVE_Z: u32 = 0x04;
+    pub const RELATIVE_YAW: u32 = 0x08;
+    pub const RELATIVE_PITCH: u32 = 0x10;
+    // the ones below only exist from TELEPORT_FLAGS_PROTOCOL_VERSION
+    pub const RELATIVE_VELOCITY_X: u32 = 0x20;
+    pub const RELATIVE_VELOCITY_Y: u32 = 0x40;
+    pub const RELATIVE_VELOCITY_Z: u32 = 0x80;
+    /// Rotate the velocity by the change in rotation before applying it
+    pub const ROTATE_VELOCITY: u32 = 0x100;
+
+    /// Everything absolute
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    fn with(self, bit: u32) -> Self {
+        Self(self.0 | bit)
+    }
+
+    pub fn relative_x(self) -> Self {
+        self.with(Self::RELATIVE_X)
+    }
+
+    pub fn relative_y(self) -> Self {
+        self.with(Self::RELATIVE_Y)
+    }
+
+    pub fn relative_z(self) -> Self {
+        self.with(Self::RELATIVE_Z)
+    }
+
+    pub fn relative_yaw(self) -> Self {
+        self.with(Self::RELATIVE_YAW)
+    }
+
+    pub fn relative_pitch(self) -> Self {
+        self.with(Self::RELATIVE_PITCH)
+    }
+
+    pub fn relative_velocity(self) -> Self {
+        self.with(Self::RELATIVE_VELOCITY_X | Self::RELATIVE_VELOCITY_Y | Self::RELATIVE_VELOCITY_Z)
+    }
+
+    pub fn rotate_velocity(self) -> Self {
+        self.with(Self::ROTATE_VELOCITY)
+    }
+
+    /// Writes the flags as a byte before 1.21.2, dropping the velocity bits
+    /// older clients don't know, and as an int from then on
+    pub fn write(self, buf: &mut BytesMut, protocol_version: i32) {
+        if protocol_version >= TELEPORT_FLAGS_PROTOCOL_VERSION {
+            buf.put_u32(self.0);
+        } else {
+            buf.put_u8((self.0 & 0x1F) as u8);
+        }
+    }
+}
+
+/// Fields of the Synchronize Player Position packet
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PlayerTeleport {
+    /// Echoed back in Confirm Teleportation
+    pub teleport_id: i32,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// Only sent from TELEPORT_FLAGS_PROTOCOL_VERSION
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub velocity_z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub flags: TeleportFlags,
+}
+
+// Before 1.21.2:
+// Packet ID             varint
+// X, Y, Z               double
+// Yaw, Pitch            float
+// Flags                 byte (see TeleportFlags)
+// Teleport ID           varint
+//
+// From 1.21.2:
+// Packet ID             varint
+// Teleport ID           varint
+// X, Y, Z               double
+// Velocity X, Y, Z      double
+// Yaw, Pitch            float
+// Flags                 int (see TeleportFlags)
+pub fn write_synchronize_player_position_packet(
+    teleport: &PlayerTeleport,
+    protocol_version: i32,
+) -> BytesMut {
+    let mut packet = BytesMut::new();
+    let with_velocity = protocol_version >= TELEPORT_FLAGS_PROTOCOL_VERSION;
+
+    let packet_id = if with_velocity {
+        TELEPORT_FLAGS_SYNCHRONIZE_PLAYER_POSITION_PACKET_ID
+    } else {
+        SYNCHRONIZE_PLAYER_POSITION_PACKET_ID
+    };
+    PacketReader::write_varint(&mut packet, packet_id);
+    if with_velocity {
+        PacketReader::write_varint(&mut packet, teleport.teleport_id);
+    }
+    packet.put_f64(teleport.x);
+    packet.put_f64(teleport.y);
+    packet.put_f64(teleport.z);
+    if with_velocity {
+        packet.put_f64(teleport.velocity_x);
+        packet.put_f64(teleport.velocity_y);
+        packet.put_f64(teleport.velocity_z);
+    }
+    packet.put_f32(teleport.yaw);
+    packet.put_f32(teleport.pitch);
+    teleport.flags.write(&mut packet, protocol_version);
+    if !with_velocity {
+        PacketReader::write_varint(&mut packet, teleport.teleport_id);
+    }
+
+    packet
+}
+
+// Packet ID             varint
+// X                     double
+// Z                     double
+// Old Diameter          double
+// New Diameter          double
+// Speed                 varlong (milliseconds)
+// Portal Boundary       varint
+// Warning Blocks        varint
+// Warning Time          varint (seconds)
+pub fn write_initialize_world_border_packet(border: &WorldBorder) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, INITIALIZE_WORLD_BORDER_PACKET_ID);
+    packet.put_f64(border.center_x);
+    packet.put_f64(border.center_z);
+    packet.put_f64(border.old_diameter);
+    packet.put_f64(border.new_diameter);
+    PacketReader::write_varlong(&mut packet, border.speed);
+    PacketReader::write_varint(&mut packet, border.portal_teleport_boundary);
+    PacketReader::write_varint(&mut packet, border.warning_blocks);
+    PacketReader::write_varint(&mut packet, border.warning_time);
+
+    packet
+}
+
+// Packet ID             varint
+// Keep Alive ID         long
+pub fn write_keep_alive_packet(id: i64) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, KEEP_ALIVE_PACKET_ID);
+    packet.put_i64(id);
+
+    packet
+}
+
+/// Signed chat session of a player, other clients need it to verify their messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatSession {
+    pub session_id: Uuid,
+    /// Milliseconds since the epoch
+    pub expires_at: i64,
+    pub public_key: Vec<u8>,
+    /// Mojang's signature of the public key
+    pub key_signature: Vec<u8>,
+}
+
+/// One player in a Player Info Update, only the fields of the packet's
+/// actions are sent
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerInfoEntry {
+    pub uuid: Uuid,
+    pub username: String,
+    pub properties: Vec<ProfileProperty>,
+    pub chat_session: Option<ChatSession>,
+    pub game_mode: GameMode,
+    /// Shown in the tab list
+    pub listed: bool,
+    pub latency_ms: i32,
+    /// Replaces the username in the tab list
+    pub display_name: Option<Component>,
+}
+
+impl From<&PlayerState> for PlayerInfoEntry {
+    fn from(player: &PlayerState) -> Self {
+        Self {
+            uuid: player.uuid,
+            username: player.username.clone(),
+            properties: player.properties.clone(),
+            chat_session: None,
+            game_mode: player.game_mode,
+            listed: true,
+            latency_ms: player.latency_ms,
+            display_name: None,
+        }
+    }
+}
+
+/// Adds players to the tab list or updates them, every player gets the
+/// same set of actions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerInfoUpdate {
+    actions: u8,
+    players: Vec<PlayerInfoEntry>,
+}
+
+impl PlayerInfoUpdate {
+    /// No actions and no players
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn actions(&self) -> u8 {
+        self.actions
+    }
+
+    fn with(mut self, action: u8) -> Self {
+        self.actions |= action;
+        self
+    }
+
+    pub fn add_player(self) -> Self {
+        self.with(ADD_PLAYER_ACTION)
+    }
+
+    pub fn initialize_chat(self) -> Self {
+        self.with(INITIALIZE_CHAT_ACTION)
+    }
+
+    pub fn update_game_mode(self) -> Self {
+        self.with(UPDATE_GAME_MODE_ACTION)
+    }
+
+    pub fn update_listed(self) -> Self {
+        self.with(UPDATE_LISTED_ACTION)
+    }
+
+    pub fn update_latency(self) -> Self {
+        self.with(UPDATE_LATENCY_ACTION)
+    }
+
+    pub fn update_display_name(self) -> Self {
+        self.with(UPDATE_DISPLAY_NAME_ACTION)
+    }
+
+    pub fn player(mut self, player: PlayerInfoEntry) -> Self {
+        self.players.push(player);
+        self
+    }
+}
+
+// Packet ID             varint
+// Actions               byte (see the `*_ACTION` bits)
+// Number Of Players     varint
+// Players               array of
+//   UUID                uuid
+//   Player Actions      the fields of each set action, lowest bit first
+//
+// Add Player            name string (16), then an array of properties:
+//                       name string, value string, optional signature string
+// Initialize Chat       optional chat session: session id uuid, key expiry
+//                       long, public key and key signature byte arrays
+// Update Game Mode      varint
+// Update Listed         boolean
+// Update Latency        varint (milliseconds)
+// Update Display Name   optional text component
+pub fn write_player_info_update_packet(update: &PlayerInfoUpdate) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, PLAYER_INFO_UPDATE_PACKET_ID);
+    packet.put_u8(update.actions);
+    PacketReader::write_varint(&mut packet, update.players.len() as i32);
+    for player in &update.players {
+        packet.put_slice(player.uuid.as_bytes());
+
+        if update.actions & ADD_PLAYER_ACTION != 0 {
+            PacketReader::write_string(&mut packet, &player.username);
+            PacketReader::write_varint(&mut packet, player.properties.len() as i32);
+            for property in &player.properties {
+                PacketReader::write_string(&mut packet, &property.name);
+                PacketReader::write_string(&mut packet, &property.value);
+                packet.put_u8(property.signature.is_some() as u8);
+                if let Some(signature) = &property.signature {
+                    PacketReader::write_string(&mut packet, signature);
+                }
+            }
+        }
+        if update.actions & INITIALIZE_CHAT_ACTION != 0 {
+            packet.put_u8(player.chat_session.is_some() as u8);
+            if let Some(session) = &player.chat_session {
+                packet.put_slice(session.session_id.as_bytes());
+                packet.put_i64(session.expires_at);
+                PacketReader::write_byte_array(&mut packet, &session.public_key);
+                PacketReader::write_byte_array(&mut packet, &session.key_signature);
+            }
+        }
+        if update.actions & UPDATE_GAME_MODE_ACTION != 0 {
+            PacketReader::write_varint(&mut packet, player.game_mode.id() as i32);
+        }
+        if update.actions & UPDATE_LISTED_ACTION != 0 {
+            packet.put_u8(player.listed as u8);
+        }
+        if update.actions & UPDATE_LATENCY_ACTION != 0 {
+            PacketReader::write_varint(&mut packet, player.latency_ms);
+        }
+        if update.actions & UPDATE_DISPLAY_NAME_ACTION != 0 {
+            packet.put_u8(player.display_name.is_some() as u8);
+            if let Some(display_name) = &player.display_name {
+                display_name.write(&mut packet);
+            }
+        }
+    }
+
+    packet
+}
+
+/// Player Info Update with only the latency of each player
+pub fn write_player_info_latency_packet(players: &[(Uuid, i32)]) -> BytesMut {
+    let update = players.iter().fold(
+        PlayerInfoUpdate::new().update_latency(),
+        |update, &(uuid, latency_ms)| {
+            update.player(PlayerInfoEntry {
+                uuid,
+                latency_ms,
+                ..Default::default()
+            })
+        },
+    );
+    write_player_info_update_packet(&update)
+}
+
+// Packet ID             varint
+// Entity ID             varint (the vehicle)
+// Passenger Count       varint
+// Passengers            varint array of entity ids
+pub fn write_set_passengers_packet(vehicle_id: i32, passengers: &[i32]) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_PASSENGERS_PACKET_ID);
+    PacketReader::write_varint(&mut packet, vehicle_id);
+    PacketReader::write_varint(&mut packet, passengers.len() as i32);
+    for &passenger in passengers {
+        PacketReader::write_varint(&mut packet, passenger);
+    }
+
+    packet
+}
+
+// Packet ID             varint
+// Count                 varint
+// Entity IDs            varint array
+pub fn write_remove_entities_packet(entity_ids: &[i32]) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, REMOVE_ENTITIES_PACKET_ID);
+    PacketReader::write_varint(&mut packet, entity_ids.len() as i32);
+    for &entity_id in entity_ids {
+        PacketReader::write_varint(&mut packet, entity_id);
+    }
+
+    packet
+}
+
+// Packet ID             varint
+// Action Bar Text       text component (NBT)
+pub fn write_set_action_bar_text_packet(text: &Component) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_ACTION_BAR_TEXT_PACKET_ID);
+    text.write(&mut packet);
+
+    packet
+}
+
+// Packet ID             varint
+// Title Text            text component (NBT)
+pub fn write_set_title_text_packet(title: &Component) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_TITLE_TEXT_PACKET_ID);
+    title.write(&mut packet);
+
+    packet
+}
+
+// Packet ID             varint
+// View Distance         varint (chunks)
+pub fn write_set_render_distance_packet(distance: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_RENDER_DISTANCE_PACKET_ID);
+    PacketReader::write_varint(&mut packet, distance);
+
+    packet
+}
+
+// Packet ID             varint
+// Simulation Distance   varint (chunks)
+pub fn write_set_simulation_distance_packet(distance: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_SIMULATION_DISTANCE_PACKET_ID);
+    PacketReader::write_varint(&mut packet, distance);
+
+    packet
+}
+
+// Packet ID             varint
+// Subtitle Text         text component (NBT)
+pub fn write_set_subtitle_text_packet(subtitle: &Component) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_SUBTITLE_TEXT_PACKET_ID);
+    subtitle.write(&mut packet);
+
+    packet
+}
+
+// Packet ID             varint
+// Fade In               int (ticks)
+// Stay                  int (ticks)
+// Fade Out              int (ticks)
+pub fn write_set_title_animation_times_packet(fade_in: i32, stay: i32, fade_out: i32) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SET_TITLE_ANIMATION_TIMES_PACKET_ID);
+    packet.put_i32(fade_in);
+    packet.put_i32(stay);
+    packet.put_i32(fade_out);
+
+    packet
+}
+
+// Packet ID             varint
+pub fn write_start_configuration_packet() -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, START_CONFIGURATION_PACKET_ID);
+
+    packet
+}
+
+/// Fields of an unsigned Player Chat Message, shown with the decoration of
+/// a chat type rather than as is like system chat
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerChatMessage {
+    pub sender: Uuid,
+    /// Messages the sender sent before this one, counting from 0
+    pub index: i32,
+    pub message: String,
+    /// Milliseconds since the epoch
+    pub timestamp: i64,
+    pub salt: i64,
+    /// From `RegistryManager::chat_type_id`
+    pub chat_type: i32,
+    pub sender_name: Component,
+    /// The receiver of a private message, shown by chat types like msg_command_outgoing
+    pub target_name: Option<Component>,
+}
+
+// Packet ID             varint
+// Sender                uuid
+// Index                 varint
+// Has Signature         boolean (always false, the message is unsigned)
+// Message               string (256)
+// Timestamp             long
+// Salt                  long
+// Previous Messages     varint count (always 0)
+// Unsigned Content      optional text component (NBT)
+// Filter Type           varint (0 shows the message unfiltered)
+// Chat Type             varint (registry id + 1, 0 would be an inline chat type)
+// Sender Name           text component (NBT)
+// Target Name           optional text component (NBT)
+pub fn write_player_chat_message_packet(chat: &PlayerChatMessage) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, PLAYER_CHAT_MESSAGE_PACKET_ID);
+    packet.put_slice(chat.sender.as_bytes());
+    PacketReader::write_varint(&mut packet, chat.index);
+    packet.put_u8(0);
+    PacketReader::write_string(&mut packet, &chat.message);
+    packet.put_i64(chat.timestamp);
+    packet.put_i64(chat.salt);
+    PacketReader::write_varint(&mut packet, 0);
+    packet.put_u8(0);
+    PacketReader::write_varint(&mut packet, 0);
+    PacketReader::write_varint(&mut packet, chat.chat_type + 1);
+    chat.sender_name.write(&mut packet);
+    packet.put_u8(chat.target_name.is_some() as u8);
+    if let Some(target_name) = &chat.target_name {
+        target_name.write(&mut packet);
+    }
+
+    packet
+}
+
+// Packet ID             varint
+// Content               text component (NBT)
+// Overlay               boolean (shown above the hotbar instead of in chat)
+pub fn write_system_chat_packet(content: &Component, overlay: bool) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SYSTEM_CHAT_PACKET_ID);
+    content.write(&mut packet);
+    packet.put_u8(overlay as u8);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_block_destroy_stage_packet() {
+        let packet = write_set_block_destroy_stage_packet(5, Position::new(1, 2, 3), 4);
+
+        // 0x06                          Packet ID             varint
+        // 0x05                          Entity ID             varint
+        // x << 38 | z << 12 | y         Location              position
+        // 0x04                          Destroy Stage         unsigned byte
+        let mut expected = b"\x06\x05".to_vec();
+        expected.extend_from_slice(&((1i64 << 38) | (3 << 12) | 2).to_be_bytes());
+        expected.push(4);
+        assert_eq!(&packet[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_rain_begins_raining() {
+        let packets = write_weather_packets(&Weather::rain());
+
+        // 0x22                          Packet ID             varint
+        // 0x02                          Event                 unsigned byte
+        // 0.0                           Value                 float
+        assert_eq!(&packets[0][..], b"\x22\x02\x00\x00\x00\x00");
+        assert_eq!(
+            &packets[1][..],
+            &write_game_event_packet(RAIN_LEVEL_CHANGE_EVENT, 1.0)[..]
+        );
+        assert_eq!(
+            &packets[2][..],
+            &write_game_event_packet(THUNDER_LEVEL_CHANGE_EVENT, 0.0)[..]
+        );
+
+        let packets = write_weather_packets(&Weather::clear());
+        assert_eq!(packets[0][1], END_RAINING_EVENT);
+    }
+
+    #[test]
+    fn test_player_info_add_player_only() {
+        let uuid = Uuid::from_u128(1);
+        let update = PlayerInfoUpdate::new()
+            .add_player()
+            .player(PlayerInfoEntry {
+                uuid,
+                username: "Steve".to_string(),
+                properties: vec![ProfileProperty {
+                    name: "textures".to_string(),
+                    value: "abc".to_string(),
+                    signature: None,
+                }],
+                // not part of the add player action, so not sent
+                latency_ms: 50,
+                listed: true,
+                ..Default::default()
+            });
+        let packet = write_player_info_update_packet(&update);
+
+        // 0x3E                          Packet ID             varint
+        // 0x01                          Actions               byte
+        // 0x01                          Number Of Players     varint
+        // uuid                          UUID                  uuid
+        // 0x05 "Steve"                  Name                  string
+        // 0x01                          Number Of Properties  varint
+        // 0x08 "textures" 0x03 "abc"    Name, Value           string, string
+        // 0x00                          Is Signed             boolean
+        let mut expected = b"\x3E\x01\x01".to_vec();
+        expected.extend_from_slice(uuid.as_bytes());
+        expected.extend_from_slice(b"\x05Steve\x01\x08textures\x03abc\x00");
+        assert_eq!(&packet[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_player_info_add_latency_and_listed() {
+        let update = PlayerInfoUpdate::new()
+            .update_latency()
+            .add_player()
+            .update_listed();
+        assert_eq!(update.actions(), 0x19);
+
+        let first = Uuid::from_u128(1);
+        let second = Uuid::from_u128(2);
+        let update = update
+            .player(PlayerInfoEntry {
+                uuid: first,
+                username: "a".to_string(),
+                listed: true,
+                latency_ms: 300,
+                ..Default::default()
+            })
+            .player(PlayerInfoEntry {
+                uuid: second,
+                username: "b".to_string(),
+                listed: false,
+                latency_ms: 0,
+                ..Default::default()
+            });
+        let packet = write_player_info_update_packet(&update);
+
+        // fields follow the order of the action bits, whatever order they were set in
+        let mut expected = b"\x3E\x19\x02".to_vec();
+        expected.extend_from_slice(first.as_bytes());
+        expected.extend_from_slice(b"\x01a\x00"); // name, no properties
+        expected.extend_from_slice(b"\x01"); // listed
+        expected.extend_from_slice(b"\xAC\x02"); // latency
+        expected.extend_from_slice(second.as_bytes());
+        expected.extend_from_slice(b"\x01b\x00\x00\x00");
+        assert_eq!(&packet[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_set_passengers_packet() {
+        let packet = write_set_passengers_packet(300, &[2, 3]);
+
+        // 0x5F                          Packet ID             varint
+        // 0xAC 0x02                     Entity ID             varint
+        // 0x02                          Passenger Count       varint
+        // 0x02 0x03                     Passengers            varint array
+        assert_eq!(&packet[..], b"\x5F\xAC\x02\x02\x02\x03");
+
+        // no passengers makes everyone get off
+        assert_eq!(&write_set_passengers_packet(1, &[])[..], b"\x5F\x01\x00");
+    }
+
+    #[test]
+    fn test_position_delta() {
+        assert_eq!(position_delta(1.0, 1.5), Some(2048));
+        assert_eq!(position_delta(1.5, 1.0), Some(-2048));
+        assert_eq!(position_delta(0.0, 7.99), Some(32727));
+        assert_eq!(position_delta(0.0, 8.0), None);
+        assert_eq!(position_delta(0.0, -8.0), Some(i16::MIN));
+
+        // small moves don't drift away from the rounded positions
+        let (mut position, mut sent) = (0.0, 0i32);
+        for _ in 0..1000 {
+            let next = position + 0.0001;
+            sent += position_delta(position, next).unwrap() as i32;
+            position = next;
+        }
+        assert_eq!(sent, (position * 4096.0f64).round() as i32);
+    }
+
+    #[test]
+    fn test_remove_entities_packet() {
+        let packet = write_remove_entities_packet(&[1, 300, 7]);
+
+        // 0x42                          Packet ID             varint
+        // 0x03                          Count                 varint
+        // 0x01 0xAC 0x02 0x07           Entity IDs            varint array
+        assert_eq!(&packet[..], b"\x42\x03\x01\xAC\x02\x07");
+    }
+
+    #[test]
+    fn test_player_chat_message_packet() {
+        let packet = write_player_chat_message_packet(&PlayerChatMessage {
+            sender: Uuid::from_u128(1),
+            index: 3,
+            message: "hi".to_string(),
+            timestamp: 1000,
+            salt: -1,
+            chat_type: 0,
+            sender_name: Component::text("hi"),
+            target_name: None,
+        });
+
+        let mut expected = b"\x39".to_vec();
+        expected.extend_from_slice(Uuid::from_u128(1).as_bytes());
+        // 0x03                          Index                 varint
+        // 0x00                          Has Signature         boolean
+        // 0x02 "hi"                     Message               string
+        expected.extend_from_slice(b"\x03\x00\x02hi");
+        expected.extend_from_slice(&1000i64.to_be_bytes());
+        expected.extend_from_slice(&(-1i64).to_be_bytes());
+        // 0x00                          Previous Messages     varint
+        // 0x00                          Has Unsigned Content  boolean
+        // 0x00                          Filter Type           varint
+        // 0x01                          Chat Type             varint
+        // 0x0A ... 0x00                 Sender Name           compound tag
+        // 0x00                          Has Target Name       boolean
+        expected.extend_from_slice(b"\x00\x00\x00\x01");
+        expected.extend_from_slice(b"\x0A\x08\x00\x04text\x00\x02hi\x00");
+        expected.push(0x00);
+        assert_eq!(&packet[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_set_action_bar_text_packet() {
+        let packet = write_set_action_bar_text_packet(&Component::text("hi"));
+
+        // 0x4C                          Packet ID             varint
+        // 0x0A                          Action Bar Text       compound tag
+        // 0x08 0x00 0x04 "text"           string tag named "text"
+        // 0x00 0x02 "hi"                  its value
+        // 0x00                            end of compound
+        assert_eq!(&packet[..], b"\x4C\x0A\x08\x00\x04text\x00\x02hi\x00");
+    }
+
+    #[test]
+    fn test_set_health_packet() {
+        let packet = write_set_health_packet(20.0, 20, 5.0);
+
+        // 0x5D                          Packet ID             varint
+        // 0x41 0xA0 0x00 0x00           Health                float
+        // 0x14                          Food                  varint
+        // 0x40 0xA0 0x00 0x00           Food Saturation       float
+        assert_eq!(&packet[..], b"\x5D\x41\xA0\x00\x00\x14\x40\xA0\x00\x00");
+    }
+
+    #[test]
+    fn test_set_experience_packet() {
+        let packet = write_set_experience_packet(0.5, 3, 300);
+
+        // 0x5C                          Packet ID             varint
+        // 0x3F 0x00 0x00 0x00           Experience Bar        float
+        // 0x03                          Level                 varint
+        // 0xAC 0x02                     Total Experience      varint
+        assert_eq!(&packet[..], b"\x5C\x3F\x00\x00\x00\x03\xAC\x02");
+    }
+
+    #[test]
+    fn test_set_render_distance_packet() {
+        let packet = write_set_render_distance_packet(32);
+
+        // 0x55                          Packet ID             varint
+        // 0x20                          View Distance         varint
+        assert_eq!(&packet[..], b"\x55\x20");
+    }
+
+    #[test]
+    fn test_set_simulation_distance_packet() {
+        let packet = write_set_simulation_distance_packet(12);
+
+        // 0x62                          Packet ID             varint
+        // 0x0C                          Simulation Distance   varint
+        assert_eq!(&packet[..], b"\x62\x0C");
+    }
+
+    #[test]
+    fn test_initialize_world_border_packet() {
+        let border = WorldBorder {
+            center_x: 8.5,
+            center_z: -16.0,
+            old_diameter: 100.0,
+            new_diameter: 200.0,
+            speed: 60_000,
+            portal_teleport_boundary: 29_999_984,
+            warning_blocks: 5,
+            warning_time: 15,
+        };
+        let packet = write_initialize_world_border_packet(&border);
+
+        let mut buf = &packet[..];
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x25);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 8.5); // x
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), -16.0); // z
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 100.0); // old diameter
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 200.0); // new diameter
+        assert_eq!(PacketReader::read_varlong(&mut buf).unwrap(), 60_000); // speed
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 29_999_984); // portal boundary
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 5); // warning blocks
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 15); // warning time
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_teleport_flag_bits() {
+        assert_eq!(TeleportFlags::new().bits(), 0);
+        assert_eq!(TeleportFlags::new().relative_x().bits(), 0x01);
+        assert_eq!(TeleportFlags::new().relative_y().bits(), 0x02);
+        assert_eq!(TeleportFlags::new().relative_z().bits(), 0x04);
+        assert_eq!(TeleportFlags::new().relative_yaw().bits(), 0x08);
+        assert_eq!(TeleportFlags::new().relative_pitch().bits(), 0x10);
+        assert_eq!(TeleportFlags::new().relative_velocity().bits(), 0xE0);
+        assert_eq!(TeleportFlags::new().rotate_velocity().bits(), 0x100);
+
+        let flags = TeleportFlags::new().relative_yaw().relative_velocity();
+        let mut old = BytesMut::new();
+        flags.write(&mut old, 767);
+        // velocity bits don't fit the byte older clients expect
+        assert_eq!(&old[..], [0x08]);
+
+        let mut new = BytesMut::new();
+        flags.write(&mut new, TELEPORT_FLAGS_PROTOCOL_VERSION);
+        assert_eq!(&new[..], [0x00, 0x00, 0x00, 0xE8]);
+    }
+
+    #[test]
+    fn test_synchronize_player_position_layouts() {
+        let teleport = PlayerTeleport {
+            teleport_id: 3,
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+            velocity_y: 0.5,
+            yaw: 90.0,
+            pitch: -10.0,
+            flags: TeleportFlags::new().relative_y(),
+            ..Default::default()
+        };
+
+        let packet = write_synchronize_player_position_packet(&teleport, 767);
+        let mut buf = &packet[..];
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x40);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 1.0);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 2.0);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 3.0);
+        assert_eq!(PacketReader::read_float(&mut buf).unwrap(), 90.0);
+        assert_eq!(PacketReader::read_float(&mut buf).unwrap(), -10.0);
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 0x02);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 3);
+        assert!(buf.is_empty());
+
+        let packet =
+            write_synchronize_player_position_packet(&teleport, TELEPORT_FLAGS_PROTOCOL_VERSION);
+        let mut buf = &packet[..];
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x42);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 3);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 1.0);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 2.0);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 3.0);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 0.0);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 0.5);
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 0.0);
+        assert_eq!(PacketReader::read_float(&mut buf).unwrap(), 90.0);
+        assert_eq!(PacketReader::read_float(&mut buf).unwrap(), -10.0);
+        assert_eq!(PacketReader::read_int(&mut buf).unwrap(), 0x02);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_change_difficulty_packet() {
+        let packet = write_change_difficulty_packet(Difficulty::Hard, true);
+
+        // 0x0B                          Packet ID             varint
+        // 0x03                          Difficulty            unsigned byte
+        // 0x01                          Locked                boolean
+        assert_eq!(&packet[..], b"\x0B\x03\x01");
+    }
+
+    #[test]
+    fn test_acknowledge_block_change_packet() {
+        let packet = write_acknowledge_block_change_packet(300);
+
+        // 0x05                          Packet ID             varint
+        // 0xAC 0x02                     Sequence ID           varint
+        assert_eq!(&packet[..], b"\x05\xAC\x02");
+    }
+
+    #[test]
+    fn test_chunk_batch_packets() {
+        // 0x0D                          Packet ID             varint
+        assert_eq!(&write_chunk_batch_start_packet()[..], b"\x0D");
+
+        // 0x0C                          Packet ID             varint
+        // 0x09                          Batch Size            varint
+        assert_eq!(&write_chunk_batch_finished_packet(9)[..], b"\x0C\x09");
+    }
+
+    #[test]
+    fn test_set_cooldown_packet() {
+        let packet = write_set_cooldown_packet(1000, 20);
+
+        // 0x17                          Packet ID             varint
+        // 0xE8 0x07                     Item ID               varint
+        // 0x14                          Cooldown Ticks        varint
+        assert_eq!(&packet[..], b"\x17\xE8\x07\x14");
+    }
+
+    #[test]
+    fn test_spawn_item_entity_packet() {
+        let uuid = Uuid::from_u128(0x0102030405060708090a0b0c0d0e0f10);
+        let packet = write_spawn_entity_packet(&SpawnEntity {
+            entity_id: 7,
+            uuid,
+            entity_type: ITEM_ENTITY_TYPE,
+            x: 1.5,
+            y: 64.0,
+            z: -2.5,
+            pitch: 0.0,
+            yaw: 90.0,
+            head_yaw: 180.0,
+            data: 1,
+            velocity_x: 0.0,
+            velocity_y: 0.2,
+            velocity_z: -0.1,
+        });
+
+        let mut buf = &packet[..];
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x01);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 7); // entity id
+        assert_eq!(PacketReader::read_uuid(&mut buf).unwrap(), uuid);
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 58); // item
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 1.5); // x
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), 64.0); // y
+        assert_eq!(PacketReader::read_double(&mut buf).unwrap(), -2.5); // z
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 0); // pitch
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 64); // yaw
+        assert_eq!(PacketReader::read_unsigned_byte(&mut buf).unwrap(), 128); // head yaw
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 1); // data
+        assert_eq!(PacketReader::read_short(&mut buf).unwrap(), 0); // velocity x
+        assert_eq!(PacketReader::read_short(&mut buf).unwrap(), 1600); // velocity y
+        assert_eq!(PacketReader::read_short(&mut buf).unwrap(), -800); // velocity z
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_entity_animation_packet() {
+        let packet = write_entity_animation_packet(5, SWING_MAIN_ARM_ANIMATION);
+
+        // 0x03                          Packet ID             varint
+        // 0x05                          Entity ID             varint
+        // 0x00                          Animation             unsigned byte
+        assert_eq!(&packet[..], b"\x03\x05\x00");
+    }
+
+    #[test]
+    fn test_velocity_fixed_point() {
+        assert_eq!(velocity_to_fixed_point(1.0), 8000);
+        assert_eq!(velocity_to_fixed_point(-0.5), -4000);
+        // anything past ~4.1 blocks/tick is clamped to the short range
+        assert_eq!(velocity_to_fixed_point(10.0), i16::MAX);
+        assert_eq!(velocity_to_fixed_point(-10.0), i16::MIN);
+
+        let packet = write_entity_velocity_packet(1, 1.0, 0.0, -1.0);
+        assert_eq!(&packet[..], b"\x5A\x01\x1F\x40\x00\x00\xE0\xC0");
+    }
+
+    #[test]
+    fn test_player_abilities_packet() {
+        let packet = write_player_abilities_packet(
+            ABILITY_INVULNERABLE | ABILITY_ALLOW_FLYING | ABILITY_INSTANT_BREAK,
+            0.05,
+            0.1,
+        );
+
+        // 0x38                          Packet ID             varint
+        // 0x0D                          Flags                 byte
+        // 0x3D 0x4C 0xCC 0xCD           Flying Speed          float
+        // 0x3D 0xCC 0xCC 0xCD           Field of View         float
+        assert_eq!(&packet[..], b"\x38\x0D\x3D\x4C\xCC\xCD\x3D\xCC\xCC\xCD");
+    }
+}
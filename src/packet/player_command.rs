@@ -0,0 +1,94 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::buf::PacketBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerCommandAction {
+    StartSneaking,
+    StopSneaking,
+    LeaveBed,
+    StartSprinting,
+    StopSprinting,
+    /// Jumping while riding a horse, the boost goes from 0 to 100
+    StartJumpWithHorse {
+        jump_boost: i32,
+    },
+    StopJumpWithHorse,
+    OpenVehicleInventory,
+    StartFlyingWithElytra,
+}
+
+/// A player changing its movement state, like starting to sneak or sprint
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerCommand {
+    pub entity_id: i32,
+    pub action: PlayerCommandAction,
+}
+
+impl PlayerCommand {
+    // Entity ID             varint
+    // Action ID             varint
+    // Jump Boost            varint (only used when jumping with a horse)
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        let entity_id = buf.read_varint()?;
+        let action_id = buf.read_varint()?;
+        let jump_boost = buf.read_varint()?;
+
+        let action = match action_id {
+            0 => PlayerCommandAction::StartSneaking,
+            1 => PlayerCommandAction::StopSneaking,
+            2 => PlayerCommandAction::LeaveBed,
+            3 => PlayerCommandAction::StartSprinting,
+            4 => PlayerCommandAction::StopSprinting,
+            5 => PlayerCommandAction::StartJumpWithHorse { jump_boost },
+            6 => PlayerCommandAction::StopJumpWithHorse,
+            7 => PlayerCommandAction::OpenVehicleInventory,
+            8 => PlayerCommandAction::StartFlyingWithElytra,
+            _ => {
+                return Err(MinecraftError::Protocol(format!(
+                    "Invalid player command action: {action_id}"
+                )))
+            }
+        };
+
+        Ok(Self { entity_id, action })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_start_sneaking() {
+        let packet = [0x05, 0x00, 0x00];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            PlayerCommand::decode(&mut buf).unwrap(),
+            PlayerCommand {
+                entity_id: 5,
+                action: PlayerCommandAction::StartSneaking,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_horse_jump_keeps_boost() {
+        let packet = [0x05, 0x05, 0x40];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            PlayerCommand::decode(&mut buf).unwrap().action,
+            PlayerCommandAction::StartJumpWithHorse { jump_boost: 64 }
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_action() {
+        let packet = [0x05, 0x09, 0x00];
+        let mut buf = PacketBuf::new(&packet);
+
+        assert!(PlayerCommand::decode(&mut buf).is_err());
+    }
+}
@@ -2,13 +2,20 @@
 
 use crate::error::MinecraftError;
 use crate::error::Result;
+use crate::world::Position;
 use bytes::BufMut;
 use bytes::BytesMut;
 use tracing::debug;
+use uuid::Uuid;
 
 const SEGMENT_BITS: u8 = 0x7F;
 const CONTINUE_BIT: u8 = 0x80;
 
+/// Longest chat message a client may send, in UTF-16 code units
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+/// Longest command a client may send, in UTF-16 code units
+pub const MAX_COMMAND_LENGTH: usize = 32767;
+
 pub struct PacketReader;
 
 impl PacketReader {
@@ -37,6 +44,38 @@ impl PacketReader {
         }
     }
 
+    /// Reads a varint and also returns how many bytes it took, which can be
+    /// more than `get_varint_size` of the value when the encoding isn't minimal
+    pub fn read_varint_counted(buffer: &mut &[u8]) -> Result<(i32, usize)> {
+        let before = buffer.len();
+        let value = Self::read_varint(buffer)?;
+        Ok((value, before - buffer.len()))
+    }
+
+    pub fn read_varlong(buffer: &mut &[u8]) -> Result<i64> {
+        let mut result = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = match buffer.first() {
+                Some(&b) => {
+                    *buffer = &buffer[1..];
+                    b
+                }
+                None => return Err(MinecraftError::VarInt("buffer underflow".to_string())),
+            };
+
+            result |= ((byte & SEGMENT_BITS) as i64) << shift;
+            if byte & CONTINUE_BIT == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+            if shift >= 64 {
+                return Err(MinecraftError::VarInt("varlong too long".to_string()));
+            }
+        }
+    }
+
     pub fn read_string(buf: &mut &[u8]) -> Result<String> {
         let length = Self::read_varint(buf)?;
 
@@ -58,6 +97,28 @@ impl PacketReader {
         Ok(string)
     }
 
+    /// Reads a string the protocol limits to `max_code_units` UTF-16 code units
+    pub fn read_string_capped(buf: &mut &[u8], max_code_units: usize) -> Result<String> {
+        // a code unit never takes more than 3 bytes in UTF-8, so bigger
+        // strings can be rejected before decoding them
+        let length = Self::read_varint(&mut &buf[..])?;
+        if length as usize > max_code_units * 3 {
+            return Err(MinecraftError::Protocol(format!(
+                "String of {length} bytes exceeds the limit of {max_code_units} characters"
+            )));
+        }
+
+        let string = Self::read_string(buf)?;
+        let code_units = string.encode_utf16().count();
+        if code_units > max_code_units {
+            return Err(MinecraftError::Protocol(format!(
+                "String of {code_units} characters exceeds the limit of {max_code_units}"
+            )));
+        }
+
+        Ok(string)
+    }
+
     pub fn read_byte(buf: &mut &[u8]) -> Result<i8> {
         if buf.is_empty() {
             return Err(MinecraftError::BufferUnderrun(
@@ -104,6 +165,32 @@ impl PacketReader {
         Ok(result)
     }
 
+    pub fn read_short(buf: &mut &[u8]) -> Result<i16> {
+        if buf.len() < 2 {
+            return Err(MinecraftError::BufferUnderrun(
+                "Not enough bytes for short".into(),
+            ));
+        }
+
+        let result = i16::from_be_bytes([buf[0], buf[1]]);
+        *buf = &buf[2..];
+
+        Ok(result)
+    }
+
+    pub fn read_int(buf: &mut &[u8]) -> Result<i32> {
+        if buf.len() < 4 {
+            return Err(MinecraftError::BufferUnderrun(
+                "Not enough bytes for int".into(),
+            ));
+        }
+
+        let result = i32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        *buf = &buf[4..];
+
+        Ok(result)
+    }
+
     pub fn read_long(buf: &mut &[u8]) -> Result<i64> {
         if buf.len() < 8 {
             return Err(MinecraftError::BufferUnderrun(
@@ -120,16 +207,69 @@ impl PacketReader {
         Ok(result)
     }
 
+    pub fn read_float(buf: &mut &[u8]) -> Result<f32> {
+        if buf.len() < 4 {
+            return Err(MinecraftError::BufferUnderrun(
+                "Not enough bytes for float".into(),
+            ));
+        }
+
+        let value = f32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        *buf = &buf[4..];
+        Ok(value)
+    }
+
+    pub fn read_double(buf: &mut &[u8]) -> Result<f64> {
+        let bits = Self::read_long(buf)?;
+        Ok(f64::from_bits(bits as u64))
+    }
+
+    /// Reads an int holding a fixed-point number with `fraction_bits` bits after
+    /// the point, e.g. 5 for the old 1/32 block positions or 12 for 1/4096
+    pub fn read_fixed_point_i32(buf: &mut &[u8], fraction_bits: u32) -> Result<f64> {
+        let raw = Self::read_int(buf)?;
+        Ok(raw as f64 / (1u64 << fraction_bits) as f64)
+    }
+
+    pub fn read_uuid(buf: &mut &[u8]) -> Result<Uuid> {
+        if buf.len() < 16 {
+            return Err(MinecraftError::BufferUnderrun(
+                "Not enough bytes for UUID".into(),
+            ));
+        }
+
+        let (bytes, rest) = buf.split_at(16);
+        *buf = rest;
+        Ok(Uuid::from_slice(bytes).unwrap())
+    }
+
+    /// Reads an angle in steps of 1/256 of a full turn, returned in degrees
+    pub fn read_angle(buf: &mut &[u8]) -> Result<f32> {
+        Ok(Self::read_unsigned_byte(buf)? as f32 * 360.0 / 256.0)
+    }
+
+    pub fn read_position(buf: &mut &[u8]) -> Result<Position> {
+        let packed = Self::read_long(buf)?;
+        Ok(Position::from_packed(packed))
+    }
+
     pub fn read_plugin_message(buf: &mut &[u8]) -> Result<(String, Vec<u8>)> {
         let channel_id = Self::read_string(buf)?;
 
         //  (byte array)
-        let data = buf.to_vec();
-        *buf = &[]; // clear the buffer
+        let data = Self::read_remaining(buf).to_vec();
 
         Ok((channel_id, data))
     }
 
+    /// Returns and consumes everything left in `buf`
+    ///
+    /// Only use this on a slice that is already bounded to a single packet,
+    /// on a raw connection buffer it would swallow the packets that follow.
+    pub fn read_remaining<'a>(buf: &mut &'a [u8]) -> &'a [u8] {
+        std::mem::take(buf)
+    }
+
     pub fn read_identifier(buf: &mut &[u8]) -> Result<(String, String)> {
         let full_id = Self::read_string(buf)?;
         let parts: Vec<&str> = full_id.split(':').collect();
@@ -139,6 +279,39 @@ impl PacketReader {
         Ok((parts[0].to_string(), parts[1].to_string()))
     }
 
+    /// Reads a varint length followed by that many bytes
+    pub fn read_byte_array(buf: &mut &[u8]) -> Result<Vec<u8>> {
+        let length = Self::read_varint(buf)?;
+        if length < 0 {
+            return Err(MinecraftError::Protocol(format!(
+                "Negative byte array length: {length}"
+            )));
+        }
+
+        let length = length as usize;
+        if buf.len() < length {
+            return Err(MinecraftError::BufferUnderrun(
+                "Byte array length exceeds buffer size".into(),
+            ));
+        }
+
+        let data = buf[..length].to_vec();
+        *buf = &buf[length..];
+        Ok(data)
+    }
+
+    /// Reads a varint count followed by that many identifiers
+    pub fn read_identifier_array(buf: &mut &[u8]) -> Result<Vec<(String, String)>> {
+        let count = Self::read_varint(buf)?;
+        if count < 0 {
+            return Err(MinecraftError::Protocol(format!(
+                "Negative identifier array length: {count}"
+            )));
+        }
+
+        (0..count).map(|_| Self::read_identifier(buf)).collect()
+    }
+
     /// Helper function to get size of varint
     pub fn get_varint_size(value: i32) -> usize {
         let mut size = 0;
@@ -173,13 +346,200 @@ impl PacketReader {
         }
     }
 
+    pub fn write_varlong(buf: &mut BytesMut, value: i64) {
+        // shifted as unsigned so negative values end after ten bytes
+        let mut value = value as u64;
+        loop {
+            let byte = value as u8 & SEGMENT_BITS;
+            value >>= 7;
+
+            if value == 0 {
+                buf.put_u8(byte);
+                break;
+            }
+            buf.put_u8(byte | CONTINUE_BIT);
+        }
+    }
+
     pub fn write_identifier(buf: &mut BytesMut, namespace: &str, path: &str) {
         let identifier = format!("{}:{}", namespace, path);
         Self::write_string(buf, &identifier);
     }
 
+    pub fn write_identifier_array(buf: &mut BytesMut, identifiers: &[(String, String)]) {
+        Self::write_varint(buf, identifiers.len() as i32);
+        for (namespace, path) in identifiers {
+            Self::write_identifier(buf, namespace, path);
+        }
+    }
+
+    /// `value` in steps of `1 / 2^fraction_bits`, rounded to the nearest step
+    /// but not yet narrowed to the integer type it's sent as
+    pub fn fixed_point_steps(value: f64, fraction_bits: u32) -> f64 {
+        (value * (1u64 << fraction_bits) as f64).round()
+    }
+
+    /// Writes `value` as a fixed-point int, rounding to the nearest step and
+    /// saturating at the ends of the i32 range
+    pub fn write_fixed_point_i32(buf: &mut BytesMut, value: f64, fraction_bits: u32) {
+        let raw = Self::fixed_point_steps(value, fraction_bits);
+        buf.put_i32(raw.clamp(i32::MIN as f64, i32::MAX as f64) as i32);
+    }
+
+    pub fn write_byte_array(buf: &mut BytesMut, data: &[u8]) {
+        Self::write_varint(buf, data.len() as i32);
+        buf.put(data);
+    }
+
     pub fn write_string(buf: &mut BytesMut, value: &str) {
         Self::write_varint(buf, value.len() as i32);
         buf.put(value.as_bytes());
     }
+
+    /// Writes an angle in degrees as 1/256 steps of a full turn, wrapping around
+    pub fn write_angle(buf: &mut BytesMut, degrees: f32) {
+        let steps = (degrees * 256.0 / 360.0).round() as i32;
+        buf.put_u8(steps.rem_euclid(256) as u8);
+    }
+
+    pub fn write_position(buf: &mut BytesMut, position: Position) {
+        buf.put_i64(position.to_packed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_point_round_trip() {
+        let mut buf = BytesMut::new();
+        PacketReader::write_fixed_point_i32(&mut buf, 1.5, 5);
+        assert_eq!(&buf[..], &48i32.to_be_bytes());
+        assert_eq!(
+            PacketReader::read_fixed_point_i32(&mut &buf[..], 5).unwrap(),
+            1.5
+        );
+
+        // values between steps come back within half a step
+        for (value, fraction_bits) in [(-12.345, 5), (100.001, 12), (-0.3, 12)] {
+            let mut buf = BytesMut::new();
+            PacketReader::write_fixed_point_i32(&mut buf, value, fraction_bits);
+            let read = PacketReader::read_fixed_point_i32(&mut &buf[..], fraction_bits).unwrap();
+            let step = 1.0 / (1u64 << fraction_bits) as f64;
+            assert!(
+                (read - value).abs() <= step / 2.0,
+                "{value} read back as {read}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_varint_counted() {
+        let mut data = &[0x05, 0xff][..];
+        assert_eq!(
+            PacketReader::read_varint_counted(&mut data).unwrap(),
+            (5, 1)
+        );
+        assert_eq!(data, [0xff]);
+
+        let mut data = &[0x80, 0x80, 0x01][..];
+        assert_eq!(
+            PacketReader::read_varint_counted(&mut data).unwrap(),
+            (16384, 3)
+        );
+        assert!(data.is_empty());
+
+        // a padded encoding of 1 is still three bytes long
+        let mut data = &[0x81, 0x80, 0x00][..];
+        assert_eq!(
+            PacketReader::read_varint_counted(&mut data).unwrap(),
+            (1, 3)
+        );
+    }
+
+    #[test]
+    fn test_varlong_round_trip() {
+        for (value, bytes) in [
+            (0, &b"\x00"[..]),
+            (127, b"\x7f"),
+            (128, b"\x80\x01"),
+            (i64::MAX, b"\xff\xff\xff\xff\xff\xff\xff\xff\x7f"),
+            (-1, b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"),
+        ] {
+            let mut buf = BytesMut::new();
+            PacketReader::write_varlong(&mut buf, value);
+            assert_eq!(&buf[..], bytes);
+
+            let mut data = &buf[..];
+            assert_eq!(PacketReader::read_varlong(&mut data).unwrap(), value);
+            assert!(data.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_identifier_array_round_trip() {
+        let identifiers = vec![
+            ("minecraft".to_string(), "overworld".to_string()),
+            ("minecraft".to_string(), "the_nether".to_string()),
+            ("octavia".to_string(), "lobby".to_string()),
+        ];
+
+        let mut buf = BytesMut::new();
+        PacketReader::write_identifier_array(&mut buf, &identifiers);
+
+        let mut data = &buf[..];
+        assert_eq!(
+            PacketReader::read_identifier_array(&mut data).unwrap(),
+            identifiers
+        );
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_angle_round_trip() {
+        for (degrees, byte) in [(0.0, 0), (90.0, 64), (180.0, 128), (270.0, 192), (360.0, 0)] {
+            let mut buf = BytesMut::new();
+            PacketReader::write_angle(&mut buf, degrees);
+            assert_eq!(&buf[..], &[byte]);
+
+            let read = PacketReader::read_angle(&mut &buf[..]).unwrap();
+            assert!((read - degrees % 360.0).abs() < 360.0 / 256.0);
+        }
+
+        // negative angles wrap around too
+        let mut buf = BytesMut::new();
+        PacketReader::write_angle(&mut buf, -90.0);
+        assert_eq!(&buf[..], &[192]);
+    }
+
+    #[test]
+    fn test_read_remaining_stays_in_bounds() {
+        // the plugin message packet, followed by the start of another packet
+        let buffer = b"\x03a:bdata\x01\x1D";
+
+        let mut packet = &buffer[..8];
+        assert_eq!(PacketReader::read_string(&mut packet).unwrap(), "a:b");
+        assert_eq!(PacketReader::read_remaining(&mut packet), b"data");
+        assert!(packet.is_empty());
+        assert!(PacketReader::read_remaining(&mut packet).is_empty());
+    }
+
+    fn read_capped(value: &str, max_code_units: usize) -> Result<String> {
+        let mut buf = BytesMut::new();
+        PacketReader::write_string(&mut buf, value);
+        PacketReader::read_string_capped(&mut &buf[..], max_code_units)
+    }
+
+    #[test]
+    fn test_read_string_capped_counts_utf16_code_units() {
+        // 'é' is 2 bytes but a single code unit
+        assert_eq!(read_capped("éé", 2).unwrap(), "éé");
+        assert!(read_capped("ééé", 2).is_err());
+
+        // '😀' is 4 bytes and a surrogate pair, so two code units
+        assert_eq!(read_capped("a😀", 3).unwrap(), "a😀");
+        assert!(read_capped("a😀", 2).is_err());
+        assert!(read_capped("😀😀", 3).is_err());
+    }
 }
@@ -0,0 +1,78 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::buf::PacketBuf;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResourcePackResult {
+    SuccessfullyDownloaded,
+    Declined,
+    FailedDownload,
+    Accepted,
+    Downloaded,
+    InvalidUrl,
+    FailedToReload,
+    Discarded,
+}
+
+/// The client reporting what happened to a resource pack the server pushed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourcePackResponse {
+    pub uuid: Uuid,
+    pub result: ResourcePackResult,
+}
+
+impl ResourcePackResponse {
+    // UUID                  uuid (of the pack)
+    // Result                varint
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        let uuid = buf.read_uuid()?;
+        let result = match buf.read_varint()? {
+            0 => ResourcePackResult::SuccessfullyDownloaded,
+            1 => ResourcePackResult::Declined,
+            2 => ResourcePackResult::FailedDownload,
+            3 => ResourcePackResult::Accepted,
+            4 => ResourcePackResult::Downloaded,
+            5 => ResourcePackResult::InvalidUrl,
+            6 => ResourcePackResult::FailedToReload,
+            7 => ResourcePackResult::Discarded,
+            result => {
+                return Err(MinecraftError::Protocol(format!(
+                    "Invalid resource pack result: {result}"
+                )))
+            }
+        };
+
+        Ok(Self { uuid, result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_resource_pack_response() {
+        let uuid = Uuid::from_u128(0x0102030405060708090a0b0c0d0e0f10);
+        let mut packet = uuid.as_bytes().to_vec();
+        packet.push(0x01);
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            ResourcePackResponse::decode(&mut buf).unwrap(),
+            ResourcePackResponse {
+                uuid,
+                result: ResourcePackResult::Declined,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_result() {
+        let mut packet = Uuid::nil().as_bytes().to_vec();
+        packet.push(0x08);
+        let mut buf = PacketBuf::new(&packet);
+
+        assert!(ResourcePackResponse::decode(&mut buf).is_err());
+    }
+}
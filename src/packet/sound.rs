@@ -0,0 +1,210 @@
+use crate::error::{MinecraftError, Result};
+use crate::packet::buf::PacketBuf;
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+
+pub const SOUND_EFFECT_PACKET_ID: i32 = 0x68;
+
+/// A sound to play, either from the sound event registry or by name
+#[derive(Debug, Clone, PartialEq)]
+pub enum SoundEvent {
+    /// Id in the `minecraft:sound_event` registry
+    ById(i32),
+    /// A sound the registry doesn't have, like one from a resource pack
+    Inline {
+        /// Like "minecraft:entity.pig.ambient"
+        id: String,
+        /// Blocks the sound can be heard from, the client scales it with
+        /// the volume when `None`
+        fixed_range: Option<f32>,
+    },
+}
+
+impl SoundEvent {
+    // Sound ID              varint (registry id + 1, 0 for an inline sound)
+    // Sound Name            identifier (inline only)
+    // Has Fixed Range       boolean (inline only)
+    // Fixed Range           optional float (inline only)
+    pub fn read(buf: &mut PacketBuf) -> Result<Self> {
+        let id = buf.read_varint()?;
+        if id > 0 {
+            return Ok(SoundEvent::ById(id - 1));
+        }
+        if id < 0 {
+            return Err(MinecraftError::Protocol(format!("Invalid sound id: {id}")));
+        }
+
+        let name = buf.read_string()?;
+        let fixed_range = match buf.read_boolean()? {
+            true => Some(buf.read_float()?),
+            false => None,
+        };
+        Ok(SoundEvent::Inline {
+            id: name,
+            fixed_range,
+        })
+    }
+
+    pub fn write(&self, buf: &mut BytesMut) {
+        match self {
+            SoundEvent::ById(id) => PacketReader::write_varint(buf, id + 1),
+            SoundEvent::Inline { id, fixed_range } => {
+                PacketReader::write_varint(buf, 0);
+                PacketReader::write_string(buf, id);
+                buf.put_u8(fixed_range.is_some() as u8);
+                if let Some(range) = fixed_range {
+                    buf.put_f32(*range);
+                }
+            }
+        }
+    }
+}
+
+/// Which volume slider in the client's sound options applies to a sound
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SoundCategory {
+    #[default]
+    Master,
+    Music,
+    Record,
+    Weather,
+    Block,
+    Hostile,
+    Neutral,
+    Player,
+    Ambient,
+    Voice,
+}
+
+impl SoundCategory {
+    pub fn id(self) -> i32 {
+        match self {
+            SoundCategory::Master => 0,
+            SoundCategory::Music => 1,
+            SoundCategory::Record => 2,
+            SoundCategory::Weather => 3,
+            SoundCategory::Block => 4,
+            SoundCategory::Hostile => 5,
+            SoundCategory::Neutral => 6,
+            SoundCategory::Player => 7,
+            SoundCategory::Ambient => 8,
+            SoundCategory::Voice => 9,
+        }
+    }
+}
+
+/// Fields of the Sound Effect packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundEffect {
+    pub sound: SoundEvent,
+    pub category: SoundCategory,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// 1.0 is full volume, louder sounds can be heard from further away
+    pub volume: f32,
+    /// From 0.5 to 2.0, 1.0 plays the sound as is
+    pub pitch: f32,
+    /// Picks between the variants of a sound, the same seed plays the same variant
+    pub seed: i64,
+}
+
+// Packet ID             varint
+// Sound Event           id or inline sound event
+// Sound Category        varint
+// Effect Position X     int (fixed-point, 3 fraction bits)
+// Effect Position Y     int (fixed-point, 3 fraction bits)
+// Effect Position Z     int (fixed-point, 3 fraction bits)
+// Volume                float
+// Pitch                 float
+// Seed                  long
+pub fn write_sound_effect_packet(effect: &SoundEffect) -> BytesMut {
+    let mut packet = BytesMut::new();
+
+    PacketReader::write_varint(&mut packet, SOUND_EFFECT_PACKET_ID);
+    effect.sound.write(&mut packet);
+    PacketReader::write_varint(&mut packet, effect.category.id());
+    PacketReader::write_fixed_point_i32(&mut packet, effect.x, 3);
+    PacketReader::write_fixed_point_i32(&mut packet, effect.y, 3);
+    PacketReader::write_fixed_point_i32(&mut packet, effect.z, 3);
+    packet.put_f32(effect.volume);
+    packet.put_f32(effect.pitch);
+    packet.put_i64(effect.seed);
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sound_event_by_id() {
+        let mut buf = BytesMut::new();
+        SoundEvent::ById(0).write(&mut buf);
+        SoundEvent::ById(200).write(&mut buf);
+
+        // ids are shifted by one, 0 means an inline sound follows
+        assert_eq!(&buf[..], b"\x01\xC9\x01");
+
+        let mut read = PacketBuf::new(&buf);
+        assert_eq!(SoundEvent::read(&mut read).unwrap(), SoundEvent::ById(0));
+        assert_eq!(SoundEvent::read(&mut read).unwrap(), SoundEvent::ById(200));
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn test_sound_event_inline() {
+        let ranged = SoundEvent::Inline {
+            id: "a:b".to_string(),
+            fixed_range: Some(16.0),
+        };
+        let unranged = SoundEvent::Inline {
+            id: "a:b".to_string(),
+            fixed_range: None,
+        };
+        let mut buf = BytesMut::new();
+        ranged.write(&mut buf);
+        unranged.write(&mut buf);
+
+        // 0x00                          Sound ID              varint
+        // 0x03 "a:b"                    Sound Name            identifier
+        // 0x01                          Has Fixed Range       boolean
+        // 16.0                          Fixed Range           float
+        let mut expected = b"\x00\x03a:b\x01".to_vec();
+        expected.extend_from_slice(&16.0f32.to_be_bytes());
+        expected.extend_from_slice(b"\x00\x03a:b\x00");
+        assert_eq!(&buf[..], &expected[..]);
+
+        let mut read = PacketBuf::new(&buf);
+        assert_eq!(SoundEvent::read(&mut read).unwrap(), ranged);
+        assert_eq!(SoundEvent::read(&mut read).unwrap(), unranged);
+        assert!(read.is_empty());
+    }
+
+    #[test]
+    fn test_sound_effect_packet() {
+        let packet = write_sound_effect_packet(&SoundEffect {
+            sound: SoundEvent::ById(5),
+            category: SoundCategory::Player,
+            x: 1.5,
+            y: -2.0,
+            z: 0.125,
+            volume: 1.0,
+            pitch: 0.5,
+            seed: 42,
+        });
+
+        let mut buf = PacketBuf::new(&packet);
+        assert_eq!(buf.read_varint().unwrap(), 0x68);
+        assert_eq!(SoundEvent::read(&mut buf).unwrap(), SoundEvent::ById(5));
+        assert_eq!(buf.read_varint().unwrap(), 7);
+        assert_eq!(buf.read_fixed_point_i32(3).unwrap(), 1.5);
+        assert_eq!(buf.read_fixed_point_i32(3).unwrap(), -2.0);
+        assert_eq!(buf.read_fixed_point_i32(3).unwrap(), 0.125);
+        assert_eq!(buf.read_float().unwrap(), 1.0);
+        assert_eq!(buf.read_float().unwrap(), 0.5);
+        assert_eq!(buf.read_long().unwrap(), 42);
+        assert!(buf.is_empty());
+    }
+}
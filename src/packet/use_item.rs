@@ -0,0 +1,55 @@
+use crate::error::Result;
+use crate::packet::buf::PacketBuf;
+
+/// A player right clicking with an item without aiming at a block, like
+/// eating or throwing a snowball
+#[derive(Debug, Clone, PartialEq)]
+pub struct UseItem {
+    pub hand: i32,
+    /// Echoed back with Acknowledge Block Change once handled
+    pub sequence: i32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl UseItem {
+    // Hand                  varint (0 main hand, 1 off hand)
+    // Sequence              varint
+    // Yaw                   float (degrees)
+    // Pitch                 float (degrees)
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        Ok(Self {
+            hand: buf.read_varint()?,
+            sequence: buf.read_varint()?,
+            yaw: buf.read_float()?,
+            pitch: buf.read_float()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{BufMut, BytesMut};
+
+    #[test]
+    fn test_decode_use_item() {
+        let mut packet = BytesMut::new();
+        packet.put_u8(0x01);
+        packet.put_slice(&[0xAC, 0x02]);
+        packet.put_f32(-90.0);
+        packet.put_f32(45.0);
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            UseItem::decode(&mut buf).unwrap(),
+            UseItem {
+                hand: 1,
+                sequence: 300,
+                yaw: -90.0,
+                pitch: 45.0,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use crate::error::Result;
+use crate::packet::buf::PacketBuf;
+
+/// Where the player steered the vehicle they're driving
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveVehicle {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl MoveVehicle {
+    // X                     double (absolute)
+    // Y                     double (absolute)
+    // Z                     double (absolute)
+    // Yaw                   float (degrees)
+    // Pitch                 float (degrees)
+    pub fn decode(buf: &mut PacketBuf) -> Result<Self> {
+        Ok(Self {
+            x: buf.read_double()?,
+            y: buf.read_double()?,
+            z: buf.read_double()?,
+            yaw: buf.read_float()?,
+            pitch: buf.read_float()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{BufMut, BytesMut};
+
+    #[test]
+    fn test_decode_move_vehicle() {
+        let mut packet = BytesMut::new();
+        packet.put_f64(10.5);
+        packet.put_f64(-60.0);
+        packet.put_f64(3.25);
+        packet.put_f32(90.0);
+        packet.put_f32(-15.0);
+        let mut buf = PacketBuf::new(&packet);
+
+        assert_eq!(
+            MoveVehicle::decode(&mut buf).unwrap(),
+            MoveVehicle {
+                x: 10.5,
+                y: -60.0,
+                z: 3.25,
+                yaw: 90.0,
+                pitch: -15.0,
+            }
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_short_move_vehicle() {
+        let mut packet = BytesMut::new();
+        packet.put_f64(10.5);
+        packet.put_f64(-60.0);
+        let mut buf = PacketBuf::new(&packet);
+
+        assert!(MoveVehicle::decode(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::packet::item::ItemStack;
+use crate::packet::metadata::{SNEAKING_FLAG, SPRINTING_FLAG};
+use crate::packet::play::{
+    ABILITY_ALLOW_FLYING, ABILITY_FLYING, ABILITY_INSTANT_BREAK, ABILITY_INVULNERABLE,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    pub fn id(self) -> u8 {
+        match self {
+            GameMode::Survival => 0,
+            GameMode::Creative => 1,
+            GameMode::Adventure => 2,
+            GameMode::Spectator => 3,
+        }
+    }
+
+    /// Player Abilities flags a player in this game mode starts with
+    pub fn abilities(self) -> u8 {
+        match self {
+            GameMode::Survival | GameMode::Adventure => 0,
+            GameMode::Creative => {
+                ABILITY_INVULNERABLE | ABILITY_ALLOW_FLYING | ABILITY_INSTANT_BREAK
+            }
+            GameMode::Spectator => ABILITY_INVULNERABLE | ABILITY_ALLOW_FLYING | ABILITY_FLYING,
+        }
+    }
+}
+
+/// A property of the player's game profile, like their skin
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Experience {
+    /// Progress towards the next level, from 0.0 to 1.0
+    pub bar: f32,
+    pub level: i32,
+    pub total: i32,
+}
+
+pub const MAX_HEALTH: f32 = 20.0;
+pub const MAX_FOOD: i32 = 20;
+pub const DEFAULT_SATURATION: f32 = 5.0;
+
+/// Slots in the player inventory window: crafting output and grid, armor,
+/// the main inventory, the hotbar and the offhand
+pub const INVENTORY_SIZE: usize = 46;
+
+/// Contents of a player's inventory, indexed like the player inventory window
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: vec![None; INVENTORY_SIZE],
+        }
+    }
+}
+
+impl Inventory {
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot).and_then(Option::as_ref)
+    }
+
+    /// Puts `item` in `slot`, returns false when there's no such slot
+    pub fn set(&mut self, slot: usize, item: Option<ItemStack>) -> bool {
+        match self.slots.get_mut(slot) {
+            Some(current) => {
+                *current = item;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// What the server knows about a player's entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+    pub username: String,
+    pub uuid: Uuid,
+    pub properties: Vec<ProfileProperty>,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    // needed for fall damage
+    pub on_ground: bool,
+    pub flying: bool,
+    pub sneaking: bool,
+    pub sprinting: bool,
+    pub game_mode: GameMode,
+    pub xp: Experience,
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+    pub inventory: Inventory,
+    /// Smoothed keep-alive round trip, shown as the ping bars in the tab list
+    pub latency_ms: i32,
+}
+
+impl PlayerState {
+    /// Entity flags other players see, see the `*_FLAG` metadata constants
+    pub fn entity_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.sneaking {
+            flags |= SNEAKING_FLAG;
+        }
+        if self.sprinting {
+            flags |= SPRINTING_FLAG;
+        }
+        flags
+    }
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            username: String::new(),
+            uuid: Uuid::nil(),
+            properties: Vec::new(),
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            on_ground: false,
+            flying: false,
+            sneaking: false,
+            sprinting: false,
+            game_mode: GameMode::default(),
+            xp: Experience::default(),
+            health: MAX_HEALTH,
+            food: MAX_FOOD,
+            saturation: DEFAULT_SATURATION,
+            inventory: Inventory::default(),
+            latency_ms: 0,
+        }
+    }
+}
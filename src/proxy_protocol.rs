@@ -0,0 +1,115 @@
+//! PROXY protocol v2, the binary header a TCP proxy puts in front of the
+//! connection to pass on the address of the real client
+
+use crate::error::{MinecraftError, Result};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const HEADER_SIZE: usize = 16;
+
+// Commands, in the low nibble of the version/command byte
+const LOCAL_COMMAND: u8 = 0x0;
+const PROXY_COMMAND: u8 = 0x1;
+
+// Address families, in the high nibble of the family/protocol byte
+const INET_FAMILY: u8 = 0x1;
+const INET6_FAMILY: u8 = 0x2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyHeader {
+    /// Where the client really connected from, `None` for the proxy's own
+    /// health checks (LOCAL command) and address families we don't know
+    pub source: Option<SocketAddr>,
+    /// Bytes the header takes up, the Minecraft handshake starts after it
+    pub length: usize,
+}
+
+// Signature             12 bytes
+// Version / Command     byte (0x2 / 0x0 local or 0x1 proxy)
+// Family / Protocol     byte
+// Address Length        unsigned short
+// Addresses             source address, destination address, source port, destination port
+//
+/// Parses a PROXY v2 header from the start of `buf`, returns `None` while it's incomplete
+pub fn parse_proxy_header(buf: &[u8]) -> Result<Option<ProxyHeader>> {
+    let available = buf.len().min(SIGNATURE.len());
+    if buf[..available] != SIGNATURE[..available] {
+        return Err(MinecraftError::Protocol(
+            "Connection did not start with a PROXY v2 header".into(),
+        ));
+    }
+    if buf.len() < HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let version = buf[12] >> 4;
+    let command = buf[12] & 0x0F;
+    if version != 2 {
+        return Err(MinecraftError::Protocol(format!(
+            "Unsupported PROXY protocol version: {version}"
+        )));
+    }
+
+    let address_length = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    let length = HEADER_SIZE + address_length;
+    if buf.len() < length {
+        return Ok(None);
+    }
+    let addresses = &buf[HEADER_SIZE..length];
+
+    let source = match command {
+        LOCAL_COMMAND => None,
+        PROXY_COMMAND => match buf[13] >> 4 {
+            INET_FAMILY if addresses.len() >= 12 => {
+                let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+                let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+                Some(SocketAddr::new(ip.into(), port))
+            }
+            INET6_FAMILY if addresses.len() >= 36 => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&addresses[..16]);
+                let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+                Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port))
+            }
+            // unix sockets and unspecified families carry no usable address
+            _ => None,
+        },
+        _ => {
+            return Err(MinecraftError::Protocol(format!(
+                "Unknown PROXY protocol command: {command}"
+            )))
+        }
+    };
+
+    Ok(Some(ProxyHeader { source, length }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proxy_header() {
+        let mut header = SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0x00, 0x0C]);
+        header.extend_from_slice(&[203, 0, 113, 7]); // source
+        header.extend_from_slice(&[10, 0, 0, 1]); // destination
+        header.extend_from_slice(&51234u16.to_be_bytes());
+        header.extend_from_slice(&25565u16.to_be_bytes());
+
+        // incomplete headers wait for more data
+        assert_eq!(parse_proxy_header(&header[..10]).unwrap(), None);
+        assert_eq!(parse_proxy_header(&header[..20]).unwrap(), None);
+
+        header.push(0x10); // the handshake that follows
+        assert_eq!(
+            parse_proxy_header(&header).unwrap(),
+            Some(ProxyHeader {
+                source: Some("203.0.113.7:51234".parse().unwrap()),
+                length: 28,
+            })
+        );
+
+        assert!(parse_proxy_header(b"\x10\x00\xFF\x05").is_err());
+    }
+}
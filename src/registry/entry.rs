@@ -1,8 +1,24 @@
+use crate::error::{MinecraftError, Result};
 use crate::packet::reader::PacketReader;
 use bytes::{BufMut, BytesMut};
 use serde::Deserialize;
 use tracing::debug;
 
+/// Registries the client accepts in Registry Data, without the minecraft namespace
+pub const KNOWN_REGISTRIES: [&str; 11] = [
+    "worldgen/biome",
+    "chat_type",
+    "trim_pattern",
+    "trim_material",
+    "wolf_variant",
+    "painting_variant",
+    "dimension_type",
+    "damage_type",
+    "banner_pattern",
+    "enchantment",
+    "jukebox_song",
+];
+
 #[derive(Debug, Deserialize)]
 pub struct RegistryEntry {
     pub namespace: String,
@@ -30,7 +46,6 @@ impl RegistryEntry {
 }
 
 // Registry packets look like the following
-// Packet Length         varint
 // Packet ID             varint
 // Length of identifier  varint
 // Registry name         identifier
@@ -42,7 +57,14 @@ impl RegistryEntry {
 // Entry name            identifier
 // Null terminate        varint
 // --------------------------------
-pub fn write_registry_packet(registry_name: &str, entries: &[&str]) -> BytesMut {
+pub fn write_registry_packet(registry_name: &str, entries: &[&str]) -> Result<BytesMut> {
+    // the client ignores registries it doesn't know, so a typo would go unnoticed
+    if !KNOWN_REGISTRIES.contains(&registry_name) {
+        return Err(MinecraftError::Registry(format!(
+            "Unknown registry minecraft:{registry_name}"
+        )));
+    }
+
     let mut packet = BytesMut::new();
 
     PacketReader::write_varint(&mut packet, 0x07);
@@ -57,24 +79,25 @@ pub fn write_registry_packet(registry_name: &str, entries: &[&str]) -> BytesMut
         packet.put_u8(0); // Has Data = false
     }
 
-    let packet_len = packet.len();
-    let mut final_packet = BytesMut::new();
-    PacketReader::write_varint(&mut final_packet, packet_len as i32);
-    final_packet.extend_from_slice(&packet);
-
-    final_packet
+    Ok(packet)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::packet::frame::frame_packet;
+    use flate2::Compression;
 
     #[test]
     fn test_registry_packet() {
         let expected = b"\x74\x07\x18minecraft:dimension_type\x04\x13minecraft:overworld\0\x19minecraft:overworld_caves\0\x14minecraft:the_nether\0\x11minecraft:the_end\0";
 
         let entries = &["overworld", "overworld_caves", "the_nether", "the_end"];
-        let packet = write_registry_packet("dimension_type", entries);
+        let packet = frame_packet(
+            &write_registry_packet("dimension_type", entries).unwrap(),
+            None,
+            Compression::default(),
+        );
 
         // 0x74                          Packet Length         varint
         // 0x07                          Packet ID             varint
@@ -92,4 +115,13 @@ mod tests {
         assert!(!packet.is_empty());
         assert_eq!(&packet[..], expected);
     }
+
+    #[test]
+    fn test_unknown_registry_is_rejected() {
+        let error = write_registry_packet("dimension_types", &["overworld"]).unwrap_err();
+        assert!(
+            matches!(&error, MinecraftError::Registry(message) if message.contains("minecraft:dimension_types")),
+            "{error}"
+        );
+    }
 }
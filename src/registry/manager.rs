@@ -3,10 +3,14 @@ use super::{
     Biome, ChatParameters, ChatType, DamageType, DimensionType, RegistryData, TrimMaterial,
     TrimPattern, WolfVariant,
 };
-use crate::{error::Result, packet::reader::PacketReader, tag::*};
+use crate::{
+    error::{MinecraftError, Result},
+    packet::reader::PacketReader,
+    tag::*,
+};
 use bytes::{BufMut, BytesMut};
 use std::collections::HashMap;
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use std::path::Path;
 use tracing::debug;
 
 pub struct RegistryManager {
@@ -15,92 +19,94 @@ pub struct RegistryManager {
 
 impl RegistryManager {
     pub fn new() -> Result<Self> {
-        let default_registry_data = include_str!("../../default_registry.json");
-        let registry_data: RegistryData = serde_json::from_str(default_registry_data)?;
+        Self::from_json(include_str!("../../default_registry.json"))
+    }
 
-        Ok(Self { registry_data })
+    /// Loads registries from a JSON file in the format of default_registry.json
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
     }
 
-    pub async fn write_registry_data(&self, socket: &mut TcpStream) -> Result<()> {
-        self.send_registry_packet(socket, "worldgen/biome", self.registry_data.biomes.keys())
-            .await?;
-        self.send_registry_packet(socket, "chat_type", self.registry_data.chat_types.keys())
-            .await?;
-        self.send_registry_packet(
-            socket,
-            "trim_pattern",
-            self.registry_data.trim_patterns.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "trim_material",
-            self.registry_data.trim_materials.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "wolf_variant",
-            self.registry_data.wolf_variants.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "painting_variant",
-            self.registry_data.painting_variants.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "dimension_type",
-            self.registry_data.dimension_types.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "damage_type",
-            self.registry_data.damage_types.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "banner_pattern",
-            self.registry_data.banner_patterns.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "enchantment",
-            self.registry_data.enchantments.keys(),
-        )
-        .await?;
-        self.send_registry_packet(
-            socket,
-            "jukebox_song",
-            self.registry_data.jukebox_songs.keys(),
-        )
-        .await?;
+    fn from_json(json: &str) -> Result<Self> {
+        let registry_data: RegistryData = serde_json::from_str(json)?;
+        let manager = Self { registry_data };
+        manager.validate()?;
+
+        Ok(manager)
+    }
+
+    /// Checks the registries a client needs to join are present and not empty,
+    /// clients disconnect during configuration when one of them is missing
+    pub fn validate(&self) -> Result<()> {
+        let required = [
+            ("worldgen/biome", self.registry_data.biomes.len()),
+            ("dimension_type", self.registry_data.dimension_types.len()),
+            ("chat_type", self.registry_data.chat_types.len()),
+            ("damage_type", self.registry_data.damage_types.len()),
+        ];
+
+        for (name, entries) in required {
+            if entries == 0 {
+                return Err(MinecraftError::Registry(format!(
+                    "Registry minecraft:{name} is missing or has no entries"
+                )));
+            }
+        }
 
         Ok(())
     }
 
-    async fn send_registry_packet<'a, I>(
-        &self,
-        socket: &mut TcpStream,
-        registry_name: &str,
-        entries: I,
-    ) -> Result<()>
+    /// Id the client gives a dimension type, its position in the dimension_type
+    /// Registry Data packet. `name` defaults to the minecraft namespace.
+    pub fn dimension_type_id(&self, name: &str) -> Option<i32> {
+        Self::entry_id(self.registry_data.dimension_types.keys(), name)
+    }
+
+    /// Id the client gives a chat type, its position in the chat_type
+    /// Registry Data packet. `name` defaults to the minecraft namespace.
+    pub fn chat_type_id(&self, name: &str) -> Option<i32> {
+        Self::entry_id(self.registry_data.chat_types.keys(), name)
+    }
+
+    fn entry_id<'a>(mut keys: impl Iterator<Item = &'a String>, name: &str) -> Option<i32> {
+        let name = match name.contains(':') {
+            true => name.to_string(),
+            false => format!("minecraft:{name}"),
+        };
+        keys.position(|key| *key == name).map(|id| id as i32)
+    }
+
+    /// Registry Data packets for every registry, in the order they're sent
+    pub fn registry_data_packets(&self) -> Result<Vec<BytesMut>> {
+        [
+            Self::registry_packet("worldgen/biome", self.registry_data.biomes.keys()),
+            Self::registry_packet("chat_type", self.registry_data.chat_types.keys()),
+            Self::registry_packet("trim_pattern", self.registry_data.trim_patterns.keys()),
+            Self::registry_packet("trim_material", self.registry_data.trim_materials.keys()),
+            Self::registry_packet("wolf_variant", self.registry_data.wolf_variants.keys()),
+            Self::registry_packet(
+                "painting_variant",
+                self.registry_data.painting_variants.keys(),
+            ),
+            Self::registry_packet("dimension_type", self.registry_data.dimension_types.keys()),
+            Self::registry_packet("damage_type", self.registry_data.damage_types.keys()),
+            Self::registry_packet("banner_pattern", self.registry_data.banner_patterns.keys()),
+            Self::registry_packet("enchantment", self.registry_data.enchantments.keys()),
+            Self::registry_packet("jukebox_song", self.registry_data.jukebox_songs.keys()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn registry_packet<'a, I>(registry_name: &str, entries: I) -> Result<BytesMut>
     where
         I: IntoIterator<Item = &'a String>,
     {
         let entries: Vec<&str> = entries.into_iter().map(|s| s.as_str()).collect();
-        let packet = write_registry_packet(registry_name, &entries);
-
-        socket.write_all(&packet).await?;
-
-        Ok(())
+        write_registry_packet(registry_name, &entries)
     }
-    pub async fn write_update_tags(&self, socket: &mut TcpStream) -> Result<()> {
+
+    pub fn update_tags_packet(&self) -> Result<BytesMut> {
         let default_tags = include_str!("../../default_tags.json");
         let tag_data: TagData = serde_json::from_str(default_tags)?;
 
@@ -152,15 +158,7 @@ impl RegistryManager {
         );
         Self::write_tag_groups(&mut packet, "worldgen/biome", &tag_data.worldgen_biomes);
 
-        // Write final packet with length prefix
-        let packet_len = packet.len();
-        let mut final_packet = BytesMut::new();
-        PacketReader::write_varint(&mut final_packet, packet_len as i32);
-        final_packet.extend_from_slice(&packet);
-
-        socket.write_all(&final_packet).await?;
-
-        Ok(())
+        Ok(packet)
     }
 
     fn write_tag_groups(packet: &mut BytesMut, registry_name: &str, tags: &[TagGroup]) {
@@ -184,3 +182,101 @@ impl RegistryManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_packets_are_deterministic() {
+        let first = RegistryManager::new().unwrap();
+        let second = RegistryManager::new().unwrap();
+
+        assert_eq!(
+            first.registry_data_packets().unwrap(),
+            second.registry_data_packets().unwrap()
+        );
+        assert_eq!(first.dimension_type_id("overworld"), Some(0));
+    }
+
+    /// Checks `id` gives every entry of `registry` its position in the
+    /// registry's Registry Data packet
+    fn assert_ids_match_send_order(
+        manager: &RegistryManager,
+        registry: &str,
+        id: impl Fn(&RegistryManager, &str) -> Option<i32>,
+    ) {
+        let mut prefix = BytesMut::new();
+        PacketReader::write_string(&mut prefix, &format!("minecraft:{registry}"));
+        let packets = manager.registry_data_packets().unwrap();
+        let packet = packets
+            .iter()
+            .find(|packet| packet[1..].starts_with(&prefix))
+            .unwrap();
+
+        let mut buf = &packet[..];
+        PacketReader::read_varint(&mut buf).unwrap(); // packet id
+        PacketReader::read_string(&mut buf).unwrap(); // registry name
+        let count = PacketReader::read_varint(&mut buf).unwrap();
+        assert!(count > 1);
+        for expected in 0..count {
+            let name = PacketReader::read_string(&mut buf).unwrap();
+            PacketReader::read_boolean(&mut buf).unwrap(); // has data
+            assert_eq!(id(manager, &name), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_dimension_type_id_matches_send_order() {
+        let manager = RegistryManager::new().unwrap();
+        assert_ids_match_send_order(
+            &manager,
+            "dimension_type",
+            RegistryManager::dimension_type_id,
+        );
+
+        assert!(manager.dimension_type_id("overworld").is_some());
+        assert_eq!(
+            manager.dimension_type_id("overworld"),
+            manager.dimension_type_id("minecraft:overworld")
+        );
+        assert_eq!(manager.dimension_type_id("minecraft:moon"), None);
+    }
+
+    #[test]
+    fn test_chat_type_id_matches_send_order() {
+        let manager = RegistryManager::new().unwrap();
+        assert_ids_match_send_order(&manager, "chat_type", RegistryManager::chat_type_id);
+
+        assert_eq!(
+            manager.chat_type_id("chat"),
+            manager.chat_type_id("minecraft:chat")
+        );
+        assert_eq!(manager.chat_type_id("minecraft:shout"), None);
+    }
+
+    #[test]
+    fn test_incomplete_registry_fails_validation() {
+        assert!(RegistryManager::new().is_ok());
+
+        let path = std::env::temp_dir().join(format!(
+            "octavia-incomplete-registry-{}.json",
+            std::process::id()
+        ));
+        // a chat type but no biomes, dimension types or damage types
+        std::fs::write(
+            &path,
+            r#"{ "minecraft:chat_type": { "minecraft:chat": {
+                "chat": { "translation_key": "chat.type.text", "parameters": ["sender", "content"] },
+                "narration": { "translation_key": "chat.type.text.narrate", "parameters": ["sender", "content"] }
+            } } }"#,
+        )
+        .unwrap();
+
+        let result = RegistryManager::from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = result.err().expect("validation should fail").to_string();
+        assert!(error.contains("minecraft:worldgen/biome"), "{error}");
+    }
+}
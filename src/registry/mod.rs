@@ -4,7 +4,7 @@ pub mod entry;
 pub mod manager;
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 type Byte = i8;
 type Boolean = bool;
@@ -14,30 +14,34 @@ type Long = i64;
 type Float = f32;
 type Double = f64;
 
-#[derive(Deserialize, Debug)]
+// missing registries come out empty, RegistryManager::validate reports them.
+// Clients number entries in the order they're sent, so they're kept sorted
+// to give every run and every RegistryManager the same ids.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct RegistryData {
     #[serde(rename = "minecraft:banner_pattern")]
-    banner_patterns: HashMap<String, BannerPattern>,
+    banner_patterns: BTreeMap<String, BannerPattern>,
     #[serde(rename = "minecraft:chat_type")]
-    chat_types: HashMap<String, ChatType>,
+    chat_types: BTreeMap<String, ChatType>,
     #[serde(rename = "minecraft:damage_type")]
-    damage_types: HashMap<String, DamageType>,
+    damage_types: BTreeMap<String, DamageType>,
     #[serde(rename = "minecraft:dimension_type")]
-    dimension_types: HashMap<String, DimensionType>,
+    dimension_types: BTreeMap<String, DimensionType>,
     #[serde(rename = "minecraft:trim_material")]
-    trim_materials: HashMap<String, TrimMaterial>,
+    trim_materials: BTreeMap<String, TrimMaterial>,
     #[serde(rename = "minecraft:trim_pattern")]
-    trim_patterns: HashMap<String, TrimPattern>,
+    trim_patterns: BTreeMap<String, TrimPattern>,
     #[serde(rename = "minecraft:wolf_variant")]
-    wolf_variants: HashMap<String, WolfVariant>,
+    wolf_variants: BTreeMap<String, WolfVariant>,
     #[serde(rename = "minecraft:worldgen/biome")]
-    biomes: HashMap<String, Biome>,
+    biomes: BTreeMap<String, Biome>,
     #[serde(rename = "minecraft:painting_variant")]
-    painting_variants: HashMap<String, PaintingVariant>,
+    painting_variants: BTreeMap<String, PaintingVariant>,
     #[serde(rename = "minecraft:enchantment")]
-    enchantments: HashMap<String, Enchantment>,
+    enchantments: BTreeMap<String, Enchantment>,
     #[serde(rename = "minecraft:jukebox_song")]
-    jukebox_songs: HashMap<String, JukeboxSong>,
+    jukebox_songs: BTreeMap<String, JukeboxSong>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -0,0 +1,138 @@
+//! Delayed and periodic tasks that run on server ticks, so features share one
+//! clock instead of each spawning its own timer
+
+use std::time::Duration;
+
+/// Length of a server tick, vanilla runs 20 of them per second
+pub const TICK_DURATION: Duration = Duration::from_millis(50);
+
+enum Task {
+    Once(Box<dyn FnOnce() + Send>),
+    Repeating {
+        period: u64,
+        task: Box<dyn FnMut() + Send>,
+    },
+}
+
+struct Scheduled {
+    // tick on which the task runs next
+    due: u64,
+    task: Task,
+}
+
+/// Runs tasks once a given number of ticks has passed
+///
+/// Tasks run inside `tick`, so whatever owns the scheduler is borrowed while
+/// they run and they can't schedule other tasks through it.
+#[derive(Default)]
+pub struct Scheduler {
+    current_tick: u64,
+    tasks: Vec<Scheduled>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ticks that have passed since the scheduler was created
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    /// Number of tasks still waiting to run, repeating tasks always count
+    pub fn pending(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Runs `task` once, `ticks` ticks from now. 0 runs it on the next tick.
+    pub fn run_later(&mut self, ticks: u64, task: impl FnOnce() + Send + 'static) {
+        self.tasks.push(Scheduled {
+            due: self.current_tick + ticks.max(1),
+            task: Task::Once(Box::new(task)),
+        });
+    }
+
+    /// Runs `task` every `period` ticks, the first time `period` ticks from now
+    pub fn run_repeating(&mut self, period: u64, task: impl FnMut() + Send + 'static) {
+        let period = period.max(1);
+        self.tasks.push(Scheduled {
+            due: self.current_tick + period,
+            task: Task::Repeating {
+                period,
+                task: Box::new(task),
+            },
+        });
+    }
+
+    /// Advances to the next tick and runs every task due on it, in the order
+    /// they were scheduled
+    pub fn tick(&mut self) {
+        self.current_tick += 1;
+
+        let mut waiting = Vec::with_capacity(self.tasks.len());
+        for mut scheduled in std::mem::take(&mut self.tasks) {
+            if scheduled.due > self.current_tick {
+                waiting.push(scheduled);
+                continue;
+            }
+
+            match scheduled.task {
+                Task::Once(task) => task(),
+                Task::Repeating {
+                    period,
+                    ref mut task,
+                } => {
+                    task();
+                    scheduled.due += period;
+                    waiting.push(scheduled);
+                }
+            }
+        }
+        self.tasks = waiting;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_run_later_runs_on_the_due_tick() {
+        let mut scheduler = Scheduler::new();
+        let ran_on = Arc::new(AtomicU64::new(0));
+
+        let task_ran_on = ran_on.clone();
+        scheduler.run_later(5, move || task_ran_on.store(5, Ordering::SeqCst));
+
+        for _ in 0..4 {
+            scheduler.tick();
+            assert_eq!(ran_on.load(Ordering::SeqCst), 0);
+        }
+        scheduler.tick();
+        assert_eq!(ran_on.load(Ordering::SeqCst), 5);
+        assert_eq!(scheduler.current_tick(), 5);
+        assert_eq!(scheduler.pending(), 0);
+    }
+
+    #[test]
+    fn test_run_repeating_runs_every_period() {
+        let mut scheduler = Scheduler::new();
+        let runs = Arc::new(AtomicU64::new(0));
+
+        let task_runs = runs.clone();
+        scheduler.run_repeating(3, move || {
+            task_runs.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let mut seen = Vec::new();
+        for _ in 0..9 {
+            scheduler.tick();
+            seen.push(runs.load(Ordering::SeqCst));
+        }
+        assert_eq!(seen, [0, 0, 1, 1, 1, 2, 2, 2, 3]);
+        assert_eq!(scheduler.pending(), 1);
+    }
+}
@@ -1,31 +1,406 @@
+use crate::command::CommandContext;
+use crate::config::{ServerConfig, MAX_COMPRESSION_LEVEL};
 use crate::connection::Connection;
-use crate::error::Result;
+use crate::error::{MinecraftError, Result};
+#[cfg(feature = "metrics-http")]
+use crate::metrics::http;
+use crate::metrics::Metrics;
+use crate::packet::frame::MAX_PACKET_SIZE;
+use crate::registry::manager::RegistryManager;
+use crate::scheduler::TICK_DURATION;
+use crate::world::SharedWorld;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tracing::{error, info};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+
+/// How long connections get to wind down before they're aborted
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct Server {
+    registries: Arc<RegistryManager>,
     listener: TcpListener,
+    config: Arc<ServerConfig>,
+    world: Arc<SharedWorld>,
+    // one task per connection
+    tasks: JoinSet<()>,
+    // every connection task holds a permit until it ends
+    connection_permits: Arc<Semaphore>,
+    shutdown: watch::Sender<bool>,
+    #[cfg(feature = "metrics-http")]
+    metrics_listener: Option<TcpListener>,
 }
 
 impl Server {
-    pub async fn new() -> Result<Self> {
-        let listener = TcpListener::bind("127.0.0.1:25565").await?;
-        Ok(Self { listener })
+    pub async fn new(config: ServerConfig) -> Result<Self> {
+        if config.compression_level > MAX_COMPRESSION_LEVEL {
+            return Err(MinecraftError::Config(format!(
+                "Compression level must be 0 to {MAX_COMPRESSION_LEVEL}, got {}",
+                config.compression_level
+            )));
+        }
+        // the largest packet a client may send, with its 3 byte length, has
+        // to fit while it arrives
+        let min_buffered_bytes = MAX_PACKET_SIZE + 3;
+        if config.max_buffered_bytes < min_buffered_bytes {
+            return Err(MinecraftError::Config(format!(
+                "Max buffered bytes must be at least {min_buffered_bytes}, got {}",
+                config.max_buffered_bytes
+            )));
+        }
+
+        // fail at startup rather than when the first player joins
+        let registries = match &config.registry_path {
+            Some(path) => RegistryManager::from_path(path)?,
+            None => RegistryManager::new()?,
+        };
+        registries.registry_data_packets()?;
+        registries.update_tags_packet()?;
+
+        let listener = TcpListener::bind(&config.address).await?;
+        #[cfg(feature = "metrics-http")]
+        let metrics_listener = match &config.metrics_address {
+            Some(address) => Some(TcpListener::bind(address).await?),
+            None => None,
+        };
+        #[cfg(not(feature = "metrics-http"))]
+        if config.metrics_address.is_some() {
+            warn!("Metrics address is set, but the metrics-http feature is disabled");
+        }
+        let world = SharedWorld::new();
+        world.set_difficulty(config.difficulty);
+        world.set_weather(config.weather);
+        let connection_permits = Arc::new(Semaphore::new(config.max_connections));
+
+        Ok(Self {
+            registries: Arc::new(registries),
+            listener,
+            config: Arc::new(config),
+            world: Arc::new(world),
+            tasks: JoinSet::new(),
+            connection_permits,
+            shutdown: watch::Sender::new(false),
+            #[cfg(feature = "metrics-http")]
+            metrics_listener,
+        })
+    }
+
+    /// Adds a command players can run by typing `/name`, replacing any
+    /// command with the same name. Players already online get the updated
+    /// command tree right away.
+    ///
+    /// The handler gets the words after the command name as arguments and
+    /// replies through the context.
+    ///
+    /// ```no_run
+    /// use octavia::config::ServerConfig;
+    /// use octavia::server::Server;
+    ///
+    /// # async fn run() -> octavia::Result<()> {
+    /// let mut server = Server::new(ServerConfig::default()).await?;
+    /// server.register_command("ping", |ctx, _args| ctx.reply("pong"));
+    /// server.run().await
+    /// # }
+    /// ```
+    pub fn register_command(
+        &self,
+        name: &str,
+        handler: impl Fn(&mut CommandContext, &[&str]) + Send + Sync + 'static,
+    ) {
+        self.world.register_command(name, Arc::new(handler));
+    }
+
+    /// Live health numbers, like the ping of the players online
+    pub fn metrics(&self) -> &Metrics {
+        self.world.metrics()
+    }
+
+    /// Address the server is listening on, useful when binding to port 0
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        info!("Server listening on port 25565");
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Accepts connections until `signal` completes, then shuts down
+    pub async fn run_until(&mut self, signal: impl Future<Output = ()>) -> Result<()> {
+        info!(address = %self.listener.local_addr()?, "Server listening");
+        tokio::pin!(signal);
+        let mut ticks = tokio::time::interval(TICK_DURATION);
+        #[cfg(feature = "metrics-http")]
+        let metrics = match self.metrics_listener.take() {
+            Some(listener) => {
+                info!(address = %listener.local_addr()?, "Serving metrics");
+                Some(tokio::spawn(http::serve(listener, self.world.clone())))
+            }
+            None => None,
+        };
 
         loop {
-            let (socket, addr) = self.listener.accept().await?;
-            let mut connection = Connection::new(socket);
-            info!(%addr, "New connection");
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (socket, addr) = accepted?;
+                    let Ok(permit) = self.connection_permits.clone().try_acquire_owned() else {
+                        warn!(%addr, "Too many connections, closing new connection");
+                        drop(socket);
+                        continue;
+                    };
+                    let mut connection =
+                        Connection::new(socket, self.config.clone(), self.world.clone())
+                            .with_registries(self.registries.clone())
+                            .with_shutdown(self.shutdown.subscribe())
+                            .with_peer_addr(addr);
+                    info!(%addr, "New connection");
 
-            tokio::spawn(async move {
-                if let Err(e) = connection.handle_connection().await {
-                    error!(%addr, error = %e, "Connection error");
+                    self.tasks.spawn(async move {
+                        if let Err(e) = connection.handle_connection().await {
+                            error!(%addr, error = %e, "Connection error");
+                        }
+                        drop(permit);
+                    });
                 }
-            });
+                _ = ticks.tick() => self.world.tick(),
+                // reap finished connections so the set doesn't keep growing
+                Some(_) = self.tasks.join_next() => {}
+                _ = &mut signal => break,
+            }
         }
+
+        self.shutdown(SHUTDOWN_TIMEOUT).await;
+        #[cfg(feature = "metrics-http")]
+        if let Some(metrics) = metrics {
+            metrics.abort();
+        }
+        Ok(())
+    }
+
+    /// Asks every connection to close and waits for them, aborting whatever
+    /// is still running after `timeout`
+    pub async fn shutdown(&mut self, timeout: Duration) {
+        info!(connections = self.tasks.len(), "Shutting down");
+        self.shutdown.send_replace(true);
+
+        let drain = async { while self.tasks.join_next().await.is_some() {} };
+        if tokio::time::timeout(timeout, drain).await.is_err() {
+            warn!(
+                connections = self.tasks.len(),
+                "Connections did not close in time, aborting them"
+            );
+            self.tasks.abort_all();
+            while self.tasks.join_next().await.is_some() {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::reader::PacketReader;
+    use bytes::{BufMut, BytesMut};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_invalid_registry_file_fails_startup() {
+        let path = std::env::temp_dir().join(format!(
+            "octavia-invalid-registry-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            registry_path: Some(path.clone()),
+            ..Default::default()
+        };
+        let result = Server::new(config).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(MinecraftError::Json(_))));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_compression_level_fails_startup() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            compression_level: 10,
+            ..Default::default()
+        };
+        let result = Server::new(config).await;
+
+        assert!(matches!(result, Err(MinecraftError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_buffer_smaller_than_a_packet_fails_startup() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            max_buffered_bytes: 64 * 1024,
+            ..Default::default()
+        };
+        let result = Server::new(config).await;
+
+        assert!(matches!(result, Err(MinecraftError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_connections_past_the_limit_are_closed() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            max_connections: 2,
+            ..Default::default()
+        };
+        let mut server = Server::new(config).await.unwrap();
+        let address = server.local_addr().unwrap();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let running = tokio::spawn(async move {
+            server
+                .run_until(async {
+                    let _ = stop_rx.await;
+                })
+                .await
+                .unwrap();
+        });
+
+        // connections are accepted in order, so these two hold the permits
+        // by the time the third one is accepted
+        let mut held = Vec::new();
+        for _ in 0..2 {
+            held.push(TcpStream::connect(address).await.unwrap());
+        }
+        let mut refused = TcpStream::connect(address).await.unwrap();
+
+        let mut byte = [0; 1];
+        let read = tokio::time::timeout(Duration::from_secs(1), refused.read(&mut byte)).await;
+        assert!(matches!(read, Ok(Ok(0)) | Ok(Err(_))), "{read:?}");
+        for client in &mut held {
+            let read =
+                tokio::time::timeout(Duration::from_millis(100), client.read(&mut byte)).await;
+            assert!(read.is_err(), "held connection was closed");
+        }
+
+        // closing one frees its permit for the next connection
+        drop(held.pop());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut admitted = TcpStream::connect(address).await.unwrap();
+        let read = tokio::time::timeout(Duration::from_millis(100), admitted.read(&mut byte)).await;
+        assert!(read.is_err(), "connection under the limit was closed");
+
+        stop_tx.send(()).unwrap();
+        running.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_joins_connection_tasks() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        };
+        let mut server = Server::new(config).await.unwrap();
+        let address = server.listener.local_addr().unwrap();
+
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let running = tokio::spawn(async move {
+            server
+                .run_until(async {
+                    let _ = stop_rx.await;
+                })
+                .await
+                .unwrap();
+            server
+        });
+
+        // a status request proves the connection task is up and running
+        let mut client = TcpStream::connect(address).await.unwrap();
+        let mut handshake = BytesMut::new();
+        PacketReader::write_varint(&mut handshake, 0x00);
+        PacketReader::write_varint(&mut handshake, 767);
+        PacketReader::write_string(&mut handshake, "localhost");
+        handshake.put_u16(address.port());
+        PacketReader::write_varint(&mut handshake, 1);
+
+        let mut packets = BytesMut::new();
+        PacketReader::write_varint(&mut packets, handshake.len() as i32);
+        packets.extend_from_slice(&handshake);
+        packets.extend_from_slice(&[0x01, 0x00]);
+        client.write_all(&packets).await.unwrap();
+
+        let mut received = vec![0; 1024];
+        assert!(client.read(&mut received).await.unwrap() > 0);
+
+        stop_tx.send(()).unwrap();
+        let server = running.await.unwrap();
+
+        assert!(server.tasks.is_empty());
+        // the connection was closed from the server side
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_status_and_ping_flow() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        };
+        let mut server = Server::new(config).await.unwrap();
+        let address = server.listener.local_addr().unwrap();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let running = tokio::spawn(async move {
+            server
+                .run_until(async {
+                    let _ = stop_rx.await;
+                })
+                .await
+                .unwrap();
+        });
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        let mut handshake = BytesMut::new();
+        PacketReader::write_varint(&mut handshake, 0x00);
+        PacketReader::write_varint(&mut handshake, 767);
+        PacketReader::write_string(&mut handshake, "localhost");
+        handshake.put_u16(address.port());
+        PacketReader::write_varint(&mut handshake, 1);
+
+        let mut packets = BytesMut::new();
+        PacketReader::write_varint(&mut packets, handshake.len() as i32);
+        packets.extend_from_slice(&handshake);
+        // status request
+        packets.extend_from_slice(&[0x01, 0x00]);
+        // ping request
+        packets.extend_from_slice(&[0x09, 0x01]);
+        packets.put_i64(0x0123_4567_89AB_CDEF);
+        client.write_all(&packets).await.unwrap();
+
+        // the server closes the connection after the pong
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        let mut buf = &received[..];
+
+        PacketReader::read_varint(&mut buf).unwrap(); // packet length
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x00);
+        let status: serde_json::Value =
+            serde_json::from_str(&PacketReader::read_string(&mut buf).unwrap()).unwrap();
+        assert_eq!(status["version"]["protocol"], 767);
+        assert_eq!(status["players"]["online"], 0);
+
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 9); // packet length
+        assert_eq!(PacketReader::read_varint(&mut buf).unwrap(), 0x01);
+        assert_eq!(
+            PacketReader::read_long(&mut buf).unwrap(),
+            0x0123_4567_89AB_CDEF
+        );
+        assert!(buf.is_empty());
+
+        stop_tx.send(()).unwrap();
+        running.await.unwrap();
     }
 }
@@ -0,0 +1,363 @@
+//! Client side of the server list ping, for querying other servers
+
+use crate::connection::PROTOCOL_VERSION;
+use crate::dns::{SrvResolver, SystemResolver};
+use crate::error::{MinecraftError, Result};
+use crate::packet::frame::MAX_PACKET_SIZE;
+use crate::packet::reader::PacketReader;
+use bytes::{BufMut, BytesMut};
+use serde_json::Value;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tracing::debug;
+
+pub const DEFAULT_PORT: u16 = 25565;
+
+/// How long to wait for the connection and for each response before giving
+/// up on a server
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a server reported in its status response
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub version_name: String,
+    pub protocol: i32,
+    pub online_players: i32,
+    pub max_players: i32,
+    /// The description as a JSON text component
+    pub description: Value,
+    /// Round trip of the ping that followed the status request
+    pub latency: Duration,
+}
+
+impl ServerStatus {
+    /// The description without any formatting
+    pub fn motd(&self) -> String {
+        let mut motd = String::new();
+        plain_text(&self.description, &mut motd);
+        motd
+    }
+}
+
+fn plain_text(component: &Value, out: &mut String) {
+    match component {
+        Value::String(text) => out.push_str(text),
+        Value::Array(parts) => parts.iter().for_each(|part| plain_text(part, out)),
+        Value::Object(object) => {
+            if let Some(Value::String(text)) = object.get("text") {
+                out.push_str(text);
+            }
+            if let Some(extra) = object.get("extra") {
+                plain_text(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Asks the server at `address` ("host", "host:port", an IP address or
+/// "[ipv6]:port") for its status and measures the latency with a ping
+///
+/// Like the vanilla client, a host without a port has its `_minecraft._tcp`
+/// SRV record looked up, falling back to the default port without one.
+/// Connecting and each of the responses time out after `PING_TIMEOUT`.
+pub async fn ping(address: &str) -> Result<ServerStatus> {
+    ping_with_resolver(address, &SystemResolver).await
+}
+
+/// Like `ping`, looking up SRV records with `resolver`
+pub async fn ping_with_resolver(address: &str, resolver: &dyn SrvResolver) -> Result<ServerStatus> {
+    query(address, resolver, PING_TIMEOUT).await
+}
+
+/// The ping itself, giving up on any step that takes longer than `timeout`
+async fn query(
+    address: &str,
+    resolver: &dyn SrvResolver,
+    timeout: Duration,
+) -> Result<ServerStatus> {
+    let (host, port) = match split_address(address)? {
+        (host, Some(port)) => (host, port),
+        // there are no records for IP addresses
+        (host, None) if host.parse::<IpAddr>().is_ok() => (host, DEFAULT_PORT),
+        (host, None) => match resolver.resolve_minecraft(&host).await {
+            Ok(Some(target)) => {
+                debug!(address, host = %target.host, port = target.port, "Using SRV record");
+                (target.host, target.port)
+            }
+            Ok(None) => (host, DEFAULT_PORT),
+            Err(e) => {
+                debug!(address, error = %e, "SRV lookup failed, using the default port");
+                (host, DEFAULT_PORT)
+            }
+        },
+    };
+    let mut stream = within(timeout, "connecting", async {
+        Ok(TcpStream::connect((host.as_str(), port)).await?)
+    })
+    .await?;
+
+    // Handshake with next state Status, followed by a Status Request
+    let mut handshake = BytesMut::new();
+    PacketReader::write_varint(&mut handshake, 0x00);
+    PacketReader::write_varint(&mut handshake, PROTOCOL_VERSION);
+    PacketReader::write_string(&mut handshake, &host);
+    handshake.put_u16(port);
+    PacketReader::write_varint(&mut handshake, 1);
+
+    let mut packets = frame(&handshake);
+    packets.extend_from_slice(&frame(&[0x00]));
+    stream.write_all(&packets).await?;
+
+    let response = within(
+        timeout,
+        "waiting for the status",
+        read_packet(&mut stream, 0x00),
+    )
+    .await?;
+    let json: Value = serde_json::from_str(&PacketReader::read_string(&mut &response[..])?)?;
+
+    // any payload works, the server has to echo it back
+    let payload: i64 = rand::random();
+    let sent_at = Instant::now();
+    let mut ping = BytesMut::new();
+    PacketReader::write_varint(&mut ping, 0x01);
+    ping.put_i64(payload);
+    stream.write_all(&frame(&ping)).await?;
+    let pong = within(
+        timeout,
+        "waiting for the pong",
+        read_packet(&mut stream, 0x01),
+    )
+    .await?;
+    let latency = sent_at.elapsed();
+    if PacketReader::read_long(&mut &pong[..])? != payload {
+        return Err(MinecraftError::Protocol(
+            "Pong doesn't echo the ping payload".to_string(),
+        ));
+    }
+
+    let number = |value: &Value| value.as_i64().unwrap_or_default() as i32;
+    Ok(ServerStatus {
+        version_name: json["version"]["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        protocol: number(&json["version"]["protocol"]),
+        online_players: number(&json["players"]["online"]),
+        max_players: number(&json["players"]["max"]),
+        description: json["description"].clone(),
+        latency,
+    })
+}
+
+/// Splits `address` into the host and the port, if it has one
+fn split_address(address: &str) -> Result<(String, Option<u16>)> {
+    if let Ok(socket) = address.parse::<SocketAddr>() {
+        return Ok((socket.ip().to_string(), Some(socket.port())));
+    }
+    // the colons in an IPv6 address don't start a port
+    let bare = address.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Ok((ip.to_string(), None));
+    }
+
+    match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| MinecraftError::Protocol(format!("Invalid port in {address}")))?;
+            Ok((host.to_string(), Some(port)))
+        }
+        None => Ok((address.to_string(), None)),
+    }
+}
+
+/// Fails with a timed out error when `future` takes longer than `timeout`
+async fn within<T>(
+    timeout: Duration,
+    step: &str,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::time::timeout(timeout, future).await.map_err(|_| {
+        MinecraftError::Io(std::io::Error::new(
+            ErrorKind::TimedOut,
+            format!("Timed out {step}"),
+        ))
+    })?
+}
+
+fn frame(content: &[u8]) -> BytesMut {
+    let mut packet = BytesMut::new();
+    PacketReader::write_varint(&mut packet, content.len() as i32);
+    packet.extend_from_slice(content);
+    packet
+}
+
+/// Reads one uncompressed packet and returns its body, checking the id
+async fn read_packet(stream: &mut TcpStream, expected_id: i32) -> Result<Vec<u8>> {
+    let mut length_bytes = Vec::new();
+    loop {
+        let byte = stream.read_u8().await?;
+        length_bytes.push(byte);
+        // a longer varint is invalid anyway, reading on would never end
+        if byte & 0x80 == 0 || length_bytes.len() == 5 {
+            break;
+        }
+    }
+    let length = PacketReader::read_varint(&mut &length_bytes[..])?;
+    // checked before allocating, a broken server could claim gigabytes
+    if length <= 0 || length as usize > MAX_PACKET_SIZE {
+        return Err(MinecraftError::Protocol(format!(
+            "Invalid packet length: {length}"
+        )));
+    }
+
+    let mut packet = vec![0; length as usize];
+    stream.read_exact(&mut packet).await?;
+
+    let mut body = &packet[..];
+    let packet_id = PacketReader::read_varint(&mut body)?;
+    if packet_id != expected_id {
+        return Err(MinecraftError::Protocol(format!(
+            "Expected packet 0x{expected_id:02X}, got 0x{packet_id:02X}"
+        )));
+    }
+
+    Ok(body.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::dns::SrvTarget;
+    use crate::server::Server;
+    use crate::text::Component;
+
+    #[tokio::test]
+    async fn test_ping_own_server() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            motd: Component::from_legacy("&aHello &bWorld"),
+            ..Default::default()
+        };
+        let mut server = Server::new(config).await.unwrap();
+        let address = server.local_addr().unwrap();
+        let running = tokio::spawn(async move { server.run().await });
+
+        let status = ping(&address.to_string()).await.unwrap();
+        running.abort();
+
+        assert_eq!(status.protocol, PROTOCOL_VERSION);
+        assert_eq!(status.online_players, 0);
+        assert_eq!(status.motd(), "Hello World");
+    }
+
+    /// Answers every lookup with the same record
+    struct FixedResolver(SrvTarget);
+
+    #[async_trait::async_trait]
+    impl SrvResolver for FixedResolver {
+        async fn resolve_minecraft(&self, host: &str) -> Result<Option<SrvTarget>> {
+            assert_eq!(host, "mc.example.invalid");
+            Ok(Some(self.0.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping_follows_srv_record() {
+        let config = ServerConfig {
+            address: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        };
+        let mut server = Server::new(config).await.unwrap();
+        let address = server.local_addr().unwrap();
+        let running = tokio::spawn(async move { server.run().await });
+
+        // the host alone has nothing listening, only the record leads to the server
+        let resolver = FixedResolver(SrvTarget {
+            host: "127.0.0.1".to_string(),
+            port: address.port(),
+        });
+        let status = ping_with_resolver("mc.example.invalid", &resolver)
+            .await
+            .unwrap();
+        running.abort();
+
+        assert_eq!(status.protocol, PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn test_split_address() {
+        let split = |address| split_address(address).unwrap();
+        assert_eq!(split("example.com"), ("example.com".to_string(), None));
+        assert_eq!(
+            split("example.com:25566"),
+            ("example.com".to_string(), Some(25566))
+        );
+        assert_eq!(split("127.0.0.1"), ("127.0.0.1".to_string(), None));
+        assert_eq!(split("127.0.0.1:80"), ("127.0.0.1".to_string(), Some(80)));
+        assert_eq!(split("::1"), ("::1".to_string(), None));
+        assert_eq!(split("[::1]"), ("::1".to_string(), None));
+        assert_eq!(split("[::1]:25566"), ("::1".to_string(), Some(25566)));
+        assert_eq!(
+            split("2001:db8::8a2e:370:7334"),
+            ("2001:db8::8a2e:370:7334".to_string(), None)
+        );
+        assert!(split_address("example.com:port").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_silent_server_times_out() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        // accepts the connection but never answers the status request
+        let server = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+        let result = query(
+            &address.to_string(),
+            &FixedResolver(SrvTarget {
+                host: String::new(),
+                port: 0,
+            }),
+            Duration::from_millis(100),
+        )
+        .await;
+        drop(server.await.unwrap());
+
+        assert!(
+            matches!(&result, Err(MinecraftError::Io(e)) if e.kind() == ErrorKind::TimedOut),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_huge_packet_length_is_rejected() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // i32::MAX as a varint, then nothing
+            socket
+                .write_all(&[0xFF, 0xFF, 0xFF, 0xFF, 0x07])
+                .await
+                .unwrap();
+            socket
+        });
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let result = read_packet(&mut stream, 0x00).await;
+        server.await.unwrap();
+
+        assert!(
+            matches!(result, Err(MinecraftError::Protocol(_))),
+            "{result:?}"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Text components, the formatted text used by chat, titles and disconnect reasons
+
+use crate::nbt::Nbt;
+use bytes::BytesMut;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    Text(String),
+    /// Looked up in the client's language file, `%s` placeholders are filled from `with`
+    Translate {
+        key: String,
+        with: Vec<Component>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub content: Content,
+    /// A named color like "yellow" or a "#rrggbb" hex color
+    pub color: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+    /// Appended after this component, inheriting its style
+    pub extra: Vec<Component>,
+}
+
+impl Component {
+    fn new(content: Content) -> Self {
+        Self {
+            content,
+            color: None,
+            bold: None,
+            italic: None,
+            underlined: None,
+            strikethrough: None,
+            obfuscated: None,
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(Content::Text(text.into()))
+    }
+
+    pub fn translate(key: impl Into<String>, with: Vec<Component>) -> Self {
+        Self::new(Content::Translate {
+            key: key.into(),
+            with,
+        })
+    }
+
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.bold = Some(bold);
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.italic = Some(italic);
+        self
+    }
+
+    /// Parses text with legacy '&' or '§' formatting codes, like "&aHello &lWorld"
+    ///
+    /// Every run of text between codes becomes an extra of an empty root
+    /// component. A color code resets the formatting, like it does in vanilla.
+    pub fn from_legacy(text: &str) -> Self {
+        let mut root = Component::text("");
+        let mut style = Component::text("");
+        let mut run = String::new();
+
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let code = match chars.peek() {
+                Some(&code) if c == '&' || c == '§' => code.to_ascii_lowercase(),
+                _ => {
+                    run.push(c);
+                    continue;
+                }
+            };
+
+            let color = legacy_color(code);
+            if color.is_none() && !"klmnor".contains(code) {
+                // not a formatting code, keep it as text
+                run.push(c);
+                continue;
+            }
+            chars.next();
+
+            if !run.is_empty() {
+                let mut part = style.clone();
+                part.content = Content::Text(std::mem::take(&mut run));
+                root.extra.push(part);
+            }
+
+            match code {
+                'k' => style.obfuscated = Some(true),
+                'l' => style.bold = Some(true),
+                'm' => style.strikethrough = Some(true),
+                'n' => style.underlined = Some(true),
+                'o' => style.italic = Some(true),
+                'r' => style = Component::text(""),
+                _ => {
+                    style = Component::text("");
+                    style.color = color.map(str::to_string);
+                }
+            }
+        }
+
+        if !run.is_empty() {
+            style.content = Content::Text(run);
+            root.extra.push(style);
+        }
+
+        root
+    }
+
+    pub fn append(mut self, component: Component) -> Self {
+        self.extra.push(component);
+        self
+    }
+
+    /// NBT form, as sent in Play and Configuration packets since 1.20.3
+    pub fn to_nbt(&self) -> Nbt {
+        let mut compound = BTreeMap::new();
+        match &self.content {
+            Content::Text(text) => {
+                compound.insert("text".to_string(), Nbt::String(text.clone()));
+            }
+            Content::Translate { key, with } => {
+                compound.insert("translate".to_string(), Nbt::String(key.clone()));
+                if !with.is_empty() {
+                    let with = with.iter().map(Component::to_nbt).collect();
+                    compound.insert("with".to_string(), Nbt::List(with));
+                }
+            }
+        }
+
+        if let Some(color) = &self.color {
+            compound.insert("color".to_string(), Nbt::String(color.clone()));
+        }
+        for (name, value) in self.decorations() {
+            if let Some(value) = value {
+                compound.insert(name.to_string(), Nbt::Byte(value as i8));
+            }
+        }
+        if !self.extra.is_empty() {
+            let extra = self.extra.iter().map(Component::to_nbt).collect();
+            compound.insert("extra".to_string(), Nbt::List(extra));
+        }
+
+        Nbt::Compound(compound)
+    }
+
+    /// JSON form, still used by the Login state and the status response
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        match &self.content {
+            Content::Text(text) => {
+                object.insert("text".to_string(), json!(text));
+            }
+            Content::Translate { key, with } => {
+                object.insert("translate".to_string(), json!(key));
+                if !with.is_empty() {
+                    let with = with.iter().map(Component::to_json).collect();
+                    object.insert("with".to_string(), Value::Array(with));
+                }
+            }
+        }
+
+        if let Some(color) = &self.color {
+            object.insert("color".to_string(), json!(color));
+        }
+        for (name, value) in self.decorations() {
+            if let Some(value) = value {
+                object.insert(name.to_string(), json!(value));
+            }
+        }
+        if !self.extra.is_empty() {
+            let extra = self.extra.iter().map(Component::to_json).collect();
+            object.insert("extra".to_string(), Value::Array(extra));
+        }
+
+        Value::Object(object)
+    }
+
+    fn decorations(&self) -> [(&'static str, Option<bool>); 5] {
+        [
+            ("bold", self.bold),
+            ("italic", self.italic),
+            ("underlined", self.underlined),
+            ("strikethrough", self.strikethrough),
+            ("obfuscated", self.obfuscated),
+        ]
+    }
+
+    /// Writes the component as a nameless NBT tag
+    pub fn write(&self, buf: &mut BytesMut) {
+        self.to_nbt().write_nameless(buf);
+    }
+}
+
+/// Named color of a legacy color code
+fn legacy_color(code: char) -> Option<&'static str> {
+    let color = match code {
+        '0' => "black",
+        '1' => "dark_blue",
+        '2' => "dark_green",
+        '3' => "dark_aqua",
+        '4' => "dark_red",
+        '5' => "dark_purple",
+        '6' => "gold",
+        '7' => "gray",
+        '8' => "dark_gray",
+        '9' => "blue",
+        'a' => "green",
+        'b' => "aqua",
+        'c' => "red",
+        'd' => "light_purple",
+        'e' => "yellow",
+        'f' => "white",
+        _ => return None,
+    };
+
+    Some(color)
+}
+
+impl From<&str> for Component {
+    fn from(text: &str) -> Self {
+        Component::text(text)
+    }
+}
+
+impl From<String> for Component {
+    fn from(text: String) -> Self {
+        Component::text(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component_nbt_and_json() {
+        let component =
+            Component::translate("multiplayer.player.joined", vec![Component::text("Notch")])
+                .color("yellow");
+
+        assert_eq!(
+            component.to_json(),
+            json!({
+                "translate": "multiplayer.player.joined",
+                "with": [{ "text": "Notch" }],
+                "color": "yellow"
+            })
+        );
+
+        let nbt = component.to_nbt();
+        assert_eq!(
+            nbt.get("translate").and_then(Nbt::as_str),
+            Some("multiplayer.player.joined")
+        );
+        assert_eq!(nbt.get("color").and_then(Nbt::as_str), Some("yellow"));
+        let with = nbt.get("with").and_then(Nbt::as_list).unwrap();
+        assert_eq!(with[0].get("text").and_then(Nbt::as_str), Some("Notch"));
+    }
+
+    #[test]
+    fn test_from_legacy() {
+        assert_eq!(
+            Component::from_legacy("&aHello &bWorld").to_json(),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "Hello ", "color": "green" },
+                    { "text": "World", "color": "aqua" }
+                ]
+            })
+        );
+
+        // formatting stacks until the next color, unknown codes stay text
+        assert_eq!(
+            Component::from_legacy("§lBold &cRed &zok").to_json(),
+            json!({
+                "text": "",
+                "extra": [
+                    { "text": "Bold ", "bold": true },
+                    { "text": "Red &zok", "color": "red" }
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Velocity modern forwarding, where the proxy passes on the player's real
+//! address and profile in a login plugin message signed with a shared secret
+
+use crate::error::{MinecraftError, Result};
+use crate::packet::reader::PacketReader;
+use crate::player::ProfileProperty;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+pub const PLAYER_INFO_CHANNEL: &str = "velocity:player_info";
+/// The forwarding version we ask for, without the chat signing key
+pub const MODERN_DEFAULT_VERSION: u8 = 1;
+
+const SIGNATURE_SIZE: usize = 32;
+
+/// What the proxy knows about the player
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardedPlayer {
+    pub address: IpAddr,
+    pub uuid: Uuid,
+    pub username: String,
+    pub properties: Vec<ProfileProperty>,
+}
+
+// Signature             32 bytes, HMAC-SHA256 of everything after it
+// Version               varint
+// Address               string
+// UUID                  uuid
+// Username              string (16)
+// Property Count        varint
+//   Name                string
+//   Value               string
+//   Is Signed           boolean
+//   Signature           optional string
+//
+/// Checks the signature on a player info response and decodes it
+pub fn verify_player_info(secret: &[u8], data: &[u8]) -> Result<ForwardedPlayer> {
+    if data.len() < SIGNATURE_SIZE {
+        return Err(MinecraftError::Protocol(
+            "Velocity forwarding data is missing its signature".into(),
+        ));
+    }
+    let (signature, mut buf) = data.split_at(SIGNATURE_SIZE);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)
+        .map_err(|e| MinecraftError::Protocol(format!("Invalid forwarding secret: {e}")))?;
+    mac.update(buf);
+    mac.verify_slice(signature).map_err(|_| {
+        MinecraftError::Protocol("Velocity forwarding signature does not match".into())
+    })?;
+
+    let version = PacketReader::read_varint(&mut buf)?;
+    if version < MODERN_DEFAULT_VERSION as i32 {
+        return Err(MinecraftError::Protocol(format!(
+            "Unsupported Velocity forwarding version: {version}"
+        )));
+    }
+
+    let address = PacketReader::read_string(&mut buf)?;
+    let address = address
+        .parse()
+        .map_err(|_| MinecraftError::Protocol(format!("Invalid forwarded address: {address}")))?;
+    let uuid = PacketReader::read_uuid(&mut buf)?;
+    let username = PacketReader::read_string_capped(&mut buf, 16)?;
+
+    let property_count = PacketReader::read_varint(&mut buf)?;
+    let mut properties = Vec::new();
+    for _ in 0..property_count {
+        let name = PacketReader::read_string(&mut buf)?;
+        let value = PacketReader::read_string(&mut buf)?;
+        let signature = if PacketReader::read_boolean(&mut buf)? {
+            Some(PacketReader::read_string(&mut buf)?)
+        } else {
+            None
+        };
+        properties.push(ProfileProperty {
+            name,
+            value,
+            signature,
+        });
+    }
+
+    Ok(ForwardedPlayer {
+        address,
+        uuid,
+        username,
+        properties,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{BufMut, BytesMut};
+
+    fn sign(secret: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(payload);
+        let mut data = mac.finalize().into_bytes().to_vec();
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn test_verify_player_info() {
+        let uuid = Uuid::from_u128(0x1234);
+        let mut payload = BytesMut::new();
+        PacketReader::write_varint(&mut payload, 1);
+        PacketReader::write_string(&mut payload, "203.0.113.7");
+        payload.put_u128(uuid.as_u128());
+        PacketReader::write_string(&mut payload, "Steve");
+        PacketReader::write_varint(&mut payload, 1);
+        PacketReader::write_string(&mut payload, "textures");
+        PacketReader::write_string(&mut payload, "e30=");
+        payload.put_u8(1);
+        PacketReader::write_string(&mut payload, "c2ln");
+
+        let data = sign(b"secret", &payload);
+        assert_eq!(
+            verify_player_info(b"secret", &data).unwrap(),
+            ForwardedPlayer {
+                address: "203.0.113.7".parse().unwrap(),
+                uuid,
+                username: "Steve".to_string(),
+                properties: vec![ProfileProperty {
+                    name: "textures".to_string(),
+                    value: "e30=".to_string(),
+                    signature: Some("c2ln".to_string()),
+                }],
+            }
+        );
+
+        // signed with another secret
+        assert!(verify_player_info(b"other", &data).is_err());
+
+        // tampered with after signing
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(verify_player_info(b"secret", &tampered).is_err());
+
+        // not signed at all
+        assert!(verify_player_info(b"secret", &payload[..8]).is_err());
+    }
+}
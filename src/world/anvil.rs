@@ -0,0 +1,471 @@
+use super::chunk::{Chunk, BIOMES_PER_SECTION, BLOCKS_PER_SECTION, SECTION_HEIGHT};
+use super::provider::{ChunkProvider, BEDROCK, DIRT, GRASS_BLOCK};
+use super::{AIR, STONE};
+use crate::error::{MinecraftError, Result};
+use crate::nbt::Nbt;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+pub const SECTOR_SIZE: u64 = 4096;
+pub const REGION_WIDTH: i32 = 32;
+
+// Chunk compression schemes
+pub const GZIP_COMPRESSION: u8 = 1;
+pub const ZLIB_COMPRESSION: u8 = 2;
+pub const NO_COMPRESSION: u8 = 3;
+// set on the compression byte when the chunk is stored in a separate .mcc file
+const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
+
+/// Loads chunks from the Anvil region files (`r.<x>.<z>.mca`) of a vanilla world
+///
+/// Region files store blocks and biomes by name. Blocks are loaded in the
+/// default state of their block, unknown blocks become stone and unknown
+/// biomes become biome 0.
+pub struct AnvilChunkProvider {
+    region_dir: PathBuf,
+    min_y: i32,
+    height: i32,
+    block_states: HashMap<String, i32>,
+    biomes: HashMap<String, i32>,
+}
+
+impl AnvilChunkProvider {
+    pub fn new(region_dir: impl Into<PathBuf>) -> Self {
+        let block_states = [
+            ("minecraft:air", AIR),
+            ("minecraft:cave_air", AIR),
+            ("minecraft:void_air", AIR),
+            ("minecraft:stone", STONE),
+            ("minecraft:granite", 2),
+            ("minecraft:polished_granite", 3),
+            ("minecraft:diorite", 4),
+            ("minecraft:polished_diorite", 5),
+            ("minecraft:andesite", 6),
+            ("minecraft:polished_andesite", 7),
+            ("minecraft:grass_block", GRASS_BLOCK),
+            ("minecraft:dirt", DIRT),
+            ("minecraft:coarse_dirt", 11),
+            ("minecraft:podzol", 13),
+            ("minecraft:cobblestone", 14),
+            ("minecraft:oak_planks", 15),
+            ("minecraft:bedrock", BEDROCK),
+            ("minecraft:water", 80),
+            ("minecraft:lava", 96),
+        ]
+        .into_iter()
+        .map(|(name, id)| (name.to_string(), id))
+        .collect();
+
+        Self {
+            region_dir: region_dir.into(),
+            min_y: -64,
+            height: 384,
+            block_states,
+            biomes: HashMap::new(),
+        }
+    }
+
+    /// Replaces the table mapping block names to block state ids
+    pub fn with_block_states(mut self, block_states: HashMap<String, i32>) -> Self {
+        self.block_states = block_states;
+        self
+    }
+
+    /// Sets the table mapping biome names to ids in the biome registry
+    pub fn with_biomes(mut self, biomes: HashMap<String, i32>) -> Self {
+        self.biomes = biomes;
+        self
+    }
+
+    /// Reads a chunk, `None` when it was never generated
+    pub fn read_chunk(&self, cx: i32, cz: i32) -> Result<Option<Chunk>> {
+        match self.read_chunk_nbt(cx, cz)? {
+            Some(nbt) => Ok(Some(self.parse_chunk(cx, cz, &nbt)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Region files start with a table of 1024 chunk locations
+    // offset        3 bytes (in sectors, from the start of the file)
+    // sector count  1 byte
+    //
+    // followed by 1024 timestamps. Each chunk then looks like
+    // length        int (includes the compression byte)
+    // compression   byte
+    // data          compressed NBT
+    fn read_chunk_nbt(&self, cx: i32, cz: i32) -> Result<Option<Nbt>> {
+        let path = self.region_dir.join(format!(
+            "r.{}.{}.mca",
+            cx.div_euclid(REGION_WIDTH),
+            cz.div_euclid(REGION_WIDTH)
+        ));
+
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let index = cx.rem_euclid(REGION_WIDTH) + cz.rem_euclid(REGION_WIDTH) * REGION_WIDTH;
+        let mut location = [0; 4];
+        file.seek(SeekFrom::Start(index as u64 * 4))?;
+        file.read_exact(&mut location)?;
+
+        let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as u64;
+        let sector_count = location[3];
+        if offset == 0 && sector_count == 0 {
+            return Ok(None);
+        }
+
+        let mut header = [0; 5];
+        file.seek(SeekFrom::Start(offset * SECTOR_SIZE))?;
+        file.read_exact(&mut header)?;
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let compression = header[4];
+        if length == 0 || length > sector_count as u64 * SECTOR_SIZE {
+            return Err(MinecraftError::Nbt(format!(
+                "Invalid length {length} for chunk {cx}, {cz}"
+            )));
+        }
+
+        if compression & EXTERNAL_CHUNK_FLAG != 0 {
+            return Err(MinecraftError::Nbt(format!(
+                "Chunk {cx}, {cz} is stored externally, which is unsupported"
+            )));
+        }
+
+        let mut compressed = vec![0; length as usize - 1];
+        file.read_exact(&mut compressed)?;
+
+        let mut data = Vec::new();
+        match compression {
+            GZIP_COMPRESSION => {
+                GzDecoder::new(&compressed[..]).read_to_end(&mut data)?;
+            }
+            ZLIB_COMPRESSION => {
+                ZlibDecoder::new(&compressed[..]).read_to_end(&mut data)?;
+            }
+            NO_COMPRESSION => data = compressed,
+            _ => {
+                return Err(MinecraftError::Nbt(format!(
+                    "Unsupported chunk compression: {compression}"
+                )))
+            }
+        }
+
+        let (_, nbt) = Nbt::read_named(&mut &data[..])?;
+        debug!(cx, cz, bytes = data.len(), "Read chunk from region file");
+
+        Ok(Some(nbt))
+    }
+
+    fn parse_chunk(&self, cx: i32, cz: i32, nbt: &Nbt) -> Result<Chunk> {
+        let mut chunk = Chunk::new(cx, cz, self.min_y, self.height);
+        let min_section = self.min_y.div_euclid(SECTION_HEIGHT);
+
+        let sections = nbt
+            .get("sections")
+            .and_then(Nbt::as_list)
+            .ok_or_else(|| MinecraftError::Nbt("Chunk is missing its sections".into()))?;
+
+        for section_nbt in sections {
+            let y = section_nbt
+                .get("Y")
+                .and_then(Nbt::as_i32)
+                .ok_or_else(|| MinecraftError::Nbt("Section is missing its Y".into()))?;
+
+            // vanilla keeps a lighting-only section above and below the world
+            let Some(section) = usize::try_from(y - min_section)
+                .ok()
+                .and_then(|index| chunk.sections.get_mut(index))
+            else {
+                continue;
+            };
+
+            if let Some(block_states) = section_nbt.get("block_states") {
+                let palette = block_states
+                    .get("palette")
+                    .and_then(Nbt::as_list)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|entry| {
+                        let name = entry.get("Name").and_then(Nbt::as_str).unwrap_or_default();
+                        self.block_states.get(name).copied().unwrap_or(STONE)
+                    })
+                    .collect::<Vec<_>>();
+
+                section.blocks = unpack_palette(
+                    &palette,
+                    block_states.get("data").and_then(Nbt::as_long_array),
+                    BLOCKS_PER_SECTION,
+                    4,
+                )?;
+            }
+
+            if let Some(biomes) = section_nbt.get("biomes") {
+                let palette = biomes
+                    .get("palette")
+                    .and_then(Nbt::as_list)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|entry| {
+                        let name = entry.as_str().unwrap_or_default();
+                        self.biomes.get(name).copied().unwrap_or(0)
+                    })
+                    .collect::<Vec<_>>();
+
+                section.biomes = unpack_palette(
+                    &palette,
+                    biomes.get("data").and_then(Nbt::as_long_array),
+                    BIOMES_PER_SECTION,
+                    1,
+                )?;
+            }
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl ChunkProvider for AnvilChunkProvider {
+    fn get_chunk(&self, cx: i32, cz: i32) -> Chunk {
+        match self.read_chunk(cx, cz) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => Chunk::new(cx, cz, self.min_y, self.height),
+            Err(e) => {
+                warn!(cx, cz, error = %e, "Failed to read chunk, sending it empty");
+                Chunk::new(cx, cz, self.min_y, self.height)
+            }
+        }
+    }
+}
+
+/// Expands palette indices packed into longs, entries never span two longs
+fn unpack_palette(
+    palette: &[i32],
+    data: Option<&[i64]>,
+    entries: usize,
+    min_bits: u32,
+) -> Result<Vec<i32>> {
+    let Some(&first) = palette.first() else {
+        return Err(MinecraftError::Nbt("Palette is empty".into()));
+    };
+
+    // a single entry palette has no data, everything is that entry
+    let data = match data {
+        Some(data) if palette.len() > 1 => data,
+        _ => return Ok(vec![first; entries]),
+    };
+
+    let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(min_bits);
+    let per_long = (64 / bits) as usize;
+    let mask = (1u64 << bits) - 1;
+
+    if data.len() < entries.div_ceil(per_long) {
+        return Err(MinecraftError::Nbt(format!(
+            "Palette data too short: {} longs for {entries} entries",
+            data.len()
+        )));
+    }
+
+    (0..entries)
+        .map(|i| {
+            let long = data[i / per_long] as u64;
+            let index = (long >> ((i % per_long) as u32 * bits)) & mask;
+            palette
+                .get(index as usize)
+                .copied()
+                .ok_or_else(|| MinecraftError::Nbt(format!("Palette index {index} out of bounds")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::collections::BTreeMap;
+    use std::io::Write;
+
+    fn compound(children: Vec<(&str, Nbt)>) -> Nbt {
+        Nbt::Compound(
+            children
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    fn palette(names: &[&str], as_blocks: bool) -> Nbt {
+        Nbt::List(
+            names
+                .iter()
+                .map(|name| {
+                    let name = Nbt::String(name.to_string());
+                    if as_blocks {
+                        compound(vec![("Name", name)])
+                    } else {
+                        name
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn pack(indices: &[u64], bits: u32) -> Vec<i64> {
+        let per_long = (64 / bits) as usize;
+        indices
+            .chunks(per_long)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |long, (i, &index)| long | index << (i as u32 * bits))
+                    as i64
+            })
+            .collect()
+    }
+
+    /// Writes a region file holding the given chunks, one sector each
+    fn write_region(path: &std::path::Path, chunks: &[(i32, i32, u8, Nbt)]) {
+        let mut header = vec![0u8; 2 * SECTOR_SIZE as usize];
+        let mut sectors = Vec::new();
+
+        for (i, (cx, cz, compression, nbt)) in chunks.iter().enumerate() {
+            let mut raw = BytesMut::new();
+            nbt.write_named(&mut raw, "");
+
+            let data = match *compression {
+                GZIP_COMPRESSION => {
+                    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(&raw).unwrap();
+                    encoder.finish().unwrap()
+                }
+                _ => {
+                    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(&raw).unwrap();
+                    encoder.finish().unwrap()
+                }
+            };
+
+            let mut sector = vec![0u8; SECTOR_SIZE as usize];
+            sector[..4].copy_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+            sector[4] = *compression;
+            sector[5..5 + data.len()].copy_from_slice(&data);
+            sectors.extend_from_slice(&sector);
+
+            let index = (cx.rem_euclid(32) + cz.rem_euclid(32) * 32) as usize;
+            let offset = 2 + i as u32;
+            header[index * 4..index * 4 + 3].copy_from_slice(&offset.to_be_bytes()[1..]);
+            header[index * 4 + 3] = 1;
+        }
+
+        header.extend_from_slice(&sectors);
+        std::fs::write(path, header).unwrap();
+    }
+
+    #[test]
+    fn test_read_fixture_region() {
+        let dir = std::env::temp_dir().join(format!("octavia-anvil-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // bottom section: a bedrock floor with two layers of stone above it
+        let blocks: Vec<u64> = (0..4096)
+            .map(|i| match i / 256 {
+                0 => 2,
+                1 | 2 => 1,
+                _ => 0,
+            })
+            .collect();
+        // the bottom row of biome cells is desert
+        let biomes: Vec<u64> = (0..64).map(|i| (i < 16) as u64).collect();
+
+        let bottom = compound(vec![
+            ("Y", Nbt::Byte(-4)),
+            (
+                "block_states",
+                compound(vec![
+                    (
+                        "palette",
+                        palette(
+                            &["minecraft:air", "minecraft:stone", "minecraft:bedrock"],
+                            true,
+                        ),
+                    ),
+                    ("data", Nbt::LongArray(pack(&blocks, 4))),
+                ]),
+            ),
+            (
+                "biomes",
+                compound(vec![
+                    (
+                        "palette",
+                        palette(&["minecraft:plains", "minecraft:desert"], false),
+                    ),
+                    ("data", Nbt::LongArray(pack(&biomes, 1))),
+                ]),
+            ),
+        ]);
+        let filled = compound(vec![
+            ("Y", Nbt::Byte(-3)),
+            (
+                "block_states",
+                compound(vec![("palette", palette(&["minecraft:dirt"], true))]),
+            ),
+        ]);
+        let chunk_nbt = compound(vec![
+            ("xPos", Nbt::Int(1)),
+            ("zPos", Nbt::Int(2)),
+            ("sections", Nbt::List(vec![bottom, filled])),
+        ]);
+        let gzip_nbt = compound(vec![(
+            "sections",
+            Nbt::List(vec![compound(vec![
+                ("Y", Nbt::Byte(0)),
+                (
+                    "block_states",
+                    compound(vec![("palette", palette(&["minecraft:bedrock"], true))]),
+                ),
+            ])]),
+        )]);
+
+        write_region(
+            &dir.join("r.0.0.mca"),
+            &[
+                (1, 2, ZLIB_COMPRESSION, chunk_nbt),
+                (0, 0, GZIP_COMPRESSION, gzip_nbt),
+            ],
+        );
+
+        let provider = AnvilChunkProvider::new(&dir).with_biomes(HashMap::from([
+            ("minecraft:plains".to_string(), 3),
+            ("minecraft:desert".to_string(), 7),
+        ]));
+
+        let chunk = provider.read_chunk(1, 2).unwrap().unwrap();
+        assert_eq!(chunk.get_block(0, -64, 0), BEDROCK);
+        assert_eq!(chunk.get_block(15, -63, 9), STONE);
+        assert_eq!(chunk.get_block(4, -62, 4), STONE);
+        assert_eq!(chunk.get_block(4, -61, 4), AIR);
+        assert_eq!(chunk.get_block(8, -48, 8), DIRT);
+        assert_eq!(chunk.get_block(8, -32, 8), AIR);
+        assert_eq!(chunk.sections[0].biomes[0], 7);
+        assert_eq!(chunk.sections[0].biomes[16], 3);
+
+        let chunk = provider.read_chunk(0, 0).unwrap().unwrap();
+        assert_eq!(chunk.get_block(3, 0, 3), BEDROCK);
+
+        // never generated, or outside of any region file on disk
+        assert!(provider.read_chunk(5, 5).unwrap().is_none());
+        assert!(provider.read_chunk(-1, 0).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use super::AIR;
+
+pub const CHUNK_WIDTH: i32 = 16;
+pub const SECTION_HEIGHT: i32 = 16;
+pub const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+// biomes are stored per 4x4x4 cell
+pub const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkSection {
+    // indexed by (y * 16 + z) * 16 + x
+    pub blocks: Vec<i32>,
+    // indexed by (y * 4 + z) * 4 + x
+    pub biomes: Vec<i32>,
+}
+
+impl ChunkSection {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![AIR; BLOCKS_PER_SECTION],
+            biomes: vec![0; BIOMES_PER_SECTION],
+        }
+    }
+
+    /// Number of blocks that aren't air
+    pub fn block_count(&self) -> i16 {
+        self.blocks.iter().filter(|&&block| block != AIR).count() as i16
+    }
+}
+
+impl Default for ChunkSection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A 16 block wide column of sections
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    pub min_y: i32,
+    pub sections: Vec<ChunkSection>,
+}
+
+impl Chunk {
+    pub fn new(x: i32, z: i32, min_y: i32, height: i32) -> Self {
+        Self {
+            x,
+            z,
+            min_y,
+            sections: vec![ChunkSection::new(); (height / SECTION_HEIGHT) as usize],
+        }
+    }
+
+    pub fn height(&self) -> i32 {
+        self.sections.len() as i32 * SECTION_HEIGHT
+    }
+
+    /// Block state at chunk-local x/z and world y, air outside the chunk's height
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> i32 {
+        match self.block_index(x, y, z) {
+            Some((section, index)) => self.sections[section].blocks[index],
+            None => AIR,
+        }
+    }
+
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_state: i32) {
+        if let Some((section, index)) = self.block_index(x, y, z) {
+            self.sections[section].blocks[index] = block_state;
+        }
+    }
+
+    fn block_index(&self, x: i32, y: i32, z: i32) -> Option<(usize, usize)> {
+        let local_y = y - self.min_y;
+        if local_y < 0 || local_y >= self.height() {
+            return None;
+        }
+
+        let section = (local_y / SECTION_HEIGHT) as usize;
+        let index =
+            (((local_y % SECTION_HEIGHT) * 16 + z.rem_euclid(16)) * 16 + x.rem_euclid(16)) as usize;
+
+        Some((section, index))
+    }
+}
@@ -0,0 +1,155 @@
+//! Paces the chunks sent to a player, so a client that just joined isn't
+//! flooded with more chunks than it can build meshes for
+
+use crate::packet::play::{write_chunk_batch_finished_packet, write_chunk_batch_start_packet};
+use bytes::BytesMut;
+use std::collections::VecDeque;
+
+/// Rate used until the client reports its own, same as vanilla
+pub const INITIAL_CHUNKS_PER_TICK: f32 = 9.0;
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+/// Batches in flight once the client has acknowledged its first one, before
+/// that only one is sent so its reply can set the rate
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+/// Queue of Chunk Data packets for one player, sent in batches at the rate
+/// the client asks for in Chunk Batch Received
+#[derive(Debug)]
+pub struct ChunkSender {
+    pending: VecDeque<BytesMut>,
+    chunks_per_tick: f32,
+    // chunks that may be sent, grows by the rate every tick
+    quota: f32,
+    unacknowledged_batches: u32,
+    max_unacknowledged_batches: u32,
+}
+
+impl ChunkSender {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            chunks_per_tick: INITIAL_CHUNKS_PER_TICK,
+            quota: 0.0,
+            unacknowledged_batches: 0,
+            max_unacknowledged_batches: 1,
+        }
+    }
+
+    /// Queues an encoded Chunk Data packet
+    pub fn queue(&mut self, chunk_data: BytesMut) {
+        self.pending.push_back(chunk_data);
+    }
+
+    /// Chunks queued but not sent yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn chunks_per_tick(&self) -> f32 {
+        self.chunks_per_tick
+    }
+
+    /// Called once a tick, returns the packets of the next batch wrapped in
+    /// Chunk Batch Start and Finished, or nothing while the client is behind
+    pub fn next_batch(&mut self) -> Vec<BytesMut> {
+        // the quota can't be saved up over idle ticks for one huge batch
+        self.quota = (self.quota + self.chunks_per_tick).min(self.chunks_per_tick.max(1.0));
+        if self.pending.is_empty()
+            || self.quota < 1.0
+            || self.unacknowledged_batches >= self.max_unacknowledged_batches
+        {
+            return Vec::new();
+        }
+
+        let count = (self.quota as usize).min(self.pending.len());
+        self.quota -= count as f32;
+        self.unacknowledged_batches += 1;
+
+        let mut packets = Vec::with_capacity(count + 2);
+        packets.push(write_chunk_batch_start_packet());
+        packets.extend(self.pending.drain(..count));
+        packets.push(write_chunk_batch_finished_packet(count as i32));
+        packets
+    }
+
+    /// Handles Chunk Batch Received, the client's chunks per tick for the
+    /// following batches
+    pub fn batch_received(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged_batches = self.unacknowledged_batches.saturating_sub(1);
+        self.max_unacknowledged_batches = MAX_UNACKNOWLEDGED_BATCHES;
+        // NaN from a misbehaving client is treated as the slowest rate
+        self.chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+    }
+}
+
+impl Default for ChunkSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: u8) -> BytesMut {
+        BytesMut::from(&[0x27, id][..])
+    }
+
+    #[test]
+    fn test_batch_is_framed_by_start_and_finished() {
+        let mut sender = ChunkSender::new();
+        for id in 0..3 {
+            sender.queue(chunk(id));
+        }
+
+        assert_eq!(
+            sender.next_batch(),
+            vec![
+                write_chunk_batch_start_packet(),
+                chunk(0),
+                chunk(1),
+                chunk(2),
+                write_chunk_batch_finished_packet(3),
+            ]
+        );
+        assert_eq!(sender.pending(), 0);
+    }
+
+    #[test]
+    fn test_batches_follow_the_client_rate() {
+        let mut sender = ChunkSender::new();
+        for id in 0..20 {
+            sender.queue(chunk(id));
+        }
+
+        // the first batch goes out at the initial rate, then the client has to answer
+        assert_eq!(sender.next_batch().len(), 9 + 2);
+        assert!(sender.next_batch().is_empty());
+
+        sender.batch_received(2.0);
+        assert_eq!(sender.chunks_per_tick(), 2.0);
+        assert_eq!(sender.next_batch().len(), 2 + 2);
+        // later batches don't wait for their acknowledgment
+        assert_eq!(sender.next_batch().len(), 2 + 2);
+
+        // a fraction of a chunk per tick sends one every few ticks
+        sender.batch_received(0.5);
+        assert!(sender.next_batch().is_empty());
+        assert_eq!(sender.next_batch().len(), 1 + 2);
+    }
+
+    #[test]
+    fn test_invalid_rates_are_clamped() {
+        let mut sender = ChunkSender::new();
+        sender.batch_received(f32::NAN);
+        assert_eq!(sender.chunks_per_tick(), MIN_CHUNKS_PER_TICK);
+        sender.batch_received(1000.0);
+        assert_eq!(sender.chunks_per_tick(), MAX_CHUNKS_PER_TICK);
+    }
+}
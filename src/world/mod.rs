@@ -0,0 +1,536 @@
+pub mod anvil;
+pub mod chunk;
+pub mod chunk_sender;
+pub mod position;
+pub mod provider;
+
+use crate::command::{CommandHandler, CommandRegistry};
+use crate::metrics::Metrics;
+use crate::packet::play::{write_system_chat_packet, write_weather_packets};
+use crate::scheduler::Scheduler;
+use crate::text::Component;
+use bytes::BytesMut;
+use chunk::{Chunk, CHUNK_WIDTH};
+pub use position::Position;
+use provider::{ChunkProvider, FlatChunkProvider};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tokio::sync::Notify;
+use tracing::debug;
+
+// Block state ids
+pub const AIR: i32 = 0;
+pub const STONE: i32 = 1;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    Peaceful,
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn id(self) -> u8 {
+        match self {
+            Difficulty::Peaceful => 0,
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Difficulty::Peaceful),
+            1 => Some(Difficulty::Easy),
+            2 => Some(Difficulty::Normal),
+            3 => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+}
+
+/// Where the world border is and how it warns players about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// Diameter the border starts moving from, equal to `new_diameter` for a still border
+    pub old_diameter: f64,
+    pub new_diameter: f64,
+    /// Milliseconds the border takes to go from the old to the new diameter
+    pub speed: i64,
+    /// Nether portals can't send players further than this many blocks from the center
+    pub portal_teleport_boundary: i32,
+    /// Distance in blocks at which the screen starts turning red
+    pub warning_blocks: i32,
+    /// Seconds before a moving border reaches the player that the warning starts
+    pub warning_time: i32,
+}
+
+impl Default for WorldBorder {
+    /// The vanilla border, as far out as the world goes
+    fn default() -> Self {
+        Self {
+            center_x: 0.0,
+            center_z: 0.0,
+            old_diameter: 59_999_968.0,
+            new_diameter: 59_999_968.0,
+            speed: 0,
+            portal_teleport_boundary: 29_999_984,
+            warning_blocks: 5,
+            warning_time: 15,
+        }
+    }
+}
+
+/// Where an entity is and which way it's facing
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EntityLocation {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Rain and thunder, levels go from 0.0 (none) to 1.0 (full strength)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Weather {
+    pub raining: bool,
+    pub rain_level: f32,
+    pub thunder_level: f32,
+}
+
+impl Weather {
+    pub fn clear() -> Self {
+        Self::default()
+    }
+
+    pub fn rain() -> Self {
+        Self {
+            raining: true,
+            rain_level: 1.0,
+            thunder_level: 0.0,
+        }
+    }
+
+    pub fn thunder() -> Self {
+        Self {
+            raining: true,
+            rain_level: 1.0,
+            thunder_level: 1.0,
+        }
+    }
+}
+
+/// Hash of the world seed sent in Login (Play) and Respawn, the first 8 bytes
+/// of the SHA-256 of the seed, so clients can't recover the seed itself
+pub fn hashed_seed(seed: i64) -> i64 {
+    let hash = Sha256::digest(seed.to_le_bytes());
+    i64::from_le_bytes(hash[..8].try_into().unwrap())
+}
+
+/// What other connections can ask a player's connection to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutboundMessage {
+    /// An unframed packet (packet id + body), the owning connection frames it when writing
+    Packet(BytesMut),
+    /// Disconnect the player with this reason
+    Kick(String),
+}
+
+/// Sending side of a connection's outbound queue
+#[derive(Debug, Clone)]
+pub struct Outbound {
+    tx: Sender<OutboundMessage>,
+    // notified when a message didn't fit, the connection then drops the client
+    overflow: Arc<Notify>,
+}
+
+impl Outbound {
+    /// Creates a queue holding at most `capacity` messages
+    pub fn channel(capacity: usize) -> (Self, Receiver<OutboundMessage>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let outbound = Self {
+            tx,
+            overflow: Arc::new(Notify::new()),
+        };
+        (outbound, rx)
+    }
+
+    /// Queues a message without waiting. A full queue means the client isn't
+    /// reading what we send, so the message is dropped and the connection told
+    /// to give up on the client instead of buffering forever.
+    pub fn send(&self, message: OutboundMessage) {
+        match self.tx.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.overflow.notify_one(),
+            // the receiver is gone when the connection is shutting down
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Completes once a message was dropped because the queue was full
+    pub async fn overflowed(&self) {
+        self.overflow.notified().await
+    }
+}
+
+pub struct PlayerHandle {
+    pub username: String,
+    pub outbound: Outbound,
+}
+
+/// Hands out entity ids, they're never reused while the server runs
+pub struct EntityIdAllocator {
+    next: AtomicI32,
+}
+
+impl Default for EntityIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntityIdAllocator {
+    pub fn new() -> Self {
+        Self {
+            next: AtomicI32::new(1),
+        }
+    }
+
+    pub fn next(&self) -> i32 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// State shared between every connection of a server
+pub struct SharedWorld {
+    entity_ids: EntityIdAllocator,
+    players: Mutex<HashMap<i32, PlayerHandle>>,
+    provider: Box<dyn ChunkProvider>,
+    // chunks that have been generated, keyed by chunk x/z
+    chunks: Mutex<HashMap<(i32, i32), Chunk>>,
+    difficulty: Mutex<Difficulty>,
+    scheduler: Mutex<Scheduler>,
+    commands: Mutex<CommandRegistry>,
+    weather: Mutex<Weather>,
+    // set when the weather changed, the next tick tells every player
+    weather_changed: AtomicBool,
+    // last known location of entities other than players, by entity id
+    entity_locations: Mutex<HashMap<i32, EntityLocation>>,
+    metrics: Metrics,
+}
+
+impl Default for SharedWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedWorld {
+    pub fn new() -> Self {
+        Self::with_provider(Box::new(FlatChunkProvider::default()))
+    }
+
+    pub fn with_provider(provider: Box<dyn ChunkProvider>) -> Self {
+        Self {
+            entity_ids: EntityIdAllocator::new(),
+            players: Mutex::new(HashMap::new()),
+            provider,
+            chunks: Mutex::new(HashMap::new()),
+            difficulty: Mutex::new(Difficulty::default()),
+            scheduler: Mutex::new(Scheduler::new()),
+            commands: Mutex::new(CommandRegistry::new()),
+            weather: Mutex::new(Weather::default()),
+            entity_locations: Mutex::new(HashMap::new()),
+            metrics: Metrics::new(),
+            weather_changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Allocator for the ids of every entity in the world, players included
+    pub fn entity_ids(&self) -> &EntityIdAllocator {
+        &self.entity_ids
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Tasks run by the server's tick loop, the lock is held while they run
+    pub fn scheduler(&self) -> MutexGuard<'_, Scheduler> {
+        self.scheduler.lock().unwrap()
+    }
+
+    /// Runs one server tick
+    pub fn tick(&self) {
+        self.scheduler().tick();
+
+        if self.weather_changed.swap(false, Ordering::Relaxed) {
+            for packet in write_weather_packets(&self.weather()) {
+                self.broadcast(&packet);
+            }
+        }
+    }
+
+    pub fn weather(&self) -> Weather {
+        *self.weather.lock().unwrap()
+    }
+
+    /// Changes the weather, players see it change on the next tick
+    pub fn set_weather(&self, weather: Weather) {
+        *self.weather.lock().unwrap() = weather;
+        self.weather_changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Adds a command players can run and sends everyone the updated command tree
+    pub fn register_command(&self, name: &str, handler: CommandHandler) {
+        let packet = {
+            let mut commands = self.commands.lock().unwrap();
+            commands.register(name, handler);
+            commands.declare_commands_packet().clone()
+        };
+        self.broadcast(&packet);
+    }
+
+    /// Handler of the command called `name`, cloned so the lock isn't held while it runs
+    pub fn command(&self, name: &str) -> Option<CommandHandler> {
+        self.commands.lock().unwrap().get(name)
+    }
+
+    /// Declare Commands packet describing every registered command
+    pub fn declare_commands_packet(&self) -> BytesMut {
+        self.commands
+            .lock()
+            .unwrap()
+            .declare_commands_packet()
+            .clone()
+    }
+
+    pub fn entity_location(&self, entity_id: i32) -> Option<EntityLocation> {
+        self.entity_locations
+            .lock()
+            .unwrap()
+            .get(&entity_id)
+            .copied()
+    }
+
+    /// Moves an entity, returns where it was before if it was known
+    pub fn move_entity(&self, entity_id: i32, location: EntityLocation) -> Option<EntityLocation> {
+        self.entity_locations
+            .lock()
+            .unwrap()
+            .insert(entity_id, location)
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        *self.difficulty.lock().unwrap()
+    }
+
+    pub fn set_difficulty(&self, difficulty: Difficulty) {
+        *self.difficulty.lock().unwrap() = difficulty;
+    }
+
+    /// Registers a player that entered the Play state and returns its entity id
+    pub fn register_player(&self, username: &str, outbound: Outbound) -> i32 {
+        let entity_id = self.entity_ids.next();
+        self.players.lock().unwrap().insert(
+            entity_id,
+            PlayerHandle {
+                username: username.to_string(),
+                outbound,
+            },
+        );
+        self.metrics.player_joined();
+
+        debug!(entity_id, username, "Registered player");
+        entity_id
+    }
+
+    /// Asks the connection of every player named `username` to disconnect,
+    /// returns whether anyone was kicked
+    pub fn kick_by_username(&self, username: &str, reason: &str) -> bool {
+        let players = self.players.lock().unwrap();
+        let mut kicked = false;
+        for (&entity_id, player) in players.iter() {
+            if player.username == username {
+                debug!(entity_id, username, reason, "Kicking player");
+                player
+                    .outbound
+                    .send(OutboundMessage::Kick(reason.to_string()));
+                kicked = true;
+            }
+        }
+
+        kicked
+    }
+
+    pub fn remove_player(&self, entity_id: i32) {
+        if self.players.lock().unwrap().remove(&entity_id).is_some() {
+            self.metrics.player_left(entity_id);
+        }
+        debug!(entity_id, "Removed player");
+    }
+
+    /// Number of players currently in the Play state
+    pub fn online_count(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    /// Returns a copy of a chunk, generating it first if needed
+    pub fn chunk(&self, cx: i32, cz: i32) -> Chunk {
+        self.with_chunk(cx, cz, |chunk| chunk.clone())
+    }
+
+    pub fn get_block(&self, position: Position) -> i32 {
+        let (cx, cz) = Self::chunk_coords(position);
+        self.with_chunk(cx, cz, |chunk| {
+            chunk.get_block(position.x, position.y, position.z)
+        })
+    }
+
+    pub fn set_block(&self, position: Position, block_state: i32) {
+        let (cx, cz) = Self::chunk_coords(position);
+        self.with_chunk(cx, cz, |chunk| {
+            chunk.set_block(position.x, position.y, position.z, block_state)
+        })
+    }
+
+    fn with_chunk<T>(&self, cx: i32, cz: i32, f: impl FnOnce(&mut Chunk) -> T) -> T {
+        let mut chunks = self.chunks.lock().unwrap();
+        let chunk = chunks
+            .entry((cx, cz))
+            .or_insert_with(|| self.provider.get_chunk(cx, cz));
+
+        f(chunk)
+    }
+
+    fn chunk_coords(position: Position) -> (i32, i32) {
+        (
+            position.x.div_euclid(CHUNK_WIDTH),
+            position.z.div_euclid(CHUNK_WIDTH),
+        )
+    }
+
+    /// Queues a packet for every player
+    pub fn broadcast(&self, packet: &BytesMut) {
+        let players = self.players.lock().unwrap();
+        for player in players.values() {
+            player
+                .outbound
+                .send(OutboundMessage::Packet(packet.clone()));
+        }
+    }
+
+    /// Shows a system message in every player's chat
+    pub fn broadcast_system_chat(&self, component: &Component) {
+        self.broadcast(&write_system_chat_packet(component, false));
+    }
+
+    /// Queues a packet for every player except `except`
+    pub fn broadcast_except(&self, except: i32, packet: &BytesMut) {
+        let players = self.players.lock().unwrap();
+        for (&entity_id, player) in players.iter() {
+            if entity_id == except {
+                continue;
+            }
+
+            player
+                .outbound
+                .send(OutboundMessage::Packet(packet.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashed_seed() {
+        // sha-256 of eight zero bytes is af5570f5a1810b7a..., read little-endian
+        assert_eq!(hashed_seed(0), 8794265229978523055);
+        assert_eq!(hashed_seed(12345), 293737985876514017);
+        assert_eq!(hashed_seed(-4172144997902289642), 2159143436479834350);
+    }
+
+    #[test]
+    fn test_entity_ids_are_distinct_and_increasing() {
+        let world = SharedWorld::new();
+        let (tx, _rx) = Outbound::channel(16);
+
+        let first = world.entity_ids().next();
+        let player = world.register_player("Steve", tx);
+        let last = world.entity_ids().next();
+        assert_eq!(first, 1);
+        assert!(first < player && player < last);
+    }
+
+    #[test]
+    fn test_system_chat_reaches_every_player() {
+        let world = SharedWorld::new();
+        let (first_tx, mut first_rx) = Outbound::channel(16);
+        let (second_tx, mut second_rx) = Outbound::channel(16);
+        world.register_player("first", first_tx);
+        world.register_player("second", second_tx);
+
+        let message = Component::text("Hello").color("yellow");
+        world.broadcast_system_chat(&message);
+
+        let expected = OutboundMessage::Packet(write_system_chat_packet(&message, false));
+        assert_eq!(first_rx.try_recv().unwrap(), expected);
+        assert_eq!(second_rx.try_recv().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_weather_change_is_broadcast_on_tick() {
+        let world = SharedWorld::new();
+        let (tx, mut rx) = Outbound::channel(16);
+        world.register_player("Steve", tx);
+
+        world.set_weather(Weather::rain());
+        assert!(rx.try_recv().is_err());
+
+        world.tick();
+        for packet in write_weather_packets(&Weather::rain()) {
+            assert_eq!(rx.try_recv().unwrap(), OutboundMessage::Packet(packet));
+        }
+
+        // nothing changed since
+        world.tick();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_signals_overflow() {
+        let world = SharedWorld::new();
+        let (tx, mut rx) = Outbound::channel(2);
+        world.register_player("Steve", tx.clone());
+
+        let packet = BytesMut::from(&[0x42][..]);
+        world.broadcast(&packet);
+        world.broadcast(&packet);
+        // nothing is signalled while the messages fit
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(10), tx.overflowed())
+                .await
+                .is_err()
+        );
+
+        world.broadcast(&packet);
+        tokio::time::timeout(std::time::Duration::from_secs(1), tx.overflowed())
+            .await
+            .unwrap();
+        // the message that didn't fit was dropped
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,65 @@
+/// Block position in the world
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Returns the position next to this one on the given block face
+    // 0 = bottom (-Y), 1 = top (+Y), 2 = north (-Z), 3 = south (+Z), 4 = west (-X), 5 = east (+X)
+    pub fn offset(self, face: i32) -> Self {
+        let (dx, dy, dz) = match face {
+            0 => (0, -1, 0),
+            1 => (0, 1, 0),
+            2 => (0, 0, -1),
+            3 => (0, 0, 1),
+            4 => (-1, 0, 0),
+            5 => (1, 0, 0),
+            _ => (0, 0, 0),
+        };
+
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+
+    // Positions are packed in a single long
+    // x  26 bits (most significant)
+    // z  26 bits
+    // y  12 bits (least significant)
+    pub fn to_packed(self) -> i64 {
+        ((self.x as i64 & 0x3FF_FFFF) << 38)
+            | ((self.z as i64 & 0x3FF_FFFF) << 12)
+            | (self.y as i64 & 0xFFF)
+    }
+
+    pub fn from_packed(value: i64) -> Self {
+        Self {
+            x: (value >> 38) as i32,
+            y: (value << 52 >> 52) as i32,
+            z: (value << 26 >> 38) as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_packing() {
+        // example from the protocol documentation
+        let position = Position::new(18357644, 831, -20882616);
+        let packed = position.to_packed();
+
+        assert_eq!(
+            packed.to_be_bytes(),
+            [0x46, 0x07, 0x63, 0x2C, 0x15, 0xB4, 0x83, 0x3F]
+        );
+        assert_eq!(Position::from_packed(packed), position);
+    }
+}
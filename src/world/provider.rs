@@ -0,0 +1,109 @@
+use super::chunk::{Chunk, CHUNK_WIDTH};
+
+// Block state ids used by the default flat world
+pub const BEDROCK: i32 = 79;
+pub const DIRT: i32 = 10;
+pub const GRASS_BLOCK: i32 = 9;
+
+/// Source of the terrain a world starts out with
+pub trait ChunkProvider: Send + Sync {
+    fn get_chunk(&self, cx: i32, cz: i32) -> Chunk;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatLayer {
+    pub block_state: i32,
+    pub thickness: i32,
+}
+
+impl FlatLayer {
+    pub fn new(block_state: i32, thickness: i32) -> Self {
+        Self {
+            block_state,
+            thickness,
+        }
+    }
+}
+
+/// Generates a superflat world, stacking layers upwards from the bottom of the world
+pub struct FlatChunkProvider {
+    min_y: i32,
+    height: i32,
+    layers: Vec<FlatLayer>,
+}
+
+impl FlatChunkProvider {
+    pub fn new(min_y: i32, height: i32, layers: Vec<FlatLayer>) -> Self {
+        Self {
+            min_y,
+            height,
+            layers,
+        }
+    }
+}
+
+impl Default for FlatChunkProvider {
+    /// Vanilla's classic flat preset in an overworld sized dimension
+    fn default() -> Self {
+        Self::new(
+            -64,
+            384,
+            vec![
+                FlatLayer::new(BEDROCK, 1),
+                FlatLayer::new(DIRT, 2),
+                FlatLayer::new(GRASS_BLOCK, 1),
+            ],
+        )
+    }
+}
+
+impl ChunkProvider for FlatChunkProvider {
+    fn get_chunk(&self, cx: i32, cz: i32) -> Chunk {
+        let mut chunk = Chunk::new(cx, cz, self.min_y, self.height);
+
+        let mut y = self.min_y;
+        for layer in &self.layers {
+            for _ in 0..layer.thickness {
+                for z in 0..CHUNK_WIDTH {
+                    for x in 0..CHUNK_WIDTH {
+                        chunk.set_block(x, y, z, layer.block_state);
+                    }
+                }
+                y += 1;
+            }
+        }
+
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{AIR, STONE};
+
+    #[test]
+    fn test_flat_provider_layers() {
+        let provider = FlatChunkProvider::new(
+            -64,
+            384,
+            vec![FlatLayer::new(BEDROCK, 1), FlatLayer::new(STONE, 16)],
+        );
+        let chunk = provider.get_chunk(3, -2);
+
+        assert_eq!((chunk.x, chunk.z), (3, -2));
+        assert_eq!(chunk.sections.len(), 24);
+
+        for (x, z) in [(0, 0), (15, 15), (7, 3)] {
+            assert_eq!(chunk.get_block(x, -64, z), BEDROCK);
+            // stone crosses into the second section
+            assert_eq!(chunk.get_block(x, -63, z), STONE);
+            assert_eq!(chunk.get_block(x, -48, z), STONE);
+            assert_eq!(chunk.get_block(x, -47, z), AIR);
+        }
+
+        assert_eq!(chunk.sections[0].block_count(), 16 * 16 * 16);
+        assert_eq!(chunk.sections[1].block_count(), 16 * 16);
+        assert_eq!(chunk.sections[2].block_count(), 0);
+    }
+}